@@ -1,3 +0,0 @@
-pub fn run() {
-    println!("'bok check' is not yet implemented");
-}
@@ -0,0 +1,208 @@
+//! LLM chapter completeness: `bok check completeness`, also part of `bok check --llm`
+
+use super::{Book, Check, Finding, Severity};
+use crate::commands::generate::render_section;
+use crate::config::Settings;
+use crate::llm::{CompletenessReport, Llm, get_llm_provider};
+use crate::node::{Node, NodeManager};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde::Serialize;
+
+pub struct Completeness;
+
+#[async_trait]
+impl Check for Completeness {
+    fn name(&self) -> &'static str {
+        "completeness"
+    }
+
+    fn description(&self) -> &'static str {
+        "Gaps, announced but missing topics and missing sections in each chapter"
+    }
+
+    fn needs_llm(&self) -> bool {
+        true
+    }
+
+    async fn run(&self, book: &Book, llm: Option<&Llm>) -> Result<Vec<Finding>> {
+        let llm = llm.ok_or_else(|| anyhow!("The {} check needs an LLM", self.name()))?;
+        let mut findings = Vec::new();
+        let scanned = |id: &str| book.nodes.iter().find(|node| node.id == id);
+        for chapter in chapters(None)? {
+            let (outline, unreadable) =
+                outline(&chapter, |node| scanned(&node.id)?.content.clone())?;
+            for id in &unreadable {
+                if let Some(node) = scanned(id) {
+                    findings.push(
+                        Finding::new(
+                            self.name(),
+                            Severity::Warning,
+                            "text.qmd is missing, the chapter was assessed without it".to_string(),
+                        )
+                        .at(node),
+                    );
+                }
+            }
+            let report = llm.assess_completeness(&outline).await?;
+            let Some(scanned) = scanned(&chapter.id) else {
+                continue;
+            };
+            let messages = report
+                .gaps
+                .iter()
+                .map(|gap| format!("gap: {}", gap))
+                .chain(
+                    report
+                        .missing
+                        .iter()
+                        .map(|missing| format!("missing: {}", missing)),
+                )
+                .chain(
+                    report
+                        .suggested_nodes
+                        .iter()
+                        .map(|suggested| format!("could use a section '{}'", suggested.blurb)),
+                );
+            for message in messages {
+                findings.push(Finding::new(self.name(), Severity::Warning, message).at(scanned));
+            }
+        }
+        Ok(findings)
+    }
+}
+
+/// The chapter with the given id, or every child of the starting node, with their subtrees
+fn chapters(node_hash: Option<&str>) -> Result<Vec<Node>> {
+    match node_hash {
+        Some(node_hash) => {
+            let mut node = NodeManager::load_node(node_hash).map_err(|e| anyhow!(e))?;
+            node.children = NodeManager::get_nodes_recursive(&node.path);
+            Ok(vec![node])
+        }
+        None => {
+            let (starting_node, _) = NodeManager::get_book_tree().map_err(|e| anyhow!(e))?;
+            let starting_node =
+                starting_node.ok_or_else(|| anyhow!("The book has no starting node"))?;
+            Ok(starting_node.children)
+        }
+    }
+}
+
+/// The chapter as markdown, rendered the way `bok generate quarto` renders it.
+/// Nodes for which `content` has no text are rendered without one; their ids come second.
+fn outline(
+    chapter: &Node,
+    content: impl Fn(&Node) -> Option<String>,
+) -> Result<(String, Vec<String>)> {
+    let mut unreadable = Vec::new();
+    let mut out = String::new();
+    render_section(&mut out, chapter, 1, &mut |node| {
+        Ok(content(node).unwrap_or_else(|| {
+            unreadable.push(node.id.clone());
+            String::new()
+        }))
+    })?;
+    Ok((out, unreadable))
+}
+
+#[derive(Serialize)]
+struct ChapterReport {
+    node: String,
+    blurb: String,
+    #[serde(flatten)]
+    report: CompletenessReport,
+    /// Ids of the stub nodes created for the suggestions
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    created: Vec<String>,
+}
+
+/// `bok check completeness`: report on one chapter or all of them, optionally creating
+/// the suggested child nodes as empty stubs
+pub async fn run(node_hash: Option<&str>, create_stubs: bool, json: bool) -> Result<()> {
+    let settings = Settings::new()?;
+    let llm = get_llm_provider(&settings.llm)?;
+
+    let mut reports = Vec::new();
+    for chapter in chapters(node_hash)? {
+        let (outline, unreadable) = outline(&chapter, |node| node.content().ok())?;
+        for id in &unreadable {
+            eprintln!(
+                "Warning: node {} has no readable text.qmd, assessing the chapter without it.",
+                id
+            );
+        }
+        let report = llm.assess_completeness(&outline).await?;
+        let created = if create_stubs {
+            create_stub_nodes(&chapter, &report)?
+        } else {
+            Vec::new()
+        };
+        reports.push(ChapterReport {
+            node: chapter.id.clone(),
+            blurb: chapter.blurb().to_string(),
+            report,
+            created,
+        });
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+        return Ok(());
+    }
+
+    for chapter in &reports {
+        println!("{} {}", chapter.node, chapter.blurb);
+        let report = &chapter.report;
+        if report.gaps.is_empty() && report.missing.is_empty() && report.suggested_nodes.is_empty()
+        {
+            println!("  Looks complete.");
+        }
+        for gap in &report.gaps {
+            println!("  gap: {}", gap);
+        }
+        for missing in &report.missing {
+            println!("  missing: {}", missing);
+        }
+        for suggested in &report.suggested_nodes {
+            println!(
+                "  suggested node: {} ({})",
+                suggested.blurb, suggested.reason
+            );
+        }
+        for id in &chapter.created {
+            println!("  created stub node {}", id);
+        }
+    }
+    if !create_stubs
+        && reports
+            .iter()
+            .any(|chapter| !chapter.report.suggested_nodes.is_empty())
+    {
+        println!("Run with --create-stubs to add the suggested nodes as empty stubs.");
+    }
+
+    Ok(())
+}
+
+/// Append the suggested nodes as empty children of the chapter, after its last child
+fn create_stub_nodes(chapter: &Node, report: &CompletenessReport) -> Result<Vec<String>> {
+    // Several children without `after` have no defined last one, fix their order first
+    let mut children = chapter.children.clone();
+    NodeManager::pin_order(&mut children).map_err(|e| anyhow!(e))?;
+    let mut last_child = children.last().map(|child| child.id.clone());
+    let mut created = Vec::new();
+    for suggested in &report.suggested_nodes {
+        let id = NodeManager::create_node(
+            &suggested.blurb,
+            "",
+            Some(&chapter.id),
+            last_child.as_deref(),
+        )
+        .map_err(|e| anyhow!("Failed to create stub '{}': {}", suggested.blurb, e))?
+        .to_string();
+        last_child = Some(id.clone());
+        created.push(id);
+    }
+    Ok(created)
+}
@@ -0,0 +1,342 @@
+//! Near-duplicate nodes: `bok check duplicates`, also part of `bok check`
+//!
+//! Candidates are found locally with MinHash over word shingles of each node's text,
+//! bucketed with locality-sensitive hashing so that not every pair has to be compared.
+//! With `--semantic`, the cached node embeddings are compared instead.
+//! An LLM can then confirm or reject the candidates.
+
+use super::{Book, Check, Finding, ScannedNode, Severity};
+use crate::config::Settings;
+use crate::embeddings::EmbeddingStore;
+use crate::llm::{Llm, cosine_similarity, get_llm_provider};
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use murmur3::murmur3_32;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Cursor;
+
+/// Words per shingle
+const SHINGLE_WORDS: usize = 3;
+/// LSH bands times rows per band gives the signature length. With 32 bands of 4 rows,
+/// pairs become candidates from an estimated similarity of about 0.4 on.
+const BANDS: usize = 32;
+const ROWS: usize = 4;
+
+/// Default minimum estimated Jaccard similarity for a pair to be reported
+pub const DEFAULT_THRESHOLD: f64 = 0.5;
+/// Default minimum cosine similarity of the embeddings with `--semantic`
+pub const DEFAULT_SEMANTIC_THRESHOLD: f64 = 0.9;
+
+pub struct Duplicates;
+
+#[async_trait]
+impl Check for Duplicates {
+    fn name(&self) -> &'static str {
+        "duplicates"
+    }
+
+    fn description(&self) -> &'static str {
+        "Nodes whose texts are near duplicates of each other"
+    }
+
+    async fn run(&self, book: &Book, _llm: Option<&Llm>) -> Result<Vec<Finding>> {
+        Ok(similar_pairs(book, DEFAULT_THRESHOLD)
+            .into_iter()
+            .map(|pair| {
+                Finding::new(
+                    self.name(),
+                    Severity::Warning,
+                    format!(
+                        "near duplicate of {} {} (similarity {:.2})",
+                        pair.second.id, pair.second.dir_title, pair.similarity
+                    ),
+                )
+                .at(pair.first)
+            })
+            .collect())
+    }
+}
+
+/// Two nodes with an estimated similarity at or above the threshold
+struct Pair<'a> {
+    first: &'a ScannedNode,
+    second: &'a ScannedNode,
+    similarity: f64,
+}
+
+/// Lowercased words of the text, without punctuation
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Hashes of the text's word shingles. Texts shorter than a shingle are one shingle.
+fn shingles(text: &str) -> BTreeSet<u32> {
+    let words = words(text);
+    if words.is_empty() {
+        return BTreeSet::new();
+    }
+    words
+        .windows(SHINGLE_WORDS.min(words.len()))
+        .map(|window| hash(window.join(" ").as_bytes(), 0))
+        .collect()
+}
+
+fn hash(bytes: &[u8], seed: u32) -> u32 {
+    murmur3_32(&mut Cursor::new(bytes), seed).unwrap_or(0)
+}
+
+/// For each seeded hash function, the smallest hash of any shingle
+fn signature(shingles: &BTreeSet<u32>) -> Vec<u32> {
+    (0..(BANDS * ROWS) as u32)
+        .map(|seed| {
+            shingles
+                .iter()
+                .map(|shingle| hash(&shingle.to_le_bytes(), seed + 1))
+                .min()
+                .unwrap_or(u32::MAX)
+        })
+        .collect()
+}
+
+/// The share of equal minimums estimates the Jaccard similarity of the shingle sets
+fn estimate(a: &[u32], b: &[u32]) -> f64 {
+    let equal = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    equal as f64 / a.len() as f64
+}
+
+/// Pairs of nodes with non-empty texts whose estimated similarity reaches the threshold,
+/// most similar first
+fn similar_pairs(book: &Book, threshold: f64) -> Vec<Pair<'_>> {
+    let signed: Vec<(&ScannedNode, Vec<u32>)> = book
+        .nodes
+        .iter()
+        .filter_map(|node| {
+            let shingles = shingles(node.content.as_deref()?);
+            (!shingles.is_empty()).then(|| (node, signature(&shingles)))
+        })
+        .collect();
+
+    // Nodes sharing all rows of any band are candidates
+    let mut candidates: BTreeSet<(usize, usize)> = BTreeSet::new();
+    for band in 0..BANDS {
+        let mut buckets: HashMap<&[u32], Vec<usize>> = HashMap::new();
+        for (i, (_, signature)) in signed.iter().enumerate() {
+            buckets
+                .entry(&signature[band * ROWS..(band + 1) * ROWS])
+                .or_default()
+                .push(i);
+        }
+        for bucket in buckets.values() {
+            for (n, &i) in bucket.iter().enumerate() {
+                for &j in &bucket[n + 1..] {
+                    candidates.insert((i, j));
+                }
+            }
+        }
+    }
+
+    let mut pairs: Vec<Pair> = candidates
+        .into_iter()
+        .filter_map(|(i, j)| {
+            let similarity = estimate(&signed[i].1, &signed[j].1);
+            (similarity >= threshold).then_some(Pair {
+                first: signed[i].0,
+                second: signed[j].0,
+                similarity,
+            })
+        })
+        .collect();
+    pairs.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    pairs
+}
+
+/// Pairs of nodes whose embeddings reach the threshold, most similar first
+fn semantic_pairs<'a>(
+    book: &'a Book,
+    embeddings: &[(String, String, Vec<f32>)],
+    threshold: f64,
+) -> Vec<Pair<'a>> {
+    let embedded: Vec<(&ScannedNode, &[f32])> = embeddings
+        .iter()
+        .filter_map(|(id, _, vector)| {
+            let node = book.nodes.iter().find(|node| node.id == *id)?;
+            Some((node, vector.as_slice()))
+        })
+        .collect();
+
+    let mut pairs = Vec::new();
+    for (n, (first, a)) in embedded.iter().enumerate() {
+        for (second, b) in &embedded[n + 1..] {
+            let similarity = cosine_similarity(a, b) as f64;
+            if similarity >= threshold {
+                pairs.push(Pair {
+                    first,
+                    second,
+                    similarity,
+                });
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    pairs
+}
+
+#[derive(Serialize)]
+struct ClusterNode {
+    id: String,
+    blurb: String,
+}
+
+#[derive(Serialize)]
+struct ClusterPair {
+    first: String,
+    second: String,
+    similarity: f64,
+    /// Why the LLM considers the pair duplicates, with `--llm`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+/// Nodes connected by similar pairs
+#[derive(Serialize)]
+struct Cluster {
+    nodes: Vec<ClusterNode>,
+    pairs: Vec<ClusterPair>,
+}
+
+/// Group the pairs into clusters of connected nodes, largest similarity first
+fn clusters(pairs: Vec<(Pair, Option<String>)>) -> Vec<Cluster> {
+    let mut cluster_of: HashMap<&str, usize> = HashMap::new();
+    let mut clusters: Vec<(BTreeMap<&str, &str>, Vec<ClusterPair>)> = Vec::new();
+
+    for (pair, reason) in &pairs {
+        let (first, second) = (pair.first.id.as_str(), pair.second.id.as_str());
+        let index = match (cluster_of.get(first), cluster_of.get(second)) {
+            (Some(&a), Some(&b)) if a != b => {
+                // Join the second cluster into the first
+                let (nodes, merged) = std::mem::take(&mut clusters[b]);
+                for id in nodes.keys() {
+                    cluster_of.insert(*id, a);
+                }
+                clusters[a].0.extend(nodes);
+                clusters[a].1.extend(merged);
+                a
+            }
+            (Some(&a), _) | (None, Some(&a)) => a,
+            (None, None) => {
+                clusters.push(Default::default());
+                clusters.len() - 1
+            }
+        };
+        for node in [pair.first, pair.second] {
+            cluster_of.insert(&node.id, index);
+            clusters[index].0.insert(&node.id, &node.dir_title);
+        }
+        clusters[index].1.push(ClusterPair {
+            first: first.to_string(),
+            second: second.to_string(),
+            similarity: (pair.similarity * 100.0).round() / 100.0,
+            reason: reason.clone(),
+        });
+    }
+
+    clusters
+        .into_iter()
+        .filter(|(nodes, _)| !nodes.is_empty())
+        .map(|(nodes, pairs)| Cluster {
+            nodes: nodes
+                .into_iter()
+                .map(|(id, blurb)| ClusterNode {
+                    id: id.to_string(),
+                    blurb: blurb.to_string(),
+                })
+                .collect(),
+            pairs,
+        })
+        .collect()
+}
+
+/// `bok check duplicates`: report clusters of near-duplicate nodes, found by shingles or
+/// by embeddings, optionally keeping only the pairs the LLM confirms
+pub async fn run(threshold: Option<f64>, semantic: bool, use_llm: bool, json: bool) -> Result<()> {
+    if let Some(threshold) = threshold
+        && !(0.0..=1.0).contains(&threshold)
+    {
+        bail!("The threshold must be between 0 and 1, got {}.", threshold);
+    }
+    let book = Book::scan()?;
+    let settings = Settings::new()?;
+    let llm = if semantic || use_llm {
+        Some(get_llm_provider(&settings.llm)?)
+    } else {
+        None
+    };
+
+    let pairs = match &llm {
+        Some(llm) if semantic => {
+            let mut store = EmbeddingStore::load(&settings.llm);
+            let (embeddings, _) = store.embed_book(llm).await?;
+            semantic_pairs(
+                &book,
+                &embeddings,
+                threshold.unwrap_or(DEFAULT_SEMANTIC_THRESHOLD),
+            )
+        }
+        _ => similar_pairs(&book, threshold.unwrap_or(DEFAULT_THRESHOLD)),
+    };
+    let candidates = pairs.len();
+
+    let mut confirmed = Vec::new();
+    if let Some(llm) = llm.as_ref().filter(|_| use_llm) {
+        for pair in pairs {
+            let first = pair.first.content.as_deref().unwrap_or_default();
+            let second = pair.second.content.as_deref().unwrap_or_default();
+            if let Some(reason) = llm.assess_duplicate(first, second).await? {
+                confirmed.push((pair, Some(reason)));
+            }
+        }
+    } else {
+        confirmed.extend(pairs.into_iter().map(|pair| (pair, None)));
+    }
+    let rejected = candidates - confirmed.len();
+    let clusters = clusters(confirmed);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&clusters)?);
+        return Ok(());
+    }
+
+    if clusters.is_empty() {
+        println!("No duplicates found.");
+    }
+    for (i, cluster) in clusters.iter().enumerate() {
+        println!("Cluster {}:", i + 1);
+        for node in &cluster.nodes {
+            println!("  {} {}", node.id, node.blurb);
+        }
+        for pair in &cluster.pairs {
+            println!(
+                "  {:.2}  {} <-> {}",
+                pair.similarity, pair.first, pair.second
+            );
+            if let Some(reason) = &pair.reason {
+                println!("        {}", reason);
+            }
+        }
+    }
+    if use_llm && rejected > 0 {
+        println!(
+            "{} candidate pair(s) were not confirmed by the LLM.",
+            rejected
+        );
+    }
+    if !clusters.is_empty() {
+        println!("Use 'bok node merge <into> <from>' to merge a pair.");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! Checks that ask the LLM, only run with `bok check --llm`
+
+use super::{Book, Check, Finding, Severity};
+use crate::llm::Llm;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+
+pub struct BlurbFit;
+
+#[async_trait]
+impl Check for BlurbFit {
+    fn name(&self) -> &'static str {
+        "blurb-fit"
+    }
+
+    fn description(&self) -> &'static str {
+        "Blurbs that no longer describe their node's text"
+    }
+
+    fn needs_llm(&self) -> bool {
+        true
+    }
+
+    async fn run(&self, book: &Book, llm: Option<&Llm>) -> Result<Vec<Finding>> {
+        let llm = llm.ok_or_else(|| anyhow!("The {} check needs an LLM", self.name()))?;
+        let mut findings = Vec::new();
+        for scanned in &book.nodes {
+            let (Some(node), Some(content)) = (scanned.node(), &scanned.content) else {
+                continue;
+            };
+            // Fresh nodes only repeat their blurb, there is nothing to compare
+            if content.trim().is_empty() || content.trim() == node.blurb() {
+                continue;
+            }
+            if let Some(reason) = llm.assess_blurb(node.blurb(), content).await? {
+                findings.push(Finding::new(self.name(), Severity::Warning, reason).at(scanned));
+            }
+        }
+        Ok(findings)
+    }
+}
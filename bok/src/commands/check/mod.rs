@@ -0,0 +1,267 @@
+pub mod completeness;
+pub mod duplicates;
+mod llm;
+mod structure;
+
+use crate::config::{FullConfig, Settings, book_root};
+use crate::llm::{Llm, get_llm_provider};
+use crate::node::Node;
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// One problem reported by a check
+#[derive(Debug, Serialize)]
+pub struct Finding {
+    pub check: &'static str,
+    pub severity: Severity,
+    /// The node the finding is about, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    pub message: String,
+}
+
+impl Finding {
+    pub fn new(check: &'static str, severity: Severity, message: String) -> Self {
+        Finding {
+            check,
+            severity,
+            node: None,
+            path: None,
+            message,
+        }
+    }
+
+    /// Attach the finding to a node
+    pub fn at(mut self, node: &ScannedNode) -> Self {
+        self.node = Some(node.id.clone());
+        self.path = Some(node.path.clone());
+        self
+    }
+}
+
+/// A node directory as found on disk, loaded as far as possible so that broken nodes
+/// can be reported instead of being skipped
+pub struct ScannedNode {
+    pub id: String,
+    /// The title part of the directory name
+    pub dir_title: String,
+    pub path: PathBuf,
+    /// Id of the enclosing node, None at the top level
+    pub parent: Option<String>,
+    /// The node loaded from meta.yaml: None if meta.yaml is missing, Err if it is unreadable
+    pub meta: Option<Result<Node, String>>,
+    /// Contents of text.qmd, None if it is missing
+    pub content: Option<String>,
+}
+
+impl ScannedNode {
+    pub fn node(&self) -> Option<&Node> {
+        self.meta.as_ref().and_then(|meta| meta.as_ref().ok())
+    }
+}
+
+/// Everything checks get to look at
+pub struct Book {
+    pub config: FullConfig,
+    /// All node directories, parents before their children
+    pub nodes: Vec<ScannedNode>,
+}
+
+impl Book {
+    pub fn scan() -> Result<Book> {
+        let config = FullConfig::load()?;
+        let mut nodes = Vec::new();
+        Self::scan_dir(book_root(), None, &mut nodes);
+        Ok(Book { config, nodes })
+    }
+
+    /// Directories named `<id> <title>` with a numeric id, or containing a meta.yaml,
+    /// are node directories
+    fn scan_dir(dir: &Path, parent: Option<&str>, nodes: &mut Vec<ScannedNode>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !path.is_dir() || name.starts_with('.') {
+                continue;
+            }
+            let (id, dir_title) = name.split_once(' ').unwrap_or((&name, ""));
+            let meta_path = path.join("meta.yaml");
+            let looks_like_node = !id.is_empty() && id.chars().all(|c| c.is_ascii_digit());
+            if !looks_like_node && !meta_path.exists() {
+                continue;
+            }
+
+            let meta = meta_path
+                .exists()
+                .then(|| Node::from_meta(&meta_path, id.to_string()));
+            let content = fs::read_to_string(path.join("text.qmd")).ok();
+            nodes.push(ScannedNode {
+                id: id.to_string(),
+                dir_title: dir_title.to_string(),
+                path: path.clone(),
+                parent: parent.map(String::from),
+                meta,
+                content,
+            });
+            Self::scan_dir(&path, Some(id), nodes);
+        }
+    }
+
+    /// Nodes directly below `parent` (None for the top level)
+    pub fn children_of<'a>(
+        &'a self,
+        parent: Option<&'a str>,
+    ) -> impl Iterator<Item = &'a ScannedNode> + 'a {
+        self.nodes
+            .iter()
+            .filter(move |node| node.parent.as_deref() == parent)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.nodes.iter().any(|node| node.id == id)
+    }
+}
+
+#[async_trait]
+pub trait Check: Send + Sync {
+    /// Short kebab-case name, used in reports and with `--only`
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    /// LLM-backed checks only run with `--llm`
+    fn needs_llm(&self) -> bool {
+        false
+    }
+
+    async fn run(&self, book: &Book, llm: Option<&Llm>) -> Result<Vec<Finding>>;
+}
+
+/// All known checks, in reporting order
+fn all_checks() -> Vec<Box<dyn Check>> {
+    vec![
+        Box::new(structure::NodeFiles),
+        Box::new(structure::DuplicateIds),
+        Box::new(structure::TitleMismatch),
+        Box::new(structure::DanglingAfter),
+        Box::new(structure::Ordering),
+        Box::new(structure::DanglingRelations),
+        Box::new(structure::EmptyNodes),
+        Box::new(structure::StartingNode),
+        Box::new(duplicates::Duplicates),
+        Box::new(llm::BlurbFit),
+        Box::new(completeness::Completeness),
+    ]
+}
+
+pub struct CheckOptions {
+    /// Run only the checks with these names
+    pub only: Vec<String>,
+    /// Also run the LLM-backed checks that are not named in `only`
+    pub llm: bool,
+    pub json: bool,
+    /// Treat warnings as failures
+    pub strict: bool,
+}
+
+pub fn list() {
+    for check in all_checks() {
+        let llm_note = if check.needs_llm() { " (--llm)" } else { "" };
+        println!("{:<20} {}{}", check.name(), check.description(), llm_note);
+    }
+}
+
+/// Run the checks and report the findings. Returns whether the book passed.
+pub async fn run(options: &CheckOptions) -> Result<bool> {
+    let checks = all_checks();
+    for name in &options.only {
+        if !checks.iter().any(|check| check.name() == name) {
+            bail!("Unknown check '{}'. See 'bok check --list'.", name);
+        }
+    }
+    let named = |check: &dyn Check| options.only.iter().any(|n| n == check.name());
+    // LLM-backed checks run with --llm or when asked for by name
+    let selected: Vec<&dyn Check> = checks
+        .iter()
+        .map(Box::as_ref)
+        .filter(|check| options.only.is_empty() || named(*check))
+        .filter(|check| options.llm || !check.needs_llm() || named(*check))
+        .collect();
+
+    let book = Book::scan()?;
+    let llm = if selected.iter().any(|check| check.needs_llm()) {
+        Some(get_llm_provider(&Settings::new()?.llm)?)
+    } else {
+        None
+    };
+
+    let mut findings = Vec::new();
+    for check in selected {
+        findings.extend(check.run(&book, llm.as_ref()).await?);
+    }
+
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    let warnings = findings.len() - errors;
+
+    if options.json {
+        #[derive(Serialize)]
+        struct Report<'a> {
+            errors: usize,
+            warnings: usize,
+            findings: &'a [Finding],
+        }
+        let report = Report {
+            errors,
+            warnings,
+            findings: &findings,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if findings.is_empty() {
+        println!("No problems found.");
+    } else {
+        for finding in &findings {
+            print_finding(finding);
+        }
+        println!("{} error(s), {} warning(s)", errors, warnings);
+    }
+
+    Ok(errors == 0 && (!options.strict || warnings == 0))
+}
+
+fn print_finding(finding: &Finding) {
+    let severity = match finding.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    };
+    match &finding.path {
+        Some(path) => println!(
+            "{:<7} [{}] {}: {}",
+            severity,
+            finding.check,
+            path.display(),
+            finding.message
+        ),
+        None => println!("{:<7} [{}] {}", severity, finding.check, finding.message),
+    }
+}
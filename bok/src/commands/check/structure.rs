@@ -0,0 +1,353 @@
+//! Checks of the book's structure on disk, no LLM needed
+
+use super::{Book, Check, Finding, Severity};
+use crate::llm::Llm;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::{BTreeSet, HashMap};
+
+pub struct NodeFiles;
+
+#[async_trait]
+impl Check for NodeFiles {
+    fn name(&self) -> &'static str {
+        "node-files"
+    }
+
+    fn description(&self) -> &'static str {
+        "Node directories with a missing or unreadable meta.yaml or text.qmd"
+    }
+
+    async fn run(&self, book: &Book, _llm: Option<&Llm>) -> Result<Vec<Finding>> {
+        let mut findings = Vec::new();
+        for node in &book.nodes {
+            let problem = match &node.meta {
+                None => Some("meta.yaml is missing".to_string()),
+                Some(Err(e)) => Some(e.clone()),
+                Some(Ok(_)) => None,
+            };
+            if let Some(problem) = problem {
+                findings.push(Finding::new(self.name(), Severity::Error, problem).at(node));
+            }
+            if node.content.is_none() {
+                findings.push(
+                    Finding::new(
+                        self.name(),
+                        Severity::Error,
+                        "text.qmd is missing or unreadable".to_string(),
+                    )
+                    .at(node),
+                );
+            }
+        }
+        Ok(findings)
+    }
+}
+
+pub struct DuplicateIds;
+
+#[async_trait]
+impl Check for DuplicateIds {
+    fn name(&self) -> &'static str {
+        "duplicate-ids"
+    }
+
+    fn description(&self) -> &'static str {
+        "The same node id used by several directories"
+    }
+
+    async fn run(&self, book: &Book, _llm: Option<&Llm>) -> Result<Vec<Finding>> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for node in &book.nodes {
+            *counts.entry(&node.id).or_default() += 1;
+        }
+        Ok(book
+            .nodes
+            .iter()
+            .filter(|node| counts[node.id.as_str()] > 1)
+            .map(|node| {
+                Finding::new(
+                    self.name(),
+                    Severity::Error,
+                    format!(
+                        "id {} is used by {} directories",
+                        node.id,
+                        counts[node.id.as_str()]
+                    ),
+                )
+                .at(node)
+            })
+            .collect())
+    }
+}
+
+pub struct TitleMismatch;
+
+#[async_trait]
+impl Check for TitleMismatch {
+    fn name(&self) -> &'static str {
+        "title-mismatch"
+    }
+
+    fn description(&self) -> &'static str {
+        "Directory name and meta.title that disagree"
+    }
+
+    async fn run(&self, book: &Book, _llm: Option<&Llm>) -> Result<Vec<Finding>> {
+        let mut findings = Vec::new();
+        for scanned in &book.nodes {
+            if let Some(node) = scanned.node()
+                && node.blurb() != scanned.dir_title
+            {
+                findings.push(
+                    Finding::new(
+                        self.name(),
+                        Severity::Warning,
+                        format!(
+                            "directory title '{}' differs from meta.title '{}'",
+                            scanned.dir_title,
+                            node.blurb()
+                        ),
+                    )
+                    .at(scanned),
+                );
+            }
+        }
+        Ok(findings)
+    }
+}
+
+pub struct DanglingAfter;
+
+#[async_trait]
+impl Check for DanglingAfter {
+    fn name(&self) -> &'static str {
+        "dangling-after"
+    }
+
+    fn description(&self) -> &'static str {
+        "'after' references to nodes that are not siblings"
+    }
+
+    async fn run(&self, book: &Book, _llm: Option<&Llm>) -> Result<Vec<Finding>> {
+        let mut findings = Vec::new();
+        for scanned in &book.nodes {
+            let Some(after) = scanned.node().and_then(|node| node.after()) else {
+                continue;
+            };
+            let is_sibling = book
+                .children_of(scanned.parent.as_deref())
+                .any(|sibling| sibling.id == *after);
+            let message = if is_sibling {
+                continue;
+            } else if book.contains(after) {
+                format!("'after' points to {}, which is not a sibling", after)
+            } else {
+                format!("'after' points to {}, which does not exist", after)
+            };
+            findings.push(Finding::new(self.name(), Severity::Error, message).at(scanned));
+        }
+        Ok(findings)
+    }
+}
+
+pub struct Ordering;
+
+#[async_trait]
+impl Check for Ordering {
+    fn name(&self) -> &'static str {
+        "ordering"
+    }
+
+    fn description(&self) -> &'static str {
+        "Cycles in the 'after' chain and siblings placed after the same node"
+    }
+
+    async fn run(&self, book: &Book, _llm: Option<&Llm>) -> Result<Vec<Finding>> {
+        let mut findings = Vec::new();
+        let mut parents: BTreeSet<Option<&str>> = BTreeSet::from([None]);
+        parents.extend(book.nodes.iter().map(|node| Some(node.id.as_str())));
+
+        for parent in parents {
+            let siblings: Vec<_> = book.children_of(parent).collect();
+            let after: HashMap<&str, &str> = siblings
+                .iter()
+                .filter_map(|scanned| {
+                    let after = scanned.node()?.after()?;
+                    siblings
+                        .iter()
+                        .any(|sibling| sibling.id == *after)
+                        .then_some((scanned.id.as_str(), after.as_str()))
+                })
+                .collect();
+
+            // Follow each chain of predecessors; coming back to a node on the current path is a cycle
+            let mut cycles: BTreeSet<Vec<&str>> = BTreeSet::new();
+            for start in after.keys() {
+                let mut path = vec![*start];
+                let mut current = *start;
+                while let Some(&previous) = after.get(current) {
+                    if let Some(pos) = path.iter().position(|id| *id == previous) {
+                        let mut cycle = path[pos..].to_vec();
+                        cycle.sort();
+                        cycles.insert(cycle);
+                        break;
+                    }
+                    path.push(previous);
+                    current = previous;
+                }
+            }
+            for cycle in cycles {
+                let first = siblings.iter().find(|s| s.id == cycle[0]).unwrap();
+                findings.push(
+                    Finding::new(
+                        self.name(),
+                        Severity::Error,
+                        format!(
+                            "'after' references form a cycle: {}; these nodes are ordered arbitrarily",
+                            cycle.join(", ")
+                        ),
+                    )
+                    .at(first),
+                );
+            }
+
+            let mut followers: HashMap<&str, Vec<&str>> = HashMap::new();
+            for (node, previous) in &after {
+                followers.entry(previous).or_default().push(node);
+            }
+            let mut shared: Vec<_> = followers
+                .into_iter()
+                .filter(|(_, nodes)| nodes.len() > 1)
+                .collect();
+            shared.sort();
+            for (previous, mut nodes) in shared {
+                nodes.sort();
+                let predecessor = siblings.iter().find(|s| s.id == previous).unwrap();
+                findings.push(
+                    Finding::new(
+                        self.name(),
+                        Severity::Warning,
+                        format!(
+                            "{} are all placed after this node, their order is ambiguous",
+                            nodes.join(", ")
+                        ),
+                    )
+                    .at(predecessor),
+                );
+            }
+        }
+        Ok(findings)
+    }
+}
+
+pub struct DanglingRelations;
+
+#[async_trait]
+impl Check for DanglingRelations {
+    fn name(&self) -> &'static str {
+        "dangling-relations"
+    }
+
+    fn description(&self) -> &'static str {
+        "Relations to nodes that do not exist"
+    }
+
+    async fn run(&self, book: &Book, _llm: Option<&Llm>) -> Result<Vec<Finding>> {
+        let mut findings = Vec::new();
+        for scanned in &book.nodes {
+            let Some(node) = scanned.node() else {
+                continue;
+            };
+            for relation in node.relations() {
+                if !book.contains(&relation.target) {
+                    findings.push(
+                        Finding::new(
+                            self.name(),
+                            Severity::Warning,
+                            format!(
+                                "{} relation points to {}, which does not exist",
+                                relation.kind, relation.target
+                            ),
+                        )
+                        .at(scanned),
+                    );
+                }
+            }
+        }
+        Ok(findings)
+    }
+}
+
+pub struct EmptyNodes;
+
+#[async_trait]
+impl Check for EmptyNodes {
+    fn name(&self) -> &'static str {
+        "empty-nodes"
+    }
+
+    fn description(&self) -> &'static str {
+        "Nodes whose text.qmd is empty"
+    }
+
+    async fn run(&self, book: &Book, _llm: Option<&Llm>) -> Result<Vec<Finding>> {
+        Ok(book
+            .nodes
+            .iter()
+            .filter(|node| {
+                node.content
+                    .as_ref()
+                    .is_some_and(|content| content.trim().is_empty())
+            })
+            .map(|node| {
+                Finding::new(
+                    self.name(),
+                    Severity::Warning,
+                    "text.qmd is empty".to_string(),
+                )
+                .at(node)
+            })
+            .collect())
+    }
+}
+
+pub struct StartingNode;
+
+#[async_trait]
+impl Check for StartingNode {
+    fn name(&self) -> &'static str {
+        "starting-node"
+    }
+
+    fn description(&self) -> &'static str {
+        "starting_node in bok.yaml that is missing or not a top-level node"
+    }
+
+    async fn run(&self, book: &Book, _llm: Option<&Llm>) -> Result<Vec<Finding>> {
+        let finding = match &book.config.starting_node {
+            None => Some(Finding::new(
+                self.name(),
+                Severity::Error,
+                "bok.yaml has no starting_node".to_string(),
+            )),
+            Some(id) => match book.nodes.iter().find(|node| node.id == *id) {
+                None => Some(Finding::new(
+                    self.name(),
+                    Severity::Error,
+                    format!("starting_node {} in bok.yaml does not exist", id),
+                )),
+                Some(node) if node.parent.is_some() => Some(
+                    Finding::new(
+                        self.name(),
+                        Severity::Warning,
+                        "the starting node is not at the top level of the book".to_string(),
+                    )
+                    .at(node),
+                ),
+                Some(_) => None,
+            },
+        };
+        Ok(finding.into_iter().collect())
+    }
+}
@@ -1,3 +1,176 @@
-pub fn run(node: &str) {
-    println!("'bok copyedit {}' is not yet implemented", node);
+use crate::commands::review::Decisions;
+use crate::config::Settings;
+use crate::llm::{CopyeditSuggestion, get_llm_provider};
+use crate::node::NodeManager;
+use anyhow::{Result, anyhow};
+use std::fs;
+use std::ops::Range;
+
+/// What to do with the suggestions
+#[derive(Clone, Copy, PartialEq)]
+pub enum CopyeditAction {
+    /// Ask for each suggestion whether to apply it
+    Interactive,
+    /// Apply all suggestions
+    Accept,
+    /// Only list the suggestions
+    DryRun,
+    /// Print the suggestions as JSON, without applying any
+    Json,
+}
+
+pub async fn run(node_hash: &str, action: CopyeditAction) -> Result<()> {
+    let node_path = NodeManager::find_node_path(node_hash).map_err(|e| anyhow!(e))?;
+    let text_path = node_path.join("text.qmd");
+    let text = NodeManager::get_node_content(node_hash).map_err(|e| anyhow!(e))?;
+
+    let settings = Settings::new()?;
+    let llm_provider = get_llm_provider(&settings.llm)?;
+    let suggestions = locate_all(&text, llm_provider.copy_edit(&text, &settings.style).await?);
+
+    if action == CopyeditAction::Json {
+        let suggestions: Vec<&CopyeditSuggestion> = suggestions.iter().map(|(_, s)| s).collect();
+        println!("{}", serde_json::to_string_pretty(&suggestions)?);
+        return Ok(());
+    }
+    if suggestions.is_empty() {
+        println!("No copyedits suggested for node '{}'.", node_hash);
+        return Ok(());
+    }
+
+    let mut decisions = Decisions::default();
+    let mut accepted: Vec<(Range<usize>, &str)> = Vec::new();
+    for (i, (span, suggestion)) in suggestions.iter().enumerate() {
+        let apply = match action {
+            CopyeditAction::Accept => {
+                print_suggestion(&text, suggestion);
+                true
+            }
+            CopyeditAction::DryRun | CopyeditAction::Json => {
+                print_suggestion(&text, suggestion);
+                false
+            }
+            CopyeditAction::Interactive => {
+                if !decisions.settled() {
+                    print_suggestion(&text, suggestion);
+                }
+                decisions.decide(&format!(
+                    "Apply this suggestion ({}/{})?",
+                    i + 1,
+                    suggestions.len()
+                ))
+            }
+        };
+
+        if !apply {
+            continue;
+        }
+        if accepted.iter().any(|(other, _)| overlaps(other, span)) {
+            println!(
+                "Skipping suggestion at {}:{}, it overlaps an applied one.",
+                suggestion.line, suggestion.column
+            );
+            continue;
+        }
+        accepted.push((span.clone(), &suggestion.replacement));
+    }
+
+    if action == CopyeditAction::DryRun {
+        return Ok(());
+    }
+
+    let applied = accepted.len();
+    if applied > 0 {
+        // Suggestions are sorted by position, applying them back to front keeps the spans valid
+        let mut edited = text.clone();
+        for (span, replacement) in accepted.into_iter().rev() {
+            edited.replace_range(span, replacement);
+        }
+        fs::write(&text_path, edited)?;
+    }
+    println!(
+        "Applied {} of {} suggestions to '{}'.",
+        applied,
+        suggestions.len(),
+        text_path.display()
+    );
+    if !decisions.complete {
+        println!("Review interrupted, the remaining suggestions were skipped.");
+    }
+
+    Ok(())
+}
+
+fn print_suggestion(text: &str, suggestion: &CopyeditSuggestion) {
+    println!(
+        "{}:{} [{}] \"{}\" -> \"{}\"",
+        suggestion.line,
+        suggestion.column,
+        suggestion.category,
+        suggestion.original,
+        suggestion.replacement
+    );
+    if let Some(line) = text.lines().nth(suggestion.line - 1) {
+        println!("    {}", line);
+    }
+    if !suggestion.explanation.is_empty() {
+        println!("    {}", suggestion.explanation);
+    }
+}
+
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+/// Resolve each suggestion to a byte range of the text, sorted by position.
+/// Models are not reliable at counting columns, so when `original` is not found at the
+/// given column the first occurrence in the same line is used instead. Suggestions whose
+/// `original` is not on that line at all are dropped with a warning.
+fn locate_all(
+    text: &str,
+    suggestions: Vec<CopyeditSuggestion>,
+) -> Vec<(Range<usize>, CopyeditSuggestion)> {
+    let mut line_starts = vec![0];
+    line_starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+
+    let mut located = Vec::new();
+    for mut suggestion in suggestions {
+        if suggestion.original.is_empty() || suggestion.original == suggestion.replacement {
+            continue;
+        }
+        let Some(span) = locate(text, &line_starts, &suggestion) else {
+            eprintln!(
+                "Warning: skipping suggestion at {}:{}, '{}' is not on that line.",
+                suggestion.line, suggestion.column, suggestion.original
+            );
+            continue;
+        };
+        let line_start = line_starts[suggestion.line - 1];
+        suggestion.column = text[line_start..span.start].chars().count() + 1;
+        located.push((span, suggestion));
+    }
+    located.sort_by_key(|(span, _)| span.start);
+    located
+}
+
+fn locate(
+    text: &str,
+    line_starts: &[usize],
+    suggestion: &CopyeditSuggestion,
+) -> Option<Range<usize>> {
+    let line_start = *line_starts.get(suggestion.line.checked_sub(1)?)?;
+    let line_end = text[line_start..]
+        .find('\n')
+        .map_or(text.len(), |end| line_start + end);
+    let line = &text[line_start..line_end];
+
+    let at_column = line
+        .char_indices()
+        .nth(suggestion.column.saturating_sub(1))
+        .map(|(offset, _)| offset)
+        .filter(|&offset| line[offset..].starts_with(&suggestion.original));
+    let offset = at_column.or_else(|| line.find(&suggestion.original))?;
+
+    let start = line_start + offset;
+    Some(start..start + suggestion.original.len())
 }
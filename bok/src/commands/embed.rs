@@ -0,0 +1,17 @@
+use crate::config::Settings;
+use crate::embeddings::EmbeddingStore;
+use crate::llm::get_llm_provider;
+use anyhow::Result;
+
+/// Bring the cached node embeddings up to date, so that later commands need not wait
+pub async fn run() -> Result<()> {
+    let settings = Settings::new()?;
+    let llm = get_llm_provider(&settings.llm)?;
+    let mut store = EmbeddingStore::load(&settings.llm);
+    let (_, stats) = store.embed_book(&llm).await?;
+    println!(
+        "Embedded {} node(s), {} unchanged.",
+        stats.embedded, stats.unchanged
+    );
+    Ok(())
+}
@@ -1,3 +1,271 @@
-pub fn quarto() {
-    println!("'bok generate quarto' is not yet implemented");
+use crate::config::{FullConfig, book_root};
+use crate::markdown::{Fence, atx_heading, blurb, inline_link};
+use crate::node::{Node, NodeManager};
+use anyhow::{Result, anyhow, bail};
+use serde::Serialize;
+use std::fs;
+use std::path::{Component, Path};
+
+/// First line of every file written by `bok generate quarto`.
+/// Files without it are considered user-provided and are never overwritten.
+const GENERATED_MARKER: &str = "generated by bok generate quarto";
+
+/// Directory (relative to the book root) that receives the generated chapters
+const CHAPTERS_DIR: &str = "chapters";
+
+const INDEX_FILE: &str = "index.qmd";
+const COVER_CANDIDATES: &[&str] = &["cover.png", "cover.jpg", "cover.jpeg", "cover.svg"];
+const STYLE_CANDIDATES: &[&str] = &["styles.css", "styles.scss", "custom.scss"];
+
+#[derive(Serialize)]
+struct QuartoConfig {
+    project: QuartoProject,
+    book: QuartoBook,
+    format: QuartoFormat,
+}
+
+#[derive(Serialize)]
+struct QuartoProject {
+    #[serde(rename = "type")]
+    project_type: String,
+    render: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct QuartoBook {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    #[serde(rename = "cover-image", skip_serializing_if = "Option::is_none")]
+    cover_image: Option<String>,
+    chapters: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct QuartoFormat {
+    html: QuartoHtml,
+}
+
+#[derive(Serialize)]
+struct QuartoHtml {
+    theme: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    css: Vec<String>,
+}
+
+pub fn quarto() -> Result<()> {
+    let config = FullConfig::load()?;
+    let (starting_node, _) = NodeManager::get_book_tree().map_err(|e| anyhow!(e))?;
+    let starting_node =
+        starting_node.ok_or_else(|| anyhow!("Cannot generate a book without a starting node"))?;
+
+    let root = book_root();
+    ensure_generated_or_missing(&root.join("_quarto.yml"))?;
+
+    let index_path = root.join(INDEX_FILE);
+    if is_user_provided(&index_path) {
+        println!("Using user-provided {}", INDEX_FILE);
+    } else {
+        fs::write(&index_path, render_index(&starting_node)?)?;
+        println!("Generated {}", INDEX_FILE);
+    }
+
+    let chapters_dir = root.join(CHAPTERS_DIR);
+    fs::create_dir_all(&chapters_dir)?;
+    remove_generated_chapters(&chapters_dir)?;
+
+    let mut chapters = vec![INDEX_FILE.to_string()];
+    for (i, chapter) in starting_node.children.iter().enumerate() {
+        let file_name = format!("{}/{:02}-{}.qmd", CHAPTERS_DIR, i + 1, chapter.id);
+        fs::write(root.join(&file_name), render_chapter(chapter)?)?;
+        println!("Generated {} ({})", file_name, chapter.blurb());
+        chapters.push(file_name);
+    }
+
+    let quarto_config = QuartoConfig {
+        project: QuartoProject {
+            project_type: "book".to_string(),
+            render: chapters.clone(),
+        },
+        book: QuartoBook {
+            title: config
+                .title
+                .or_else(|| Some(starting_node.blurb().to_string())),
+            author: config.author,
+            cover_image: find_existing(root, COVER_CANDIDATES).into_iter().next(),
+            chapters,
+        },
+        format: QuartoFormat {
+            html: QuartoHtml {
+                theme: "cosmo".to_string(),
+                css: find_existing(root, STYLE_CANDIDATES),
+            },
+        },
+    };
+
+    let yaml = serde_yaml::to_string(&quarto_config)?;
+    fs::write(
+        root.join("_quarto.yml"),
+        format!("# {}\n{}", GENERATED_MARKER, yaml),
+    )?;
+    println!("Generated _quarto.yml");
+
+    Ok(())
+}
+
+/// A file is user-provided when it exists but was not written by us
+fn is_user_provided(path: &Path) -> bool {
+    match fs::read_to_string(path) {
+        Ok(content) => !content
+            .lines()
+            .next()
+            .is_some_and(|line| line.contains(GENERATED_MARKER)),
+        Err(_) => false,
+    }
+}
+
+fn ensure_generated_or_missing(path: &Path) -> Result<()> {
+    if is_user_provided(path) {
+        bail!(
+            "{} exists and was not generated by bok. Move it away to let bok generate it.",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Remove chapters from a previous run so that deleted nodes don't linger
+fn remove_generated_chapters(chapters_dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(chapters_dir)?.filter_map(Result::ok) {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "qmd") && !is_user_provided(&path) {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+fn find_existing(root: &Path, candidates: &[&str]) -> Vec<String> {
+    candidates
+        .iter()
+        .filter(|name| root.join(name).exists())
+        .map(|name| name.to_string())
+        .collect()
+}
+
+fn render_index(starting_node: &Node) -> Result<String> {
+    let mut out = format!("<!-- {} -->\n\n", GENERATED_MARKER);
+    out.push_str(&format!("# {} {{.unnumbered}}\n\n", starting_node.blurb()));
+    let content = asset_paths(starting_node, &node_content(starting_node)?, Path::new(""));
+    push_body(
+        &mut out,
+        starting_node,
+        split_heading(starting_node, &content).1,
+    );
+    Ok(out)
+}
+
+fn render_chapter(chapter: &Node) -> Result<String> {
+    let mut out = format!("<!-- {} -->\n\n", GENERATED_MARKER);
+    render_section(&mut out, chapter, 1, &mut |node| {
+        Ok(asset_paths(
+            node,
+            &node_content(node)?,
+            Path::new(CHAPTERS_DIR),
+        ))
+    })?;
+    Ok(out)
+}
+
+fn node_content(node: &Node) -> Result<String> {
+    node.content().map_err(|e| anyhow!(e))
+}
+
+/// Point the images stored in the node's directory, e.g. the files `bok import --obsidian`
+/// copies there, at that directory as seen from `from`, relative to the book root
+fn asset_paths(node: &Node, content: &str, from: &Path) -> String {
+    let dir = node.path.strip_prefix(book_root()).unwrap_or(&node.path);
+    let mut to_dir = String::new();
+    for _ in from.components() {
+        to_dir.push_str("../");
+    }
+    for component in dir.components() {
+        if let Component::Normal(name) = component {
+            to_dir.push_str(&name.to_string_lossy());
+            to_dir.push('/');
+        }
+    }
+
+    let mut out = String::with_capacity(content.len());
+    let mut fence = Fence::default();
+    for line in content.split_inclusive('\n') {
+        if fence.track(line.trim_end_matches(['\n', '\r'])) {
+            out.push_str(line);
+            continue;
+        }
+        let mut rest = line;
+        while let Some(start) = rest.find("![") {
+            out.push_str(&rest[..start + 1]);
+            rest = &rest[start + 1..];
+            let Some((label, target, length)) = inline_link(rest) else {
+                continue;
+            };
+            let in_node = !target.is_empty()
+                && !target.contains("://")
+                && !target.starts_with(['/', '#'])
+                && node.path.join(target).is_file();
+            if in_node {
+                out.push_str(&format!("[{}](<{}{}>)", label, to_dir, target));
+                rest = &rest[length..];
+            }
+        }
+        out.push_str(rest);
+    }
+    out
+}
+
+/// Render a node and its subtree, the heading level following the tree depth.
+/// `content` supplies the text of each node.
+pub(crate) fn render_section(
+    out: &mut String,
+    node: &Node,
+    level: usize,
+    content: &mut dyn FnMut(&Node) -> Result<String>,
+) -> Result<()> {
+    let text = content(node)?;
+    let (title, body) = split_heading(node, &text);
+    if !node.meta.no_heading {
+        out.push_str(&format!(
+            "{} {}\n\n",
+            "#".repeat(level.min(6)),
+            title.as_deref().unwrap_or(node.blurb())
+        ));
+    }
+    push_body(out, node, body);
+    for child in &node.children {
+        render_section(out, child, level + 1, content)?;
+    }
+    Ok(())
+}
+
+/// The title of a heading line the text starts with, if it names the node, and the text
+/// after it. `bok import` keeps the heading of a section in the section's node, the
+/// rendered heading replaces it.
+fn split_heading<'a>(node: &Node, content: &'a str) -> (Option<String>, &'a str) {
+    let content = content.trim_start();
+    let (first_line, rest) = content.split_once('\n').unwrap_or((content, ""));
+    match atx_heading(first_line) {
+        Some((_, title)) if blurb(&title) == node.blurb() => (Some(title), rest),
+        _ => (None, content),
+    }
+}
+
+fn push_body(out: &mut String, node: &Node, content: &str) {
+    let body = content.trim();
+    // `bok node add` seeds text.qmd with the blurb, which would just repeat the heading
+    if !body.is_empty() && (node.meta.no_heading || body != node.blurb()) {
+        out.push_str(body);
+        out.push_str("\n\n");
+    }
 }
@@ -0,0 +1,205 @@
+//! Importing a directory of markdown files: a node for every folder and every file,
+//! with the nodes of the file under it
+
+use super::obsidian::{self, Note};
+use super::staging::{FileReport, Outcome, Staging, manifest_path};
+use super::{
+    ImportOptions, SplitFile, Unit, abort, blurb, check, commit, llm_for, split_content,
+    stage_units,
+};
+use crate::llm::Llm;
+use anyhow::{Result, anyhow, bail};
+use futures::stream::{self, StreamExt};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extensions of the files that are imported
+const EXTENSIONS: [&str; 2] = ["md", "qmd"];
+
+pub async fn run(dir: &str, options: &ImportOptions) -> Result<()> {
+    let root = Path::new(dir);
+    let files = markdown_files(root)?;
+    if files.is_empty() {
+        bail!("No .md or .qmd files found in '{}'.", dir);
+    }
+    let llm_provider = llm_for(options)?;
+
+    // Split the files concurrently, every file sends its sections to the LLM one by one
+    let total = files.len();
+    println!("Importing {} file(s) from '{}'.", total, dir);
+    let mut splits: Vec<Option<SplitFile>> = (0..total).map(|_| None).collect();
+    let mut pending = stream::iter(files.iter().enumerate())
+        .map(|(i, file)| {
+            let llm_provider = llm_provider.as_ref();
+            async move { (i, split_file(&root.join(file), llm_provider, options).await) }
+        })
+        .buffer_unordered(options.jobs.max(1));
+    let mut done = 0;
+    while let Some((i, split)) = pending.next().await {
+        done += 1;
+        let name = files[i].display();
+        match &split.error {
+            None => println!(
+                "[{}/{}] {}: {} part(s)",
+                done,
+                total,
+                name,
+                split.units.len()
+            ),
+            Some(e) if options.keep_partial => eprintln!("[{}/{}] {}: {}", done, total, name, e),
+            Some(e) => bail!("{}: {}, nothing was added to the book.", name, e),
+        }
+        splits[i] = Some(split);
+    }
+    drop(pending);
+
+    let mut staging = Staging::begin()?;
+    let mut folders = HashMap::new();
+    let mut reports = Vec::new();
+    let mut notes = Vec::new();
+    for (file, split) in files.iter().zip(splits) {
+        let mut split = split.unwrap();
+        let name = file.display().to_string();
+        // Rolling back removes the folder nodes staged for this file, too
+        let before = staging.nodes().len();
+        let (file_node, staged) =
+            match stage_file(&mut staging, &mut folders, file, &mut split.units) {
+                Ok(staged) => staged,
+                Err(e) => {
+                    return abort(staging, dir, options, reports, anyhow!("{}: {}", name, e));
+                }
+            };
+
+        let mut report = FileReport::new(&name, staged.len());
+        match split.error {
+            Some(e) => report.fail(&e),
+            None => {
+                let (outcome, differences) = check(
+                    &name,
+                    &split.content,
+                    &staging,
+                    &staged,
+                    options.on_mismatch,
+                )?;
+                if outcome == Outcome::RolledBack {
+                    staging.truncate(before)?;
+                    folders.retain(|_, index| *index < before);
+                    report.parts = 0;
+                } else if options.obsidian {
+                    notes.push(Note::new(
+                        file.clone(),
+                        split.front_matter,
+                        file_node,
+                        &mut split.units,
+                        &staged,
+                    ));
+                }
+                report.outcome = outcome;
+                report.differences = differences;
+            }
+        }
+        reports.push(report);
+    }
+
+    if options.obsidian {
+        obsidian::resolve(&staging, root, &notes)?;
+    }
+    println!("Summary:");
+    for report in &reports {
+        println!("  {}: {}", report.file, report.describe());
+    }
+    let manifest = commit(staging, dir, options.under.as_deref(), reports)?;
+    println!(
+        "The report is in '{}'.",
+        manifest_path(&manifest.id).display()
+    );
+    Ok(())
+}
+
+/// The markdown files under `root`, relative to it and sorted. Hidden files and
+/// directories, e.g. `.obsidian` or `.git`, are skipped.
+fn markdown_files(root: &Path) -> Result<Vec<PathBuf>> {
+    fn collect(root: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        let dir = root.join(relative);
+        let entries =
+            fs::read_dir(&dir).map_err(|e| anyhow!("Failed to read '{}': {}", dir.display(), e))?;
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = relative.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                collect(root, &path, files)?;
+            } else if path
+                .extension()
+                .is_some_and(|extension| EXTENSIONS.iter().any(|e| extension == *e))
+            {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    collect(root, Path::new(""), &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+async fn split_file(path: &Path, llm_provider: Option<&Llm>, options: &ImportOptions) -> SplitFile {
+    match fs::read_to_string(path) {
+        Ok(content) => split_content(&content, llm_provider, options).await,
+        Err(e) => SplitFile {
+            error: Some(anyhow!("Failed to read the file: {}", e)),
+            ..Default::default()
+        },
+    }
+}
+
+/// Stage a node for the file, with its units under it, and nodes for the folders it is in
+/// that are not staged yet. A single top-level unit, e.g. from a note with one `#` heading,
+/// is the file's node. Nodes for files are titled by the file name. Returns the staged
+/// indices of the file's node and its units.
+fn stage_file(
+    staging: &mut Staging,
+    folders: &mut HashMap<PathBuf, usize>,
+    file: &Path,
+    units: &mut [Unit],
+) -> Result<(usize, Vec<usize>)> {
+    let parent = stage_folder(staging, folders, file.parent())?;
+    let title = blurb(&file.file_stem().unwrap_or_default().to_string_lossy());
+    if units.iter().filter(|unit| unit.parent.is_none()).count() == 1 {
+        units[0].blurb = title;
+        let staged = stage_units(staging, units, parent, false, false)?;
+        return Ok((staged[0], staged));
+    }
+    let file_node = staging
+        .add(&title, "", parent)
+        .map_err(|e| anyhow!("Failed to create node: {}", e))?;
+    let staged = stage_units(staging, units, Some(file_node), true, false)?;
+    Ok((file_node, staged))
+}
+
+/// The staged node of a folder, staging it (and its parents) the first time.
+/// None for the imported directory itself.
+fn stage_folder(
+    staging: &mut Staging,
+    folders: &mut HashMap<PathBuf, usize>,
+    folder: Option<&Path>,
+) -> Result<Option<usize>> {
+    let Some(folder) = folder.filter(|folder| !folder.as_os_str().is_empty()) else {
+        return Ok(None);
+    };
+    if let Some(&index) = folders.get(folder) {
+        return Ok(Some(index));
+    }
+    let parent = stage_folder(staging, folders, folder.parent())?;
+    let name = folder.file_name().unwrap_or_default().to_string_lossy();
+    let index = staging
+        .add(&blurb(&name), "", parent)
+        .map_err(|e| anyhow!("Failed to create node: {}", e))?;
+    folders.insert(folder.to_path_buf(), index);
+    Ok(Some(index))
+}
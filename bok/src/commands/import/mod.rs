@@ -0,0 +1,448 @@
+mod batch;
+mod obsidian;
+mod outline;
+mod staging;
+mod validate;
+
+use crate::commands::prompt::ask;
+use crate::config::Settings;
+use crate::llm::{AsyncIterator, Llm, get_llm_provider};
+use crate::markdown::blurb;
+use anyhow::{Result, anyhow, bail};
+use clap::ValueEnum;
+use obsidian::{FrontMatter, Marks, Note};
+use outline::{Step, outline, paragraphs, plan};
+use staging::{FileReport, ImportManifest, Outcome, Staging};
+use std::fs;
+use std::path::{Path, PathBuf};
+use validate::{ImportedNode, validate};
+
+/// What to do when the imported nodes do not add up to the original file
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum MismatchAction {
+    /// Show the differences and ask
+    Ask,
+    /// Replace the text of the affected nodes with their part of the original
+    Patch,
+    /// Leave the nodes as the LLM wrote them
+    Keep,
+    /// Remove all imported nodes
+    Rollback,
+}
+
+/// How `bok import` builds the nodes
+pub struct ImportOptions {
+    /// Node to create the imported nodes under, None for the top level
+    pub under: Option<String>,
+    pub on_mismatch: MismatchAction,
+    /// Add the nodes created before a failure instead of nothing
+    pub keep_partial: bool,
+    /// Create all nodes as siblings instead of following the headings
+    pub flat: bool,
+    /// Split on headings and paragraphs without asking the LLM
+    pub no_llm: bool,
+    /// Import every markdown file of a directory
+    pub recursive: bool,
+    /// Files split at the same time with `recursive`, which bounds the LLM requests in flight
+    pub jobs: usize,
+    /// Read the files as Obsidian notes: wiki-links, embeds, tags and front matter
+    pub obsidian: bool,
+}
+
+/// A node split from a file, not created yet
+pub struct Unit {
+    /// Index of the parent unit, None for the top of the file
+    parent: Option<usize>,
+    blurb: String,
+    text: String,
+    /// Made from a heading, whose line starts the text; other units are parts of a text
+    section: bool,
+    /// Tags, wiki-links and embeds taken out of the text with `obsidian`
+    marks: Marks,
+}
+
+/// A file split into units, as far as it went
+#[derive(Default)]
+struct SplitFile {
+    /// The text the units should add up to
+    content: String,
+    front_matter: FrontMatter,
+    units: Vec<Unit>,
+    error: Option<anyhow::Error>,
+}
+
+pub async fn run(file: &str, options: &ImportOptions) -> Result<()> {
+    let file_path = Path::new(file);
+    let under = options.under.as_deref();
+
+    if !file_path.exists() {
+        eprintln!("Error: File '{}' not found.", file);
+        return Ok(());
+    }
+    if file_path.is_dir() {
+        if !options.recursive {
+            bail!("'{}' is a directory, import it with --recursive.", file);
+        }
+        return batch::run(file, options).await;
+    }
+
+    let original_content = fs::read_to_string(file_path)?;
+    let llm_provider = llm_for(options)?;
+
+    let mut split = split_content(&original_content, llm_provider.as_ref(), options).await;
+    if let Some(e) = &split.error
+        && !options.keep_partial
+    {
+        bail!("{}, nothing was added to the book.", e);
+    }
+
+    let mut staging = Staging::begin()?;
+    let staged = match stage_units(&mut staging, &split.units, None, false, true) {
+        Ok(staged) => staged,
+        Err(e) => return abort(staging, file, options, Vec::new(), e),
+    };
+    let mut report = FileReport::new(file, staged.len());
+    if let Some(e) = split.error {
+        report.fail(&e);
+        commit(staging, file, under, vec![report])?;
+        return Err(e);
+    }
+
+    println!(
+        "Successfully imported {} parts from '{}'.",
+        staged.len(),
+        file
+    );
+
+    let (outcome, differences) =
+        check(file, &split.content, &staging, &staged, options.on_mismatch)?;
+    match outcome {
+        Outcome::Exact => {
+            println!("Validation successful: Reconstructed content matches original.")
+        }
+        Outcome::Whitespace => println!(
+            "Validation successful: Reconstructed content matches original up to whitespace."
+        ),
+        Outcome::RolledBack => {
+            staging.discard()?;
+            bail!(
+                "The imported nodes did not match '{}', removed all {} of them.",
+                file,
+                staged.len()
+            );
+        }
+        _ => {}
+    }
+    if options.obsidian
+        && let Some(&node) = staged.first()
+    {
+        let name = PathBuf::from(file_path.file_name().unwrap_or_default());
+        let note = Note::new(name, split.front_matter, node, &mut split.units, &staged);
+        obsidian::resolve(
+            &staging,
+            file_path
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or(Path::new(".")),
+            &[note],
+        )?;
+    }
+    report.outcome = outcome;
+    report.differences = differences;
+    commit(staging, file, under, vec![report])?;
+    Ok(())
+}
+
+fn llm_for(options: &ImportOptions) -> Result<Option<Llm>> {
+    match options.no_llm {
+        true => Ok(None),
+        false => Ok(Some(get_llm_provider(&Settings::new()?.llm)?)),
+    }
+}
+
+/// Split the content of a file. With `obsidian` the front matter is left out and the units
+/// are converted, so the content they should add up to is converted as well.
+async fn split_content(
+    content: &str,
+    llm_provider: Option<&Llm>,
+    options: &ImportOptions,
+) -> SplitFile {
+    let mut units = Vec::new();
+    if !options.obsidian {
+        let error = split(content, llm_provider, options, &mut units)
+            .await
+            .err();
+        return SplitFile {
+            content: content.to_string(),
+            units,
+            error,
+            ..Default::default()
+        };
+    }
+    let (mut front_matter, body) = obsidian::front_matter(content);
+    let error = split(body, llm_provider, options, &mut units).await.err();
+    let units = obsidian::convert_units(units, &mut front_matter);
+    SplitFile {
+        content: obsidian::convert(body).0,
+        front_matter,
+        units,
+        error,
+    }
+}
+
+/// Split a file into units following its outline, or the whole file at once with `flat`,
+/// adding them to `units` as they come. Parts of the LLM response that cannot be read
+/// fail the split, or are skipped with `keep_partial`.
+async fn split(
+    content: &str,
+    llm_provider: Option<&Llm>,
+    options: &ImportOptions,
+    units: &mut Vec<Unit>,
+) -> Result<()> {
+    let steps = match llm_provider {
+        Some(_) if options.flat => vec![Step::Split {
+            parent: None,
+            text: content.to_string(),
+        }],
+        _ => plan(&outline(content), options.flat),
+    };
+
+    // The unit each step created, None for splits
+    let mut created: Vec<Option<usize>> = Vec::with_capacity(steps.len());
+    for step in &steps {
+        let (parent, text) = match step {
+            Step::Node {
+                parent,
+                blurb,
+                text,
+            } => {
+                units.push(Unit {
+                    parent: parent.and_then(|step| created[step]),
+                    blurb: blurb.clone(),
+                    text: text.clone(),
+                    section: true,
+                    marks: Marks::default(),
+                });
+                created.push(Some(units.len() - 1));
+                continue;
+            }
+            Step::Split { parent, text } => (parent.and_then(|step| created[step]), text),
+        };
+        created.push(None);
+
+        let Some(llm_provider) = llm_provider else {
+            units.extend(paragraphs(text).into_iter().map(|paragraph| Unit {
+                parent,
+                blurb: blurb(&paragraph),
+                text: paragraph,
+                section: false,
+                marks: Marks::default(),
+            }));
+            continue;
+        };
+        let mut parts_stream = llm_provider.dissect_markdown(text).await?;
+        while let Some(part_result) = parts_stream.next().await {
+            match part_result {
+                // Made a valid blurb like the ones from headings and paragraphs
+                Ok((title, text)) => units.push(Unit {
+                    parent,
+                    blurb: blurb(if title.trim().is_empty() {
+                        &text
+                    } else {
+                        &title
+                    }),
+                    text,
+                    section: false,
+                    marks: Marks::default(),
+                }),
+                Err(e) if options.keep_partial => eprintln!("Error processing part: {}", e),
+                Err(e) => bail!("Error processing part: {}", e),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Create the units in the staging directory, the top ones under the staged node `parent`.
+/// Units that are not sections are marked as parts of their parent's text, the top ones
+/// only with `in_parent`. Returns the staged index of every unit.
+fn stage_units(
+    staging: &mut Staging,
+    units: &[Unit],
+    parent: Option<usize>,
+    in_parent: bool,
+    announce: bool,
+) -> Result<Vec<usize>> {
+    let mut staged: Vec<usize> = Vec::with_capacity(units.len());
+    for unit in units {
+        if announce {
+            println!(
+                "Creating node for part {}: {}",
+                staged.len() + 1,
+                unit.blurb
+            );
+        }
+        let unit_parent = unit.parent.map(|i| staged[i]).or(parent);
+        let index = staging
+            .add(&unit.blurb, &unit.text, unit_parent)
+            .map_err(|e| anyhow!("Failed to create node: {}", e))?;
+        if !unit.section && (unit.parent.is_some() || in_parent) {
+            staging.update_meta(index, |meta| meta.no_heading = true)?;
+        }
+        staged.push(index);
+    }
+    Ok(staged)
+}
+
+/// Give up after a node could not be created: add what was staged with `keep_partial`,
+/// nothing otherwise
+fn abort(
+    staging: Staging,
+    source: &str,
+    options: &ImportOptions,
+    reports: Vec<FileReport>,
+    e: anyhow::Error,
+) -> Result<()> {
+    if options.keep_partial {
+        commit(staging, source, options.under.as_deref(), reports)?;
+        return Err(e);
+    }
+    staging.discard()?;
+    bail!("{}, nothing was added to the book.", e)
+}
+
+/// Validate the staged nodes of a file against it and deal with a mismatch as asked.
+/// Returns the outcome and how many differences were found; a rollback is left to the caller.
+fn check(
+    file: &str,
+    original_content: &str,
+    staging: &Staging,
+    staged: &[usize],
+    on_mismatch: MismatchAction,
+) -> Result<(Outcome, usize)> {
+    let nodes = read_back(staging, staged)?;
+    let validation = validate(original_content, &nodes);
+    if validation.exact {
+        return Ok((Outcome::Exact, 0));
+    }
+    if validation.changes.is_empty() {
+        return Ok((Outcome::Whitespace, 0));
+    }
+
+    eprintln!(
+        "Validation failed: the imported nodes differ from '{}' in {} place(s):",
+        file,
+        validation.changes.len()
+    );
+    for change in &validation.changes {
+        let node = &nodes[change.node];
+        eprintln!(
+            "  line {} (node {} {}): {}",
+            change.line,
+            node.id,
+            node.blurb,
+            change.describe()
+        );
+    }
+
+    let action = match on_mismatch {
+        MismatchAction::Ask => ask_action(validation.patches.len()),
+        action => action,
+    };
+    let outcome = match action {
+        MismatchAction::Patch => {
+            for (i, text) in &validation.patches {
+                let path = staging.nodes()[staged[*i]].path.join("text.qmd");
+                fs::write(&path, text)
+                    .map_err(|e| anyhow!("Failed to write '{}': {}", path.display(), e))?;
+            }
+            println!(
+                "Patched {} node(s) with the original text.",
+                validation.patches.len()
+            );
+            let patched = validate(original_content, &read_back(staging, staged)?);
+            if patched.changes.is_empty() {
+                println!("Validation successful: Reconstructed content matches original.");
+            } else {
+                eprintln!(
+                    "Warning: {} difference(s) remain after patching.",
+                    patched.changes.len()
+                );
+            }
+            Outcome::Patched
+        }
+        MismatchAction::Rollback => Outcome::RolledBack,
+        MismatchAction::Keep | MismatchAction::Ask => {
+            println!("Kept the imported nodes as they are.");
+            Outcome::Kept
+        }
+    };
+    Ok((outcome, validation.changes.len()))
+}
+
+/// Undo an import: move the nodes it added to the trash
+pub fn undo(import_id: &str) -> Result<()> {
+    let removed = staging::undo(import_id)?;
+    println!(
+        "Moved {} node(s) of import {} to the trash.",
+        removed, import_id
+    );
+    Ok(())
+}
+
+/// Move the staged nodes into the book
+fn commit(
+    staging: Staging,
+    source: &str,
+    under: Option<&str>,
+    files: Vec<FileReport>,
+) -> Result<ImportManifest> {
+    let manifest = staging.commit(source, under, files)?;
+    println!(
+        "Added {} node(s) to the book as import {}, undo with 'bok import --undo {}'.",
+        manifest.nodes.len(),
+        manifest.id,
+        manifest.id
+    );
+    Ok(manifest)
+}
+
+/// The staged nodes at the given indices, with their text as it is on disk now
+fn read_back(staging: &Staging, staged: &[usize]) -> Result<Vec<ImportedNode>> {
+    staged
+        .iter()
+        .map(|&i| {
+            let node = &staging.nodes()[i];
+            let content = fs::read_to_string(node.path.join("text.qmd"))
+                .map_err(|e| anyhow!("Failed to get content for node {}: {}", node.id, e))?;
+            Ok(ImportedNode {
+                id: node.id.clone(),
+                blurb: node.blurb.clone(),
+                content,
+            })
+        })
+        .collect()
+}
+
+/// Ask how to deal with a mismatch. A closed stdin keeps the nodes.
+fn ask_action(affected: usize) -> MismatchAction {
+    loop {
+        match ask(&format!(
+            "Patch the {} affected node(s) with the original text, keep them or roll back the import? [p,k,r]",
+            affected
+        ))
+        .as_deref()
+        {
+            Some("p") => return MismatchAction::Patch,
+            Some("k") => return MismatchAction::Keep,
+            Some("r") => return MismatchAction::Rollback,
+            Some(_) => println!(
+                "p - replace the text of the affected nodes with the original, k - keep the nodes as they are, r - remove all nodes imported from the file"
+            ),
+            None => {
+                println!();
+                return MismatchAction::Keep;
+            }
+        }
+    }
+}
@@ -0,0 +1,575 @@
+//! Obsidian vaults: wiki-links between the imported notes become relations, embedded files
+//! are copied into the node that embeds them and tags go to the node metadata
+
+use super::Unit;
+use super::staging::Staging;
+use crate::markdown::{Fence, blurb, inline_link};
+use crate::node::RelationKind;
+use crate::node::node::Relation;
+use anyhow::Result;
+use serde_yaml::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extensions of embedded files that are notes rather than assets
+const NOTE_EXTENSIONS: [&str; 2] = ["md", "qmd"];
+
+/// Tags, wiki-links and embedded files taken out of a text
+#[derive(Default)]
+pub struct Marks {
+    pub tags: Vec<String>,
+    pub links: Vec<WikiLink>,
+    /// Embedded files that are not notes, as written
+    pub embeds: Vec<String>,
+}
+
+/// A `[[note#heading|alias]]` link
+pub struct WikiLink {
+    /// Name or path of the linked note, empty for a heading of the same note
+    pub note: String,
+    pub heading: Option<String>,
+}
+
+/// The front matter fields the import uses
+#[derive(Default)]
+pub struct FrontMatter {
+    pub tags: Vec<String>,
+    /// Other names links may use for the note
+    pub aliases: Vec<String>,
+}
+
+/// An imported file, to resolve links once all files are staged
+pub struct Note {
+    /// Path relative to the imported directory
+    pub path: PathBuf,
+    pub front_matter: FrontMatter,
+    /// The staged node standing for the whole note
+    pub node: usize,
+    /// The staged nodes of the note with their marks
+    pub nodes: Vec<(usize, Marks)>,
+    /// (blurb, staged index) of the note's heading nodes
+    pub headings: Vec<(String, usize)>,
+}
+
+impl Note {
+    /// A staged file: `node` stands for the whole note and `staged` holds the staged index
+    /// of every unit. Takes the marks of the units.
+    pub fn new(
+        path: PathBuf,
+        front_matter: FrontMatter,
+        node: usize,
+        units: &mut [Unit],
+        staged: &[usize],
+    ) -> Self {
+        let headings = units
+            .iter()
+            .zip(staged)
+            .filter(|(unit, _)| unit.section)
+            .map(|(unit, &index)| (unit.blurb.clone(), index))
+            .collect();
+        let nodes = units
+            .iter_mut()
+            .zip(staged)
+            .map(|(unit, &index)| (index, std::mem::take(&mut unit.marks)))
+            .collect();
+        Note {
+            path,
+            front_matter,
+            node,
+            nodes,
+            headings,
+        }
+    }
+}
+
+/// Split off a leading `---` front matter block, returning the rest of the content
+pub fn front_matter(content: &str) -> (FrontMatter, &str) {
+    let Some(rest) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return (FrontMatter::default(), content);
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if matches!(line.trim_end(), "---" | "...") {
+            let yaml: Value = serde_yaml::from_str(&rest[..offset]).unwrap_or(Value::Null);
+            let front_matter = FrontMatter {
+                tags: strings(yaml.get("tags"))
+                    .into_iter()
+                    .map(|tag| tag.trim_start_matches('#').to_string())
+                    .collect(),
+                aliases: strings(yaml.get("aliases")),
+            };
+            return (front_matter, &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (FrontMatter::default(), content)
+}
+
+/// A list of strings, or a single string of comma or space separated ones
+fn strings(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Sequence(items)) => items
+            .iter()
+            .filter_map(|item| item.as_str().map(String::from))
+            .collect(),
+        Some(Value::String(items)) => items
+            .split([',', ' '])
+            .filter(|item| !item.is_empty())
+            .map(String::from)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Take tags, wiki-links and embeds out of a text. Links are replaced by their display text,
+/// embedded files by markdown images and tags by their name; lines of nothing but tags are
+/// dropped. Code is left alone.
+pub fn convert(text: &str) -> (String, Marks) {
+    let mut marks = Marks::default();
+    let mut converted = String::with_capacity(text.len());
+    let mut fence = Fence::default();
+    for line in text.split_inclusive('\n') {
+        if fence.track(line.trim_end_matches(['\n', '\r'])) {
+            converted.push_str(line);
+            continue;
+        }
+        let tags: Option<Vec<&str>> = line.split_whitespace().map(tag_token).collect();
+        if let Some(tags) = tags.filter(|tags| !tags.is_empty()) {
+            for tag in tags {
+                add_tag(&mut marks.tags, tag);
+            }
+            continue;
+        }
+        converted.push_str(&convert_line(line, &mut marks));
+    }
+    (converted, marks)
+}
+
+/// Convert the text of every unit. Units left empty, e.g. a paragraph of tags, are dropped
+/// and their tags go to their parent, or to the note at the top level.
+pub fn convert_units(units: Vec<Unit>, front_matter: &mut FrontMatter) -> Vec<Unit> {
+    let mut converted: Vec<Unit> = Vec::with_capacity(units.len());
+    // Where the children of every unit go: the converted unit, or the parent of a dropped one
+    let mut parents: Vec<Option<usize>> = Vec::with_capacity(units.len());
+    for unit in units {
+        let parent = unit.parent.and_then(|i| parents[i]);
+        let (text, marks) = convert(&unit.text);
+        if text.trim().is_empty() {
+            let tags = match parent {
+                Some(parent) => &mut converted[parent].marks.tags,
+                None => &mut front_matter.tags,
+            };
+            for tag in &marks.tags {
+                add_tag(tags, tag);
+            }
+            parents.push(parent);
+            continue;
+        }
+        // Blurbs made from the text are made again, without the link syntax. A unit
+        // starting with an embedded file is named after the file.
+        let blurb = match marks.embeds.first() {
+            _ if unit.blurb != blurb(&unit.text) => unit.blurb,
+            Some(embed) if text.trim_start().starts_with("![") => {
+                blurb(embed.rsplit('/').next().unwrap_or(embed))
+            }
+            _ => blurb(&plain(&text)),
+        };
+        converted.push(Unit {
+            parent,
+            blurb,
+            text,
+            section: unit.section,
+            marks,
+        });
+        parents.push(Some(converted.len() - 1));
+    }
+    converted
+}
+
+/// The text without markdown images, and links replaced by their label
+fn plain(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if let Some(image) = rest.strip_prefix('!')
+            && let Some((_, _, length)) = inline_link(image)
+        {
+            rest = &image[length..];
+            // with the `{width=...}` of an embed
+            if rest.starts_with('{')
+                && let Some(end) = rest.find('}')
+            {
+                rest = &rest[end + 1..];
+            }
+        } else if let Some((label, _, length)) = inline_link(rest) {
+            plain.push_str(label);
+            rest = &rest[length..];
+        } else {
+            plain.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    plain
+}
+
+fn convert_line(line: &str, marks: &mut Marks) -> String {
+    let mut converted = String::with_capacity(line.len());
+    let mut rest = line;
+    let mut after_space = true;
+    let mut in_code = false;
+    while let Some(c) = rest.chars().next() {
+        if c == '`' {
+            in_code = !in_code;
+        } else if !in_code {
+            if let Some(inner) = rest.strip_prefix("![[")
+                && let Some(end) = inner.find("]]")
+            {
+                converted.push_str(&embed(&inner[..end], marks));
+                rest = &inner[end + 2..];
+                after_space = false;
+                continue;
+            }
+            if let Some(inner) = rest.strip_prefix("[[")
+                && let Some(end) = inner.find("]]")
+            {
+                converted.push_str(&link(&inner[..end], marks));
+                rest = &inner[end + 2..];
+                after_space = false;
+                continue;
+            }
+            if c == '#'
+                && after_space
+                && let Some(tag) = tag_name(&rest[1..])
+            {
+                add_tag(&mut marks.tags, tag);
+                converted.push_str(tag);
+                rest = &rest[1 + tag.len()..];
+                after_space = false;
+                continue;
+            }
+        }
+        converted.push(c);
+        after_space = c.is_whitespace();
+        rest = &rest[c.len_utf8()..];
+    }
+    converted
+}
+
+/// The tag a whitespace-free token is, if it is one
+fn tag_token(token: &str) -> Option<&str> {
+    token
+        .strip_prefix('#')
+        .and_then(tag_name)
+        .filter(|tag| tag.len() + 1 == token.len())
+}
+
+/// The tag at the start of the text after a `#`. Like Obsidian, a tag is letters, digits,
+/// `_`, `-` and `/`, not only digits.
+fn tag_name(text: &str) -> Option<&str> {
+    let end = text
+        .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '/')))
+        .unwrap_or(text.len());
+    let tag = &text[..end];
+    (!tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit())).then_some(tag)
+}
+
+fn add_tag(tags: &mut Vec<String>, tag: &str) {
+    if !tags.iter().any(|known| known == tag) {
+        tags.push(tag.to_string());
+    }
+}
+
+/// Record a `[[...]]` link and return the text shown for it
+fn link(inner: &str, marks: &mut Marks) -> String {
+    let (target, alias) = match inner.split_once('|') {
+        // In tables the pipe is escaped
+        Some((target, alias)) => (target.trim_end_matches('\\'), Some(alias.trim())),
+        None => (inner, None),
+    };
+    let (note, heading) = match target.split_once('#') {
+        Some((note, heading)) => (note.trim(), Some(heading.trim())),
+        None => (target.trim(), None),
+    };
+    // Block references (`#^id`) link to the note
+    let heading = heading.filter(|heading| !heading.starts_with('^') && !heading.is_empty());
+    marks.links.push(WikiLink {
+        note: note.to_string(),
+        heading: heading.map(String::from),
+    });
+    let name = note.rsplit('/').next().unwrap_or(note);
+    match (alias, heading) {
+        (Some(alias), _) if !alias.is_empty() => alias.to_string(),
+        (_, Some(heading)) if name.is_empty() => heading.to_string(),
+        _ => name.to_string(),
+    }
+}
+
+/// Record a `![[...]]` embed and return the markdown replacing it. Embedded notes are
+/// treated as links.
+fn embed(inner: &str, marks: &mut Marks) -> String {
+    let (name, size) = match inner.split_once('|') {
+        Some((name, size)) => (name.trim(), Some(size.trim())),
+        None => (inner.trim(), None),
+    };
+    let is_note = Path::new(name)
+        .extension()
+        .is_none_or(|extension| NOTE_EXTENSIONS.iter().any(|e| extension == *e));
+    if is_note {
+        return link(inner, marks);
+    }
+    marks.embeds.push(name.to_string());
+    let image = image(name.rsplit('/').next().unwrap_or(name));
+    // `|300` and `|300x200` set the width, Quarto keeps the aspect ratio
+    match size.and_then(|size| size.split('x').next()) {
+        Some(width) if !width.is_empty() && width.chars().all(|c| c.is_ascii_digit()) => {
+            format!("{}{{width={}}}", image, width)
+        }
+        _ => image,
+    }
+}
+
+/// A markdown image of a file in the node's directory
+fn image(file_name: &str) -> String {
+    if file_name.contains(' ') {
+        format!("![](<{}>)", file_name)
+    } else {
+        format!("![]({})", file_name)
+    }
+}
+
+/// Point the images of the text at the names the embedded files were copied as. `copies`
+/// holds the name of every embed's copy in order, None for files that were not found.
+fn rename_images(text: &str, embeds: &[String], copies: &[Option<String>]) -> String {
+    let mut renamed = String::with_capacity(text.len());
+    let mut rest = text;
+    for (embed, copy) in embeds.iter().zip(copies) {
+        let original = image(embed.rsplit('/').next().unwrap_or(embed));
+        let Some(start) = rest.find(&original) else {
+            break;
+        };
+        renamed.push_str(&rest[..start]);
+        match copy {
+            Some(copy) => renamed.push_str(&image(copy)),
+            None => renamed.push_str(&original),
+        }
+        rest = &rest[start + original.len()..];
+    }
+    renamed.push_str(rest);
+    renamed
+}
+
+/// Turn the links of the staged notes into `see-also` relations, copy the embedded files
+/// found under `root` into the nodes embedding them and add the tags to the metadata.
+/// Links to notes that were not imported stay plain text.
+pub fn resolve(staging: &Staging, root: &Path, notes: &[Note]) -> Result<()> {
+    let mut by_name: HashMap<String, usize> = HashMap::new();
+    for (i, note) in notes.iter().enumerate() {
+        let names = [
+            note.path.with_extension(""),
+            note.path.file_stem().map(PathBuf::from).unwrap_or_default(),
+        ];
+        for name in names.iter().map(|name| name.to_string_lossy().into_owned()) {
+            by_name.entry(note_key(&name)).or_insert(i);
+        }
+        for alias in &note.front_matter.aliases {
+            by_name.entry(note_key(alias)).or_insert(i);
+        }
+    }
+    let assets = if notes
+        .iter()
+        .any(|note| note.nodes.iter().any(|(_, marks)| !marks.embeds.is_empty()))
+    {
+        Assets::collect(root)
+    } else {
+        Assets::default()
+    };
+
+    let mut linked = 0;
+    let mut copied = 0;
+    let mut unresolved = HashSet::new();
+    for (i, note) in notes.iter().enumerate() {
+        if !note.front_matter.tags.is_empty() {
+            add_tags(staging, note.node, &note.front_matter.tags)?;
+        }
+        for (index, marks) in &note.nodes {
+            if !marks.tags.is_empty() {
+                add_tags(staging, *index, &marks.tags)?;
+            }
+            for link in &marks.links {
+                let target_note = match link.note.is_empty() {
+                    true => Some(i),
+                    false => by_name.get(&note_key(&link.note)).copied(),
+                };
+                let Some(target_note) = target_note else {
+                    if unresolved.insert(link.note.clone()) {
+                        eprintln!(
+                            "Warning: '{}' links to '{}', which was not imported; the link is left as text.",
+                            note.path.display(),
+                            link.note
+                        );
+                    }
+                    continue;
+                };
+                let target = &notes[target_note];
+                let node = link
+                    .heading
+                    .as_ref()
+                    .and_then(|heading| {
+                        let heading = blurb(heading);
+                        target
+                            .headings
+                            .iter()
+                            .find(|(title, _)| title.eq_ignore_ascii_case(&heading))
+                    })
+                    .map_or(target.node, |(_, index)| *index);
+                if node != *index && relate(staging, *index, node)? {
+                    linked += 1;
+                }
+            }
+            let mut copies = Vec::with_capacity(marks.embeds.len());
+            for embed in &marks.embeds {
+                let copy = match assets.find(&note.path, embed) {
+                    Some(source) => {
+                        copied += 1;
+                        Some(staging.attach(*index, source)?)
+                    }
+                    None => {
+                        eprintln!(
+                            "Warning: '{}' embeds '{}', which was not found.",
+                            note.path.display(),
+                            embed
+                        );
+                        None
+                    }
+                };
+                copies.push(copy);
+            }
+            let renamed = marks.embeds.iter().zip(&copies).any(|(embed, copy)| {
+                copy.as_deref()
+                    .is_some_and(|copy| copy != embed.rsplit('/').next().unwrap_or(embed))
+            });
+            if renamed {
+                staging.update_text(*index, |text| rename_images(text, &marks.embeds, &copies))?;
+            }
+        }
+    }
+    println!(
+        "Converted {} wiki-link(s) to relations and copied {} embedded file(s).",
+        linked, copied
+    );
+    Ok(())
+}
+
+/// How links name a note: case-insensitive, without extension
+fn note_key(name: &str) -> String {
+    let name = name.trim();
+    let name = NOTE_EXTENSIONS
+        .iter()
+        .find_map(|extension| name.strip_suffix(&format!(".{}", extension)))
+        .unwrap_or(name);
+    name.to_lowercase()
+}
+
+/// The files under the imported directory that are not notes. Hidden files and
+/// directories are skipped.
+#[derive(Default)]
+struct Assets {
+    /// By lowercase path relative to the directory
+    by_path: HashMap<String, PathBuf>,
+    /// By lowercase file name, shallowest first
+    by_name: HashMap<String, Vec<PathBuf>>,
+}
+
+impl Assets {
+    fn collect(root: &Path) -> Self {
+        fn collect(dir: &Path, root: &Path, assets: &mut Assets) {
+            let Ok(entries) = fs::read_dir(dir) else {
+                return;
+            };
+            for entry in entries.filter_map(Result::ok) {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let path = entry.path();
+                if name.starts_with('.') {
+                    continue;
+                }
+                if path.is_dir() {
+                    collect(&path, root, assets);
+                } else if !path
+                    .extension()
+                    .is_some_and(|extension| NOTE_EXTENSIONS.iter().any(|e| extension == *e))
+                {
+                    if let Ok(relative) = path.strip_prefix(root) {
+                        assets
+                            .by_path
+                            .insert(relative.to_string_lossy().to_lowercase(), path.clone());
+                    }
+                    assets
+                        .by_name
+                        .entry(name.to_lowercase())
+                        .or_default()
+                        .push(path);
+                }
+            }
+        }
+
+        let mut assets = Assets::default();
+        collect(root, root, &mut assets);
+        for paths in assets.by_name.values_mut() {
+            paths.sort_by_key(|path| (path.components().count(), path.clone()));
+        }
+        assets
+    }
+
+    /// The file an embed in the note at `note` refers to: a path relative to the note's
+    /// folder or to the imported directory, else a file of that name. Among several files
+    /// of the name the shallowest is taken, with a warning.
+    fn find(&self, note: &Path, embed: &str) -> Option<&PathBuf> {
+        let beside = note.with_file_name(embed);
+        let found = [beside.to_string_lossy().as_ref(), embed]
+            .iter()
+            .find_map(|path| self.by_path.get(&path.to_lowercase()));
+        if found.is_some() {
+            return found;
+        }
+        let name = embed.rsplit('/').next().unwrap_or(embed);
+        let paths = self.by_name.get(&name.to_lowercase())?;
+        if paths.len() > 1 {
+            eprintln!(
+                "Warning: '{}' embeds '{}', which matches {} files; copied '{}'.",
+                note.display(),
+                embed,
+                paths.len(),
+                paths[0].display()
+            );
+        }
+        paths.first()
+    }
+}
+
+fn add_tags(staging: &Staging, index: usize, tags: &[String]) -> Result<()> {
+    staging.update_meta(index, |meta| {
+        for tag in tags {
+            add_tag(&mut meta.tags, tag);
+        }
+    })
+}
+
+/// Add a `see-also` relation between staged nodes, returns false if it was there already
+fn relate(staging: &Staging, from: usize, to: usize) -> Result<bool> {
+    let relation = Relation {
+        target: staging.nodes()[to].id.clone(),
+        kind: RelationKind::SeeAlso,
+    };
+    let mut added = false;
+    staging.update_meta(from, |meta| {
+        if !meta.relations.contains(&relation) {
+            meta.relations.push(relation);
+            added = true;
+        }
+    })?;
+    Ok(added)
+}
@@ -0,0 +1,156 @@
+//! Splitting markdown along its headings and paragraphs
+
+use crate::markdown::{Fence, atx_heading, blurb};
+
+/// A heading with its text up to the next heading, and the sections nested under it
+pub struct Section {
+    /// The heading line as written, None for the text before the first heading
+    pub heading: Option<String>,
+    pub title: String,
+    /// 1 for `#`, 2 for `##`, ..., 0 for the text before the first heading
+    pub level: usize,
+    /// Text between the heading and the first nested section
+    pub body: String,
+    pub children: Vec<Section>,
+}
+
+/// One step of an import
+pub enum Step {
+    /// Create a node with this text
+    Node {
+        /// Index of the step creating the parent node, None for the top level
+        parent: Option<usize>,
+        blurb: String,
+        text: String,
+    },
+    /// Split the text into units and create a node for each
+    Split { parent: Option<usize>, text: String },
+}
+
+impl Section {
+    fn new(heading: Option<String>, title: String, level: usize) -> Self {
+        Section {
+            heading,
+            title,
+            level,
+            body: String::new(),
+            children: Vec::new(),
+        }
+    }
+}
+
+/// The document as a section without a heading, holding the text before the first heading.
+/// A heading nests under the closest heading before it with a lower level.
+/// Only ATX headings (`# Title`) count, and none inside fenced code blocks.
+pub fn outline(markdown: &str) -> Section {
+    let mut stack = vec![Section::new(None, String::new(), 0)];
+    let mut fence = Fence::default();
+    for line in markdown.lines() {
+        if !fence.track(line)
+            && let Some((level, title)) = atx_heading(line)
+        {
+            close_sections(&mut stack, level);
+            stack.push(Section::new(Some(line.to_string()), title, level));
+            continue;
+        }
+        let body = &mut stack.last_mut().unwrap().body;
+        body.push_str(line);
+        body.push('\n');
+    }
+    close_sections(&mut stack, 1);
+    let mut root = stack.pop().unwrap();
+    tidy_bodies(&mut root);
+    root
+}
+
+/// The steps creating a node for every heading, nested as the headings are. Sections
+/// without nested sections get their text split into child nodes, other sections keep
+/// it in their own node. With `flat` every node goes to the top level and all text is
+/// split.
+pub fn plan(root: &Section, flat: bool) -> Vec<Step> {
+    let mut steps = Vec::new();
+    push_split(&mut steps, None, &root.body);
+    for section in &root.children {
+        plan_section(&mut steps, None, section, flat);
+    }
+    steps
+}
+
+fn plan_section(steps: &mut Vec<Step>, parent: Option<usize>, section: &Section, flat: bool) {
+    let heading = section.heading.clone().unwrap_or_default();
+    let leaf = section.children.is_empty();
+    let text = if leaf || flat || section.body.is_empty() {
+        heading
+    } else {
+        format!("{}\n\n{}", heading, section.body)
+    };
+    steps.push(Step::Node {
+        parent,
+        blurb: blurb(&section.title),
+        text,
+    });
+    let node = if flat { None } else { Some(steps.len() - 1) };
+    if leaf || flat {
+        push_split(steps, node, &section.body);
+    }
+    for child in &section.children {
+        plan_section(steps, node, child, flat);
+    }
+}
+
+fn push_split(steps: &mut Vec<Step>, parent: Option<usize>, text: &str) {
+    if !text.trim().is_empty() {
+        steps.push(Step::Split {
+            parent,
+            text: text.to_string(),
+        });
+    }
+}
+
+/// The blank line separated paragraphs of the text, keeping fenced code blocks whole
+pub fn paragraphs(text: &str) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut current = String::new();
+    let mut fence = Fence::default();
+    for line in text.lines() {
+        if !fence.track(line) && line.trim().is_empty() {
+            if !current.trim().is_empty() {
+                paragraphs.push(tidy(&current));
+            }
+            current.clear();
+            continue;
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    if !current.trim().is_empty() {
+        paragraphs.push(tidy(&current));
+    }
+    paragraphs
+}
+
+/// Nest the open sections of `level` and deeper into their parents
+fn close_sections(stack: &mut Vec<Section>, level: usize) {
+    while stack.len() > 1 && stack.last().unwrap().level >= level {
+        let section = stack.pop().unwrap();
+        stack.last_mut().unwrap().children.push(section);
+    }
+}
+
+fn tidy_bodies(section: &mut Section) {
+    section.body = tidy(&section.body);
+    for child in &mut section.children {
+        tidy_bodies(child);
+    }
+}
+
+/// Without blank lines around it and trailing whitespace
+fn tidy(text: &str) -> String {
+    let text = text.trim_end();
+    let start = text
+        .lines()
+        .take_while(|line| line.trim().is_empty())
+        .map(|line| line.len() + 1)
+        .sum::<usize>();
+    text[start.min(text.len())..].to_string()
+}
@@ -0,0 +1,381 @@
+//! Imports are built in a staging directory under `.bok/imports/` and only moved into the
+//! book once complete, so that a failed import leaves no half-made nodes behind.
+
+use crate::config::{book_root, data_dir};
+use crate::node::index::NodeIndex;
+use crate::node::node::Meta;
+use crate::node::{Node, NodeManager};
+use anyhow::{Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Records what an import added to the book, stored as import.yaml in the import's directory
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportManifest {
+    pub id: String,
+    /// The imported file, as given on the command line
+    pub file: String,
+    /// The node the import went under, None for the top level
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub under: Option<String>,
+    /// Seconds since the Unix epoch
+    pub imported_at: u64,
+    /// The top-level nodes added, in order
+    pub nodes: Vec<String>,
+    /// What became of every imported file
+    #[serde(default)]
+    pub files: Vec<FileReport>,
+}
+
+/// How the nodes of a file compared to it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    /// The file could not be split completely, its nodes were not validated
+    Failed,
+    Exact,
+    /// Identical up to whitespace
+    Whitespace,
+    Patched,
+    Kept,
+    RolledBack,
+}
+
+/// What an import did with one file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileReport {
+    pub file: String,
+    /// Nodes created for the file
+    pub parts: usize,
+    pub outcome: Outcome,
+    /// Differences validation found, before any patching
+    #[serde(default)]
+    pub differences: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl FileReport {
+    pub fn new(file: &str, parts: usize) -> Self {
+        FileReport {
+            file: file.to_string(),
+            parts,
+            outcome: Outcome::Exact,
+            differences: 0,
+            error: None,
+        }
+    }
+
+    pub fn fail(&mut self, error: &anyhow::Error) {
+        self.outcome = Outcome::Failed;
+        self.error = Some(error.to_string());
+    }
+
+    /// One line summing up the file
+    pub fn describe(&self) -> String {
+        let outcome = match self.outcome {
+            Outcome::Failed => {
+                return format!(
+                    "{} part(s), failed: {}",
+                    self.parts,
+                    self.error.as_deref().unwrap_or("unknown error")
+                );
+            }
+            Outcome::Exact => "matches the original",
+            Outcome::Whitespace => "matches the original up to whitespace",
+            Outcome::Patched => "patched",
+            Outcome::Kept => "kept",
+            Outcome::RolledBack => "rolled back",
+        };
+        match self.differences {
+            0 => format!("{} part(s), {}", self.parts, outcome),
+            differences => format!(
+                "{} part(s), {} difference(s), {}",
+                self.parts, differences, outcome
+            ),
+        }
+    }
+}
+
+/// A node created in the staging directory
+pub struct StagedNode {
+    pub id: String,
+    pub blurb: String,
+    pub path: PathBuf,
+    /// Index of the staged parent node, None for the top level
+    pub parent: Option<usize>,
+}
+
+/// An import in progress
+pub struct Staging {
+    pub id: String,
+    /// `.bok/imports/<id>`
+    dir: PathBuf,
+    nodes: Vec<StagedNode>,
+    /// Ids given out so far, the book's index does not see the staging directory
+    reserved: HashSet<String>,
+    /// Committed or discarded, see the Drop impl
+    finished: bool,
+}
+
+fn imports_dir() -> PathBuf {
+    data_dir().join("imports")
+}
+
+/// Where the manifest of an import is kept
+pub fn manifest_path(import_id: &str) -> PathBuf {
+    imports_dir().join(import_id).join("import.yaml")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl Staging {
+    /// Start an import with a fresh id and an empty staging directory
+    pub fn begin() -> Result<Staging> {
+        let started_at = now();
+        let (id, dir) = (0..)
+            .map(|n| match n {
+                0 => started_at.to_string(),
+                n => format!("{}-{}", started_at, n),
+            })
+            .map(|id| {
+                let dir = imports_dir().join(&id);
+                (id, dir)
+            })
+            .find(|(_, dir)| !dir.exists())
+            .unwrap();
+        let staging = Staging {
+            id,
+            dir,
+            nodes: Vec::new(),
+            reserved: HashSet::new(),
+            finished: false,
+        };
+        fs::create_dir_all(staging.staging_dir()).map_err(|e| {
+            anyhow!(
+                "Failed to create '{}': {}",
+                staging.staging_dir().display(),
+                e
+            )
+        })?;
+        Ok(staging)
+    }
+
+    fn staging_dir(&self) -> PathBuf {
+        self.dir.join("staging")
+    }
+
+    pub fn nodes(&self) -> &[StagedNode] {
+        &self.nodes
+    }
+
+    /// Stage a node under the staged node at index `parent` (or at the top level), after
+    /// the last node staged there. Returns the new node's index.
+    pub fn add(&mut self, blurb: &str, content: &str, parent: Option<usize>) -> Result<usize> {
+        let dir = match parent {
+            Some(parent) => self.nodes[parent].path.clone(),
+            None => self.staging_dir(),
+        };
+        let after = self
+            .nodes
+            .iter()
+            .rev()
+            .find(|node| node.parent == parent)
+            .map(|node| node.id.clone());
+        let id =
+            NodeManager::create_node_in(&dir, blurb, content, after.as_deref(), &self.reserved)
+                .map_err(|e| anyhow!(e))?
+                .to_string();
+        self.reserved.insert(id.clone());
+        self.nodes.push(StagedNode {
+            path: dir.join(format!("{} {}", id, blurb)),
+            id,
+            blurb: blurb.to_string(),
+            parent,
+        });
+        Ok(self.nodes.len() - 1)
+    }
+
+    /// Change the metadata of the staged node at `index`
+    pub fn update_meta(&self, index: usize, update: impl FnOnce(&mut Meta)) -> Result<()> {
+        let staged = &self.nodes[index];
+        let meta_path = staged.path.join("meta.yaml");
+        let mut node = Node::from_meta(&meta_path, staged.id.clone()).map_err(|e| anyhow!(e))?;
+        update(&mut node.meta);
+        node.save_meta(&meta_path).map_err(|e| anyhow!(e))
+    }
+
+    /// Change the text of the staged node at `index`
+    pub fn update_text(&self, index: usize, update: impl FnOnce(&str) -> String) -> Result<()> {
+        let text_path = self.nodes[index].path.join("text.qmd");
+        let text = fs::read_to_string(&text_path)
+            .map_err(|e| anyhow!("Failed to read '{}': {}", text_path.display(), e))?;
+        fs::write(&text_path, update(&text))
+            .map_err(|e| anyhow!("Failed to write '{}': {}", text_path.display(), e))
+    }
+
+    /// Copy a file into the directory of the staged node at `index` and return the name it
+    /// has there: its own, or with a number added if the node has a different file of that
+    /// name already
+    pub fn attach(&self, index: usize, source: &Path) -> Result<String> {
+        let (Some(stem), Some(name)) = (source.file_stem(), source.file_name()) else {
+            bail!("'{}' is not a file.", source.display());
+        };
+        let read = |path: &Path| {
+            fs::read(path).map_err(|e| anyhow!("Failed to read '{}': {}", path.display(), e))
+        };
+        let mut n = 1;
+        loop {
+            let name = match (n, source.extension()) {
+                (1, _) => name.to_string_lossy().into_owned(),
+                (n, Some(extension)) => format!(
+                    "{}-{}.{}",
+                    stem.to_string_lossy(),
+                    n,
+                    extension.to_string_lossy()
+                ),
+                (n, None) => format!("{}-{}", stem.to_string_lossy(), n),
+            };
+            let target = self.nodes[index].path.join(&name);
+            if !target.exists() {
+                fs::copy(source, &target)
+                    .map_err(|e| anyhow!("Failed to copy '{}': {}", source.display(), e))?;
+                return Ok(name);
+            }
+            if target.is_file() && read(&target)? == read(source)? {
+                return Ok(name);
+            }
+            n += 1;
+        }
+    }
+
+    /// Drop the nodes staged from index `len` on, e.g. those of a file that was rolled back
+    pub fn truncate(&mut self, len: usize) -> Result<()> {
+        for node in &self.nodes[len..] {
+            if node.parent.is_none_or(|parent| parent < len) {
+                fs::remove_dir_all(&node.path)
+                    .map_err(|e| anyhow!("Failed to remove '{}': {}", node.path.display(), e))?;
+            }
+        }
+        self.nodes.truncate(len);
+        Ok(())
+    }
+
+    /// Drop the import, nothing reaches the book
+    pub fn discard(mut self) -> Result<()> {
+        self.finished = true;
+        fs::remove_dir_all(&self.dir)
+            .map_err(|e| anyhow!("Failed to remove '{}': {}", self.dir.display(), e))
+    }
+
+    /// Move the staged nodes into the book, after the last child of `under` (or of the book),
+    /// and record them for `bok import --undo`
+    pub fn commit(
+        mut self,
+        file: &str,
+        under: Option<&str>,
+        files: Vec<FileReport>,
+    ) -> Result<ImportManifest> {
+        let target = match under {
+            Some(under_hash) => NodeManager::find_node_path(under_hash)
+                .map_err(|_| anyhow!("Parent node with hash {} not found.", under_hash))?,
+            None => book_root().to_path_buf(),
+        };
+        let last_child = NodeManager::get_nodes_recursive(&target)
+            .last()
+            .map(|node| node.id.clone());
+
+        let top_level: Vec<&StagedNode> = self
+            .nodes
+            .iter()
+            .filter(|node| node.parent.is_none())
+            .collect();
+        let mut moved: Vec<(&Path, PathBuf)> = Vec::new();
+        NodeIndex::invalidate();
+        for node in &top_level {
+            let target_path = target.join(node.path.file_name().unwrap());
+            let result = if target_path.exists() {
+                Err(anyhow!("'{}' already exists.", target_path.display()))
+            } else {
+                fs::rename(&node.path, &target_path).map_err(|e| {
+                    anyhow!(
+                        "Error moving '{}' to '{}': {}",
+                        node.path.display(),
+                        target_path.display(),
+                        e
+                    )
+                })
+            };
+            if let Err(e) = result {
+                // Put back what was moved so far, the import stays all or nothing
+                for (staged, moved_to) in moved.iter().rev() {
+                    let _ = fs::rename(moved_to, staged);
+                }
+                return Err(e);
+            }
+            moved.push((&node.path, target_path));
+        }
+        // The nodes are in the book now, the import directory stays for `--undo`
+        self.finished = true;
+
+        if let (Some(last_child), Some((_, first))) = (last_child, moved.first()) {
+            let mut node = Node::from_meta(&first.join("meta.yaml"), top_level[0].id.clone())
+                .map_err(|e| anyhow!(e))?;
+            node.meta.after = Some(last_child);
+            node.save_meta(&node.meta_path()).map_err(|e| anyhow!(e))?;
+        }
+
+        let manifest = ImportManifest {
+            id: self.id.clone(),
+            file: file.to_string(),
+            under: under.map(String::from),
+            imported_at: now(),
+            nodes: top_level.iter().map(|node| node.id.clone()).collect(),
+            files,
+        };
+        fs::write(manifest_path(&self.id), serde_yaml::to_string(&manifest)?)?;
+        fs::remove_dir_all(self.staging_dir())?;
+        Ok(manifest)
+    }
+}
+
+impl Drop for Staging {
+    /// An import that fails half way, e.g. on an error returned with `?`, leaves no
+    /// staging directory behind
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+/// Move the nodes an import added to the trash and forget the import.
+/// Returns how many nodes were removed.
+pub fn undo(import_id: &str) -> Result<usize> {
+    let dir = imports_dir().join(import_id);
+    let path = manifest_path(import_id);
+    if !path.exists() {
+        bail!("No import with id '{}'.", import_id);
+    }
+    let manifest: ImportManifest = serde_yaml::from_str(&fs::read_to_string(&path)?)?;
+
+    let mut removed = 0;
+    for id in manifest.nodes.iter().rev() {
+        if NodeManager::find_node_path(id).is_err() {
+            eprintln!("Warning: node '{}' is no longer in the book.", id);
+            continue;
+        }
+        NodeManager::remove_node(id).map_err(|e| anyhow!(e))?;
+        removed += 1;
+    }
+    fs::remove_dir_all(&dir)?;
+    Ok(removed)
+}
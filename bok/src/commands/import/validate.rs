@@ -0,0 +1,208 @@
+//! Checking that the imported nodes add up to the imported file
+
+use similar::{DiffOp, DiffTag, TextDiff};
+use std::ops::Range;
+use std::time::Duration;
+
+/// What the node texts are joined with to reconstruct the file
+pub const SEPARATOR: &str = "\n\n";
+/// Changes separated by fewer equal characters than this are reported as one,
+/// so that a rewritten sentence is one change rather than one per letter
+const MERGE_GAP: usize = 8;
+/// Characters of a changed span shown in the report
+const EXCERPT_LEN: usize = 60;
+/// Beyond this the diff settles for a coarser, still correct result
+const DIFF_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A node created by the import, with its text as written
+pub struct ImportedNode {
+    pub id: String,
+    pub blurb: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    /// Text of the original that is in no node
+    Dropped,
+    /// Text of the original that the nodes have differently
+    Rewritten,
+    /// Text in the nodes that is not in the original
+    Added,
+}
+
+/// A span where the reconstruction differs from the original, beyond whitespace
+pub struct Change {
+    pub kind: ChangeKind,
+    /// 1-based line of the original where the change starts
+    pub line: usize,
+    pub original: String,
+    pub imported: String,
+    /// Index of the node the change falls into
+    pub node: usize,
+}
+
+impl Change {
+    /// One line describing the change
+    pub fn describe(&self) -> String {
+        match self.kind {
+            ChangeKind::Dropped => format!("dropped \"{}\"", excerpt(&self.original)),
+            ChangeKind::Rewritten => format!(
+                "rewritten \"{}\" as \"{}\"",
+                excerpt(&self.original),
+                excerpt(&self.imported)
+            ),
+            ChangeKind::Added => format!("added \"{}\"", excerpt(&self.imported)),
+        }
+    }
+}
+
+pub struct Validation {
+    /// The reconstruction is identical to the original, whitespace included
+    pub exact: bool,
+    pub changes: Vec<Change>,
+    /// (node index, original text) for every node whose text differs from its part of
+    /// the original beyond whitespace
+    pub patches: Vec<(usize, String)>,
+}
+
+/// Diff the original against the node texts joined by [`SEPARATOR`], character by character.
+///
+/// Every node is assigned the part of the original that its text was aligned with, up to
+/// where the next node's part begins, so that dropped text goes to the node before it.
+pub fn validate(original: &str, nodes: &[ImportedNode]) -> Validation {
+    let mut reconstructed = String::new();
+    // Where each node's text starts in the reconstruction, in characters
+    let mut starts = Vec::new();
+    let mut position = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        if i > 0 {
+            reconstructed.push_str(SEPARATOR);
+            position += SEPARATOR.chars().count();
+        }
+        starts.push(position);
+        reconstructed.push_str(&node.content);
+        position += node.content.chars().count();
+    }
+    if reconstructed == original {
+        return Validation {
+            exact: true,
+            changes: Vec::new(),
+            patches: Vec::new(),
+        };
+    }
+
+    let old: Vec<char> = original.chars().collect();
+    let new: Vec<char> = reconstructed.chars().collect();
+    let diff = TextDiff::configure()
+        .timeout(DIFF_TIMEOUT)
+        .diff_chars(original, &reconstructed);
+    let ops = diff.ops();
+
+    // Where each node's part starts in the original
+    let bounds: Vec<usize> = starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            if i == 0 {
+                0
+            } else {
+                to_old(ops, start, old.len())
+            }
+        })
+        .collect();
+    let node_at = |position: usize| bounds.partition_point(|&b| b <= position).saturating_sub(1);
+
+    let patches = nodes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, node)| {
+            let end = bounds.get(i + 1).copied().unwrap_or(old.len());
+            let part: String = old[bounds[i]..end].iter().collect();
+            let part = part.trim_matches(|c| c == '\n' || c == '\r');
+            (!same_words(part, &node.content)).then(|| (i, part.to_string()))
+        })
+        .collect();
+
+    let changes = changed_spans(ops)
+        .into_iter()
+        .filter_map(|(old_range, new_range)| {
+            let original: String = old[old_range.clone()].iter().collect();
+            let imported: String = new[new_range].iter().collect();
+            if same_words(&original, &imported) {
+                return None;
+            }
+            let kind = if original.trim().is_empty() {
+                ChangeKind::Added
+            } else if imported.trim().is_empty() {
+                ChangeKind::Dropped
+            } else {
+                ChangeKind::Rewritten
+            };
+            // Locate the change by its first visible character
+            let leading = original.chars().take_while(|c| c.is_whitespace()).count();
+            let at = (old_range.start + leading).min(old_range.end);
+            Some(Change {
+                kind,
+                line: old[..at].iter().filter(|&&c| c == '\n').count() + 1,
+                original,
+                imported,
+                node: node_at(at),
+            })
+        })
+        .collect();
+
+    Validation {
+        exact: false,
+        changes,
+        patches,
+    }
+}
+
+/// The position in the original that a position in the reconstruction corresponds to.
+/// Deleted text counts as coming before the position.
+fn to_old(ops: &[DiffOp], position: usize, old_len: usize) -> usize {
+    for op in ops {
+        let (old, new) = (op.old_range(), op.new_range());
+        if position < new.end {
+            return match op.tag() {
+                DiffTag::Equal => old.start + position - new.start,
+                _ => old.end,
+            };
+        }
+    }
+    old_len
+}
+
+/// The (original, reconstruction) ranges of all changes, merging changes that are
+/// only a few characters apart
+fn changed_spans(ops: &[DiffOp]) -> Vec<(Range<usize>, Range<usize>)> {
+    let mut spans: Vec<(Range<usize>, Range<usize>)> = Vec::new();
+    for op in ops.iter().filter(|op| op.tag() != DiffTag::Equal) {
+        let (old, new) = (op.old_range(), op.new_range());
+        match spans.last_mut() {
+            Some((last_old, last_new)) if old.start - last_old.end < MERGE_GAP => {
+                last_old.end = old.end;
+                last_new.end = new.end;
+            }
+            _ => spans.push((old, new)),
+        }
+    }
+    spans
+}
+
+fn same_words(a: &str, b: &str) -> bool {
+    a.split_whitespace().eq(b.split_whitespace())
+}
+
+/// The text on one line, shortened to [`EXCERPT_LEN`] characters
+fn excerpt(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() > EXCERPT_LEN {
+        let mut short: String = text.chars().take(EXCERPT_LEN).collect();
+        short.push('…');
+        short
+    } else {
+        text
+    }
+}
@@ -1,21 +1,28 @@
-use crate::config::{FullConfig, LlmSettings};
+use crate::config::{FullConfig, LlmSettings, book_root, config_path};
 use crate::node::NodeManager;
+use anyhow::{Result, anyhow};
 use std::fs;
 
-pub fn run(blurb: Option<&str>) {
+pub fn run(blurb: Option<&str>) -> Result<()> {
     let starting_node_title = blurb.unwrap_or("Starting Node");
+    // Before anything is created, a bad blurb leaves no half-made book behind
+    NodeManager::validate_blurb(starting_node_title).map_err(|e| anyhow!(e))?;
+    fs::create_dir_all(book_root())?;
     let starting_node_id =
-        NodeManager::create_node(starting_node_title, starting_node_title, None, None).unwrap();
+        NodeManager::create_node(starting_node_title, starting_node_title, None, None)
+            .map_err(|e| anyhow!(e))?;
 
     let bok_config = FullConfig {
         llm: LlmSettings::default(),
         title: Some("My New Book".to_string()),
         author: Some("Unknown Author".to_string()),
         starting_node: Some(starting_node_id.to_string()),
+        style: None,
     };
 
-    let yaml_content = serde_yaml::to_string(&bok_config).unwrap();
+    let yaml_content = serde_yaml::to_string(&bok_config)?;
 
-    fs::write("bok.yaml", yaml_content).unwrap();
+    fs::write(config_path(), yaml_content)?;
     println!("Created bok.yaml and starting node directory.");
+    Ok(())
 }
@@ -1,3 +1,122 @@
-pub fn run(node: &str) {
-    println!("'bok lineedit {}' is not yet implemented", node);
+use crate::commands::review::{review_hunks, unified_diff};
+use crate::config::Settings;
+use crate::llm::get_llm_provider;
+use crate::node::NodeManager;
+use anyhow::{Result, anyhow, bail};
+use std::fs;
+use std::path::Path;
+
+/// Proposed edits saved for later review, next to the node's text.qmd
+pub const PROPOSED_FILE: &str = "text.qmd.proposed";
+
+/// The text.qmd the saved edits were proposed for, they replace the whole text
+pub const PROPOSED_BASE_FILE: &str = "text.qmd.proposed.base";
+
+/// What to do with the edits once they are proposed
+#[derive(Clone, Copy, PartialEq)]
+pub enum LineeditAction {
+    /// Go through the edits hunk by hunk
+    Interactive,
+    /// Apply all edits
+    Accept,
+    /// Save the edits as text.qmd.proposed without touching text.qmd
+    Propose,
+}
+
+/// Line edit a node's text. With `from_proposed` the edits saved by an earlier
+/// `--propose` run are used instead of asking the LLM again.
+pub async fn run(node_hash: &str, action: LineeditAction, from_proposed: bool) -> Result<()> {
+    let node_path = NodeManager::find_node_path(node_hash).map_err(|e| anyhow!(e))?;
+    let text_path = node_path.join("text.qmd");
+    let proposed_path = node_path.join(PROPOSED_FILE);
+    let base_path = node_path.join(PROPOSED_BASE_FILE);
+    let original = NodeManager::get_node_content(node_hash).map_err(|e| anyhow!(e))?;
+
+    let proposed = if from_proposed {
+        if !proposed_path.exists() {
+            bail!(
+                "No proposed edits for node '{}'. Run 'bok lineedit {} --propose' first.",
+                node_hash,
+                node_hash
+            );
+        }
+        // Applying the proposal over a text edited since would undo those edits
+        if fs::read_to_string(&base_path).ok().as_deref() != Some(original.as_str()) {
+            bail!(
+                "The text of node '{}' changed since the edits were proposed. Run 'bok lineedit {} --propose' again.",
+                node_hash,
+                node_hash
+            );
+        }
+        fs::read_to_string(&proposed_path)?
+    } else {
+        let settings = Settings::new()?;
+        let llm_provider = get_llm_provider(&settings.llm)?;
+        llm_provider.line_edit(&original).await?
+    };
+
+    if proposed == original {
+        println!("No edits proposed for node '{}'.", node_hash);
+        if from_proposed {
+            discard_proposal(&node_path)?;
+        }
+        return Ok(());
+    }
+
+    match action {
+        LineeditAction::Propose => {
+            print!("{}", unified_diff(&original, &proposed, "text.qmd"));
+            save_proposal(&node_path, &original, &proposed)?;
+            println!(
+                "Saved the proposed edits to '{}'. Review them with 'bok lineedit {} --review'.",
+                proposed_path.display(),
+                node_hash
+            );
+        }
+        LineeditAction::Accept => {
+            print!("{}", unified_diff(&original, &proposed, "text.qmd"));
+            fs::write(&text_path, &proposed)?;
+            println!("Applied all edits to '{}'.", text_path.display());
+            if from_proposed {
+                discard_proposal(&node_path)?;
+            }
+        }
+        LineeditAction::Interactive => {
+            let outcome = review_hunks(&original, &proposed);
+            if outcome.accepted > 0 {
+                fs::write(&text_path, &outcome.text)?;
+            }
+            println!(
+                "Applied {} of {} hunks to '{}'.",
+                outcome.accepted,
+                outcome.total,
+                text_path.display()
+            );
+            if !outcome.complete {
+                // Keep what was not looked at for another round, over the text as it is now
+                save_proposal(&node_path, &outcome.text, &proposed)?;
+                println!(
+                    "Review interrupted, the proposed edits are kept in '{}'.",
+                    proposed_path.display()
+                );
+            } else if from_proposed {
+                discard_proposal(&node_path)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Save edits for a later review together with the text they were proposed for
+fn save_proposal(node_path: &Path, base: &str, proposed: &str) -> Result<()> {
+    fs::write(node_path.join(PROPOSED_BASE_FILE), base)?;
+    fs::write(node_path.join(PROPOSED_FILE), proposed)?;
+    Ok(())
+}
+
+fn discard_proposal(node_path: &Path) -> Result<()> {
+    fs::remove_file(node_path.join(PROPOSED_FILE))?;
+    let _ = fs::remove_file(node_path.join(PROPOSED_BASE_FILE));
+    Ok(())
 }
@@ -1,8 +1,13 @@
 pub mod check;
 pub mod copyedit;
+pub mod embed;
 pub mod generate;
 pub mod import;
 pub mod init;
 pub mod lineedit;
 pub mod node;
+pub mod prompt;
+pub mod review;
+pub mod search;
+pub mod suggest;
 pub mod vis;
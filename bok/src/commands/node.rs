@@ -1,4 +1,5 @@
-use crate::node::NodeManager;
+use crate::commands::prompt;
+use crate::node::{NodeManager, Placement, RelationKind, Trash};
 
 pub fn add(blurb: &str, under: Option<&str>) {
     match NodeManager::create_node(blurb, blurb, under, None) {
@@ -7,10 +8,101 @@ pub fn add(blurb: &str, under: Option<&str>) {
     }
 }
 
-pub fn rm(node_hash: &str) {
+pub fn rm(node_hash: &str, yes: bool, dry_run: bool) {
+    let removal = match NodeManager::plan_removal(node_hash) {
+        Ok(removal) => removal,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return;
+        }
+    };
+
+    println!("The following nodes will be removed:");
+    NodeManager::print_nodes_recursive(std::slice::from_ref(&removal.node), "  ");
+    if !removal.relations.is_empty() {
+        println!("The following relations will be removed:");
+        for removed in &removal.relations {
+            println!(
+                "  {} -> {} ({})",
+                removed.source, removed.relation.target, removed.relation.kind
+            );
+        }
+    }
+
+    if dry_run {
+        return;
+    }
+    if !yes && !prompt::confirm("Move these nodes to the trash?") {
+        println!("Aborted.");
+        return;
+    }
+
     match NodeManager::remove_node(node_hash) {
-        Ok(path) => println!("Removed node '{}'", path.display()),
-        Err(e) => eprintln!("{}", e),
+        Ok(entry) => println!(
+            "Moved node '{}' to the trash. Use 'bok node restore {}' to bring it back.",
+            removal.node.path.display(),
+            entry.manifest.id
+        ),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+pub fn restore(node_hash: Option<&str>) {
+    let Some(node_hash) = node_hash else {
+        let entries = Trash::list();
+        if entries.is_empty() {
+            println!("The trash is empty.");
+        }
+        for entry in entries {
+            println!("{} {}", entry.manifest.id, entry.manifest.title);
+        }
+        return;
+    };
+
+    match NodeManager::restore_node(node_hash) {
+        Ok(path) => println!("Restored node '{}'", path.display()),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+pub fn rename(node_hash: &str, blurb: &str) {
+    match NodeManager::rename_node(node_hash, blurb) {
+        Ok(path) => println!("Renamed node to '{}'", path.display()),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+pub fn mv(node_hash: &str, under: Option<&str>, placement: Placement) {
+    match NodeManager::move_node(node_hash, under, placement) {
+        Ok(path) => println!("Moved node to '{}'", path.display()),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+pub fn merge(into: &str, from: &str) {
+    match NodeManager::merge_nodes(into, from) {
+        Ok(_) => println!(
+            "Merged node '{}' into '{}' and moved it to the trash.",
+            from, into
+        ),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+pub fn link(source: &str, target: &str, kind: RelationKind) {
+    match NodeManager::add_relation(source, target, kind) {
+        Ok(()) => println!("Linked '{}' to '{}' as {}.", source, target, kind),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+pub fn unlink(source: &str, target: &str, kind: Option<RelationKind>) {
+    match NodeManager::remove_relation(source, target, kind) {
+        Ok(count) => println!(
+            "Removed {} relation(s) from '{}' to '{}'.",
+            count, source, target
+        ),
+        Err(e) => eprintln!("Error: {}", e),
     }
 }
 
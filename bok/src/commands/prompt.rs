@@ -0,0 +1,20 @@
+use std::io::{self, BufRead, Write};
+
+/// Ask a question on the terminal and return the trimmed, lowercased answer,
+/// or None when stdin is closed
+pub fn ask(question: &str) -> Option<String> {
+    print!("{} ", question);
+    let _ = io::stdout().flush();
+
+    let mut answer = String::new();
+    match io::stdin().lock().read_line(&mut answer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(answer.trim().to_lowercase()),
+    }
+}
+
+/// Ask a yes/no question on the terminal. Anything but an explicit yes, including
+/// a closed stdin, counts as no.
+pub fn confirm(question: &str) -> bool {
+    ask(&format!("{} [y/N]", question)).is_some_and(|answer| matches!(answer.as_str(), "y" | "yes"))
+}
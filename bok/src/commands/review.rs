@@ -0,0 +1,120 @@
+use crate::commands::prompt::ask;
+use similar::{DiffOp, DiffTag, TextDiff};
+
+/// What came out of reviewing proposed edits hunk by hunk
+pub struct ReviewOutcome {
+    /// The original text with the accepted hunks applied
+    pub text: String,
+    pub accepted: usize,
+    pub total: usize,
+    /// False when the review was cut short, e.g. because stdin was closed
+    pub complete: bool,
+}
+
+/// Unified diff between the original and the proposed text
+pub fn unified_diff(original: &str, proposed: &str, original_name: &str) -> String {
+    TextDiff::from_lines(original, proposed)
+        .unified_diff()
+        .header(original_name, &format!("{} (proposed)", original_name))
+        .to_string()
+}
+
+/// Answers to a series of "apply this?" questions, in the spirit of `git add -p`:
+/// `y`/`n` decide one item, `a`/`d` apply or skip it and all remaining ones.
+pub struct Decisions {
+    rest: Option<bool>,
+    /// False once stdin was closed before all items were decided
+    pub complete: bool,
+}
+
+impl Default for Decisions {
+    fn default() -> Self {
+        Decisions {
+            rest: None,
+            complete: true,
+        }
+    }
+}
+
+impl Decisions {
+    /// Whether the remaining items are already decided, i.e. there is nothing to show
+    pub fn settled(&self) -> bool {
+        self.rest.is_some()
+    }
+
+    /// Ask about the next item. A closed stdin skips it and all remaining ones.
+    pub fn decide(&mut self, question: &str) -> bool {
+        if let Some(apply) = self.rest {
+            return apply;
+        }
+        loop {
+            match ask(&format!("{} [y,n,a,d]", question)).as_deref() {
+                Some("y") => return true,
+                Some("n") => return false,
+                Some("a") => {
+                    self.rest = Some(true);
+                    return true;
+                }
+                Some("d") => {
+                    self.rest = Some(false);
+                    return false;
+                }
+                Some(_) => println!(
+                    "y - apply this one, n - skip it, a - apply it and all remaining ones, d - skip it and all remaining ones"
+                ),
+                None => {
+                    println!();
+                    self.complete = false;
+                    self.rest = Some(false);
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+/// Walk through the hunks of the diff, asking for each whether to apply it.
+/// Unanswered hunks are never applied.
+pub fn review_hunks(original: &str, proposed: &str) -> ReviewOutcome {
+    let diff = TextDiff::from_lines(original, proposed);
+    let unified = diff.unified_diff();
+    let hunks: Vec<_> = unified.iter_hunks().collect();
+
+    let mut accepted_ops: Vec<DiffOp> = Vec::new();
+    let mut accepted = 0;
+    let mut decisions = Decisions::default();
+
+    for (i, hunk) in hunks.iter().enumerate() {
+        if !decisions.settled() {
+            print!("{}", hunk);
+        }
+        if decisions.decide(&format!("Apply this hunk ({}/{})?", i + 1, hunks.len())) {
+            accepted += 1;
+            accepted_ops.extend(hunk.ops().iter().filter(|op| op.tag() != DiffTag::Equal));
+        }
+    }
+
+    ReviewOutcome {
+        text: apply_ops(&diff, &accepted_ops),
+        accepted,
+        total: hunks.len(),
+        complete: decisions.complete,
+    }
+}
+
+/// Rebuild the text taking the new side of the accepted operations and the old side of the rest
+fn apply_ops(diff: &TextDiff<'_, '_, '_, str>, accepted_ops: &[DiffOp]) -> String {
+    let old_lines = diff.old_slices();
+    let new_lines = diff.new_slices();
+
+    let mut text = String::new();
+    for op in diff.ops() {
+        let lines = if accepted_ops.contains(op) {
+            &new_lines[op.new_range()]
+        } else {
+            &old_lines[op.old_range()]
+        };
+        text.extend(lines.iter().copied());
+    }
+    text
+}
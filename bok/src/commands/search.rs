@@ -0,0 +1,174 @@
+use crate::config::Settings;
+use crate::embeddings::EmbeddingStore;
+use crate::llm::{cosine_similarity, get_llm_provider};
+use crate::node::index::NodeIndex;
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+
+/// Title matches count this many times as much as matches in the text
+const TITLE_WEIGHT: f64 = 3.0;
+/// Characters of context shown before and after a match
+const SNIPPET_BEFORE: usize = 40;
+const SNIPPET_AFTER: usize = 80;
+
+#[derive(Serialize)]
+struct SearchResult {
+    id: String,
+    blurb: String,
+    score: f64,
+    /// Titles from the top of the book down to the node
+    tree: Vec<String>,
+    /// The node directory
+    path: PathBuf,
+    snippet: String,
+}
+
+/// `bok search`: rank the nodes by how well their title and text match the query
+pub async fn run(query: &str, semantic: bool, limit: usize, json: bool) -> Result<()> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Err(anyhow!("The query is empty"));
+    }
+    let index = NodeIndex::current();
+
+    let mut results = if semantic {
+        semantic_search(&index, query).await?
+    } else {
+        full_text_search(&index, query)
+    };
+    results.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+    results.truncate(limit);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+        return Ok(());
+    }
+    if results.is_empty() {
+        println!("No nodes match '{}'.", query);
+    }
+    for result in &results {
+        println!("{} {}", result.id, result.blurb);
+        println!("    {}", result.tree.join(" > "));
+        if !result.snippet.is_empty() {
+            println!("    {}", result.snippet);
+        }
+    }
+    Ok(())
+}
+
+fn read_text(index: &NodeIndex, id: &str) -> String {
+    index
+        .get(id)
+        .and_then(|entry| fs::read_to_string(entry.full_path().join("text.qmd")).ok())
+        .unwrap_or_default()
+}
+
+fn result(index: &NodeIndex, id: &str, score: f64, snippet: String) -> Option<SearchResult> {
+    let entry = index.get(id)?;
+    Some(SearchResult {
+        id: id.to_string(),
+        blurb: entry.title.clone(),
+        score: (score * 1000.0).round() / 1000.0,
+        tree: index.tree_path(id),
+        path: entry.full_path(),
+        snippet,
+    })
+}
+
+/// Nodes whose title or text contains every word of the query, case-insensitively.
+/// Scored by the number of occurrences, title matches weighted higher.
+fn full_text_search(index: &NodeIndex, query: &str) -> Vec<SearchResult> {
+    let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+
+    index
+        .entries()
+        .filter_map(|(id, entry)| {
+            let text = read_text(index, id);
+            let mut score = 0.0;
+            let mut first_match = None;
+            for term in &terms {
+                let in_title = find_all(&entry.title, term).len();
+                let in_text = find_all(&text, term);
+                if in_title == 0 && in_text.is_empty() {
+                    return None;
+                }
+                score += TITLE_WEIGHT * in_title as f64 + in_text.len() as f64;
+                first_match =
+                    first_match.or(in_text.first().map(|&start| (start, term.chars().count())));
+            }
+            let snippet = match first_match {
+                Some((start, len)) => snippet(&text, start, len),
+                None => snippet(&text, 0, 0),
+            };
+            result(index, id, score, snippet)
+        })
+        .collect()
+}
+
+/// All nodes, scored by the cosine similarity of their embedding to the query's
+async fn semantic_search(index: &NodeIndex, query: &str) -> Result<Vec<SearchResult>> {
+    let settings = Settings::new()?;
+    let llm = get_llm_provider(&settings.llm)?;
+    let mut store = EmbeddingStore::load(&settings.llm);
+    let (embeddings, _) = store.embed_book(&llm).await?;
+    let query_vector = llm
+        .embed(&[query.to_string()])
+        .await?
+        .pop()
+        .ok_or_else(|| anyhow!("The provider returned no embedding for the query"))?;
+
+    Ok(embeddings
+        .iter()
+        .filter_map(|(id, _, vector)| {
+            let score = cosine_similarity(&query_vector, vector) as f64;
+            result(index, id, score, snippet(&read_text(index, id), 0, 0))
+        })
+        .collect())
+}
+
+/// Byte offsets of the case-insensitive occurrences of a lowercase term
+fn find_all(haystack: &str, term: &str) -> Vec<usize> {
+    if term.is_empty() {
+        return Vec::new();
+    }
+    let term_chars = term.chars().count();
+    haystack
+        .char_indices()
+        .filter(|(start, _)| {
+            haystack[*start..]
+                .chars()
+                .flat_map(char::to_lowercase)
+                .take(term_chars)
+                .eq(term.chars())
+        })
+        .map(|(start, _)| start)
+        .collect()
+}
+
+/// One line of context around the `len` characters at byte offset `start`,
+/// whitespace collapsed
+fn snippet(text: &str, start: usize, len: usize) -> String {
+    let before: Vec<char> = text[..start]
+        .chars()
+        .rev()
+        .take(SNIPPET_BEFORE + 1)
+        .collect();
+    let after: Vec<char> = text[start..]
+        .chars()
+        .take(len + SNIPPET_AFTER + 1)
+        .collect();
+
+    let mut snippet = String::new();
+    if before.len() > SNIPPET_BEFORE {
+        snippet.push('…');
+    }
+    snippet.extend(before.iter().take(SNIPPET_BEFORE).rev());
+    snippet.extend(after.iter().take(len + SNIPPET_AFTER));
+    let mut snippet = snippet.split_whitespace().collect::<Vec<_>>().join(" ");
+    if after.len() > len + SNIPPET_AFTER {
+        snippet.push('…');
+    }
+    snippet
+}
@@ -0,0 +1,225 @@
+use crate::commands::check::Book;
+use crate::commands::review::Decisions;
+use crate::config::Settings;
+use crate::embeddings::EmbeddingStore;
+use crate::llm::{cosine_similarity, get_llm_provider};
+use crate::node::{NodeManager, RelationKind};
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Default minimum cosine similarity for a pair of nodes to be considered
+pub const DEFAULT_THRESHOLD: f64 = 0.5;
+
+/// What to do with the suggested relations
+#[derive(Clone, Copy, PartialEq)]
+pub enum SuggestAction {
+    /// Ask for each suggestion whether to add it
+    Interactive,
+    /// Add all suggestions
+    Accept,
+    /// Only list the suggestions
+    DryRun,
+    /// Print the suggestions as JSON, without adding any
+    Json,
+}
+
+pub struct SuggestLinksOptions<'a> {
+    /// Only suggest relations from or to this node
+    pub node: Option<&'a str>,
+    pub limit: usize,
+    pub threshold: f64,
+    /// Suggest see-also relations for similar embeddings without asking the LLM
+    pub no_llm: bool,
+    pub action: SuggestAction,
+}
+
+#[derive(Serialize)]
+struct LinkSuggestion {
+    source: String,
+    source_blurb: String,
+    target: String,
+    target_blurb: String,
+    kind: RelationKind,
+    similarity: f64,
+    reason: String,
+}
+
+/// `bok suggest links`: propose relations between similar nodes in different subtrees
+pub async fn links(options: &SuggestLinksOptions<'_>) -> Result<()> {
+    let settings = Settings::new()?;
+    let llm = get_llm_provider(&settings.llm)?;
+    let book = Book::scan()?;
+    if let Some(node) = options.node
+        && !book.contains(node)
+    {
+        return Err(anyhow!("Node '{}' not found.", node));
+    }
+
+    let mut store = EmbeddingStore::load(&settings.llm);
+    let (embeddings, _) = store.embed_book(&llm).await?;
+
+    let parents: HashMap<&str, Option<&str>> = book
+        .nodes
+        .iter()
+        .map(|node| (node.id.as_str(), node.parent.as_deref()))
+        .collect();
+    let ancestors: HashMap<&str, HashSet<&str>> = parents
+        .keys()
+        .map(|&id| {
+            let mut ancestors = HashSet::new();
+            let mut current = parents[id];
+            while let Some(parent) = current {
+                if !ancestors.insert(parent) {
+                    break;
+                }
+                current = parents.get(parent).copied().flatten();
+            }
+            (id, ancestors)
+        })
+        .collect();
+    let contains =
+        |ancestor: &str, id: &str| ancestors.get(id).is_some_and(|a| a.contains(ancestor));
+    let related: HashSet<(&str, &str)> = book
+        .nodes
+        .iter()
+        .filter_map(|scanned| Some((scanned.id.as_str(), scanned.node()?)))
+        .flat_map(|(id, node)| {
+            node.relations()
+                .iter()
+                .flat_map(move |r| [(id, r.target.as_str()), (r.target.as_str(), id)])
+        })
+        .collect();
+
+    // Pairs in different subtrees: neither contains the other and they are not siblings
+    let mut candidates = Vec::new();
+    for (n, (first, _, a)) in embeddings.iter().enumerate() {
+        for (second, _, b) in &embeddings[n + 1..] {
+            let (first, second) = (first.as_str(), second.as_str());
+            if options
+                .node
+                .is_some_and(|node| node != first && node != second)
+                || parents.get(first) == parents.get(second)
+                || contains(first, second)
+                || contains(second, first)
+                || related.contains(&(first, second))
+            {
+                continue;
+            }
+            let similarity = cosine_similarity(a, b) as f64;
+            if similarity >= options.threshold {
+                candidates.push((first, second, similarity));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    let blurbs: HashMap<&str, &str> = embeddings
+        .iter()
+        .map(|(id, blurb, _)| (id.as_str(), blurb.as_str()))
+        .collect();
+    let contents: HashMap<&str, &str> = book
+        .nodes
+        .iter()
+        .filter_map(|node| Some((node.id.as_str(), node.content.as_deref()?)))
+        .collect();
+    let text = |id: &str| {
+        EmbeddingStore::node_text(blurbs[id], contents.get(id).copied().unwrap_or_default())
+    };
+
+    let mut suggestions = Vec::new();
+    for (first, second, similarity) in candidates {
+        if suggestions.len() >= options.limit {
+            break;
+        }
+        let (source, target, kind, reason) = if options.no_llm {
+            (
+                first,
+                second,
+                RelationKind::SeeAlso,
+                "similar texts".to_string(),
+            )
+        } else {
+            let Some(suggestion) = llm.suggest_relation(&text(first), &text(second)).await? else {
+                continue;
+            };
+            let (source, target) = if suggestion.reversed {
+                (second, first)
+            } else {
+                (first, second)
+            };
+            (source, target, suggestion.kind, suggestion.reason)
+        };
+        suggestions.push(LinkSuggestion {
+            source: source.to_string(),
+            source_blurb: blurbs[source].to_string(),
+            target: target.to_string(),
+            target_blurb: blurbs[target].to_string(),
+            kind,
+            similarity: (similarity * 100.0).round() / 100.0,
+            reason,
+        });
+    }
+
+    if options.action == SuggestAction::Json {
+        println!("{}", serde_json::to_string_pretty(&suggestions)?);
+        return Ok(());
+    }
+    if suggestions.is_empty() {
+        println!("No relations to suggest.");
+        return Ok(());
+    }
+
+    let mut decisions = Decisions::default();
+    let mut added = 0;
+    for (i, suggestion) in suggestions.iter().enumerate() {
+        let add = match options.action {
+            SuggestAction::Accept => {
+                print_suggestion(suggestion);
+                true
+            }
+            SuggestAction::DryRun | SuggestAction::Json => {
+                print_suggestion(suggestion);
+                false
+            }
+            SuggestAction::Interactive => {
+                if !decisions.settled() {
+                    print_suggestion(suggestion);
+                }
+                decisions.decide(&format!(
+                    "Add this relation ({}/{})?",
+                    i + 1,
+                    suggestions.len()
+                ))
+            }
+        };
+        if !add {
+            continue;
+        }
+        match NodeManager::add_relation(&suggestion.source, &suggestion.target, suggestion.kind) {
+            Ok(()) => added += 1,
+            Err(e) => eprintln!("Warning: {}", e),
+        }
+    }
+
+    if options.action != SuggestAction::DryRun {
+        println!("Added {} of {} relations.", added, suggestions.len());
+    }
+    if !decisions.complete {
+        println!("Review interrupted, the remaining suggestions were skipped.");
+    }
+    Ok(())
+}
+
+fn print_suggestion(suggestion: &LinkSuggestion) {
+    println!(
+        "{} {} -> {} {} [{}] {:.2}",
+        suggestion.source,
+        suggestion.source_blurb,
+        suggestion.target,
+        suggestion.target_blurb,
+        suggestion.kind,
+        suggestion.similarity
+    );
+    println!("    {}", suggestion.reason);
+}
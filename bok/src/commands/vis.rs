@@ -1,33 +1,408 @@
-use crate::node::NodeManager;
+use crate::config::book_root;
+use crate::node::{Node, NodeManager, RelationKind};
+use clap::ValueEnum;
 use serde::Serialize;
-use std::path::Path;
+use std::collections::HashSet;
+
+/// JSON layout produced by `bok vis d3`
+#[derive(Clone, Copy, ValueEnum)]
+pub enum D3Shape {
+    /// Nested `{name, children}` objects for `d3.hierarchy`
+    Tree,
+    /// `{nodes, links}` for force-directed graphs
+    Graph,
+}
+
+#[derive(Serialize)]
+struct D3TreeNode {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    name: String,
+    depth: usize,
+    words: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    relations: Vec<D3Relation>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<D3TreeNode>,
+}
+
+#[derive(Serialize)]
+struct D3Relation {
+    target: String,
+    #[serde(rename = "type")]
+    relation_type: RelationKind,
+}
+
+#[derive(Serialize)]
+struct D3Graph {
+    nodes: Vec<D3GraphNode>,
+    links: Vec<D3Link>,
+}
 
 #[derive(Serialize)]
-struct VisNode {
+struct D3GraphNode {
     id: String,
-    title: String,
+    name: String,
+    depth: usize,
+    words: usize,
 }
 
-pub fn d3() {
-    let nodes = NodeManager::get_nodes_recursive(Path::new("."));
+#[derive(Serialize)]
+struct D3Link {
+    source: String,
+    target: String,
+    /// `child`, `after` or the kind of a non-hierarchical relation
+    #[serde(rename = "type")]
+    link_type: String,
+}
+
+pub fn d3(root: Option<&str>, depth: Option<usize>, shape: D3Shape) {
+    let nodes = match select_nodes(root, depth) {
+        Ok(nodes) => nodes,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return;
+        }
+    };
+
+    let json = match shape {
+        D3Shape::Tree => serde_json::to_string_pretty(&d3_tree(&nodes)),
+        D3Shape::Graph => serde_json::to_string_pretty(&d3_graph(&nodes)),
+    };
+    println!("{}", json.unwrap());
+}
+
+fn word_count(node: &Node) -> usize {
+    node.content()
+        .map(|content| content.split_whitespace().count())
+        .unwrap_or(0)
+}
+
+/// `d3.hierarchy` needs a single root, so several root nodes get a synthetic parent
+fn d3_tree(nodes: &[Node]) -> D3TreeNode {
+    fn convert(node: &Node, depth: usize, visible: &HashSet<String>) -> D3TreeNode {
+        D3TreeNode {
+            id: Some(node.id.clone()),
+            name: node.blurb().to_string(),
+            depth,
+            words: word_count(node),
+            relations: node
+                .relations()
+                .iter()
+                .filter(|r| visible.contains(&r.target))
+                .map(|r| D3Relation {
+                    target: r.target.clone(),
+                    relation_type: r.kind,
+                })
+                .collect(),
+            children: node
+                .children
+                .iter()
+                .map(|child| convert(child, depth + 1, visible))
+                .collect(),
+        }
+    }
+
+    let visible = visible_ids(nodes);
+    match nodes {
+        [single] => convert(single, 1, &visible),
+        _ => D3TreeNode {
+            id: None,
+            name: "bok".to_string(),
+            depth: 0,
+            words: 0,
+            relations: Vec::new(),
+            children: nodes
+                .iter()
+                .map(|node| convert(node, 1, &visible))
+                .collect(),
+        },
+    }
+}
 
-    fn flatten_nodes(nodes: &[crate::node::Node], result: &mut Vec<VisNode>) {
+fn d3_graph(nodes: &[Node]) -> D3Graph {
+    fn collect(nodes: &[Node], depth: usize, graph: &mut D3Graph) {
         for node in nodes {
-            result.push(VisNode {
+            graph.nodes.push(D3GraphNode {
                 id: node.id.clone(),
-                title: node.blurb().to_string(),
+                name: node.blurb().to_string(),
+                depth,
+                words: word_count(node),
             });
-            flatten_nodes(&node.children, result);
+            for child in &node.children {
+                graph.links.push(D3Link {
+                    source: node.id.clone(),
+                    target: child.id.clone(),
+                    link_type: "child".to_string(),
+                });
+            }
+            if let Some(after) = node.after()
+                && nodes.iter().any(|sibling| sibling.id == *after)
+            {
+                graph.links.push(D3Link {
+                    source: after.clone(),
+                    target: node.id.clone(),
+                    link_type: "after".to_string(),
+                });
+            }
+            collect(&node.children, depth + 1, graph);
+        }
+    }
+
+    let mut graph = D3Graph {
+        nodes: Vec::new(),
+        links: Vec::new(),
+    };
+    collect(nodes, 1, &mut graph);
+    for (source, target, kind) in visible_relations(nodes) {
+        graph.links.push(D3Link {
+            source,
+            target,
+            link_type: kind.to_string(),
+        });
+    }
+    graph
+}
+
+pub fn mermaid(root: Option<&str>, depth: Option<usize>, mindmap: bool) {
+    match select_nodes(root, depth) {
+        Ok(nodes) if mindmap => print!("{}", mermaid_mindmap(&nodes)),
+        Ok(nodes) => print!("{}", mermaid_graph(&nodes)),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+pub fn dot(root: Option<&str>, depth: Option<usize>) {
+    match select_nodes(root, depth) {
+        Ok(nodes) => print!("{}", dot_graph(&nodes)),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
+
+/// Select the nodes to visualize: the subtree of `root` (or all root-level nodes),
+/// cut off after `depth` levels
+fn select_nodes(root: Option<&str>, depth: Option<usize>) -> Result<Vec<Node>, String> {
+    let all_nodes = NodeManager::get_nodes_recursive(book_root());
+    let mut nodes = match root {
+        Some(root_id) => vec![
+            NodeManager::find_in_tree(&all_nodes, root_id)
+                .cloned()
+                .ok_or_else(|| format!("Node with hash '{}' not found.", root_id))?,
+        ],
+        None => all_nodes,
+    };
+
+    if let Some(depth) = depth {
+        truncate_depth(&mut nodes, depth);
+    }
+    Ok(nodes)
+}
+
+fn visible_ids(nodes: &[Node]) -> HashSet<String> {
+    fn collect(nodes: &[Node], ids: &mut HashSet<String>) {
+        for node in nodes {
+            ids.insert(node.id.clone());
+            collect(&node.children, ids);
+        }
+    }
+
+    let mut ids = HashSet::new();
+    collect(nodes, &mut ids);
+    ids
+}
+
+/// Relations (source, target, kind) whose both ends are part of the selection
+fn visible_relations(nodes: &[Node]) -> Vec<(String, String, RelationKind)> {
+    fn collect(
+        nodes: &[Node],
+        visible: &HashSet<String>,
+        relations: &mut Vec<(String, String, RelationKind)>,
+    ) {
+        for node in nodes {
+            for relation in node.relations() {
+                if visible.contains(&relation.target) {
+                    relations.push((node.id.clone(), relation.target.clone(), relation.kind));
+                }
+            }
+            collect(&node.children, visible, relations);
+        }
+    }
+
+    let visible = visible_ids(nodes);
+    let mut relations = Vec::new();
+    collect(nodes, &visible, &mut relations);
+    relations
+}
+
+fn truncate_depth(nodes: &mut [Node], depth: usize) {
+    for node in nodes {
+        if depth <= 1 {
+            node.children.clear();
+        } else {
+            truncate_depth(&mut node.children, depth - 1);
+        }
+    }
+}
+
+fn mermaid_graph(nodes: &[Node]) -> String {
+    fn declare(nodes: &[Node], out: &mut String) {
+        for node in nodes {
+            out.push_str(&format!(
+                "    n{}[\"{}\"]\n",
+                node.id,
+                escape_mermaid_label(node.blurb())
+            ));
+            declare(&node.children, out);
+        }
+    }
+
+    fn connect(nodes: &[Node], out: &mut String) {
+        for node in nodes {
+            for child in &node.children {
+                out.push_str(&format!("    n{} --> n{}\n", node.id, child.id));
+            }
+            connect(&node.children, out);
+        }
+    }
+
+    /// Dashed arrows from a node to the sibling placed right after it
+    fn connect_after(nodes: &[Node], out: &mut String) {
+        for node in nodes {
+            if let Some(after) = node.after()
+                && nodes.iter().any(|sibling| sibling.id == *after)
+            {
+                out.push_str(&format!("    n{} -.-> n{}\n", after, node.id));
+            }
+            connect_after(&node.children, out);
+        }
+    }
+
+    let mut out = String::from("graph TD\n");
+    declare(nodes, &mut out);
+    connect(nodes, &mut out);
+    connect_after(nodes, &mut out);
+    for (source, target, kind) in visible_relations(nodes) {
+        out.push_str(&format!("    n{} ==>|{}| n{}\n", source, kind, target));
+    }
+    out
+}
+
+/// Mindmaps have no edges besides the hierarchy, so `after` only shows up as the sibling order
+fn mermaid_mindmap(nodes: &[Node]) -> String {
+    fn push(nodes: &[Node], indent: usize, out: &mut String) {
+        for node in nodes {
+            out.push_str(&format!(
+                "{}n{}[\"{}\"]\n",
+                "  ".repeat(indent),
+                node.id,
+                escape_mermaid_label(node.blurb())
+            ));
+            push(&node.children, indent + 1, out);
         }
     }
 
-    let mut vis_nodes = Vec::new();
-    flatten_nodes(&nodes, &mut vis_nodes);
+    let mut out = String::from("mindmap\n");
+    // A mindmap needs exactly one root
+    if nodes.len() == 1 {
+        push(nodes, 1, &mut out);
+    } else {
+        out.push_str("  root((bok))\n");
+        push(nodes, 2, &mut out);
+    }
+    out
+}
+
+/// Graphviz digraph with one cluster per top-level chapter, i.e. per child of a root node
+fn dot_graph(nodes: &[Node]) -> String {
+    fn declare(node: &Node, indent: &str, out: &mut String) {
+        out.push_str(&format!(
+            "{}n{} [label=\"{}\"];\n",
+            indent,
+            node.id,
+            escape_dot_label(node.blurb())
+        ));
+    }
+
+    fn declare_subtree(nodes: &[Node], indent: &str, out: &mut String) {
+        for node in nodes {
+            declare(node, indent, out);
+            declare_subtree(&node.children, indent, out);
+        }
+    }
 
-    let json = serde_json::to_string_pretty(&vis_nodes).unwrap();
-    println!("{}", json);
+    fn connect(nodes: &[Node], out: &mut String) {
+        for node in nodes {
+            for child in &node.children {
+                out.push_str(&format!("    n{} -> n{};\n", node.id, child.id));
+            }
+            if let Some(after) = node.after()
+                && nodes.iter().any(|sibling| sibling.id == *after)
+            {
+                out.push_str(&format!(
+                    "    n{} -> n{} [style=dashed, constraint=false];\n",
+                    after, node.id
+                ));
+            }
+            connect(&node.children, out);
+        }
+    }
+
+    let mut out = String::from("digraph bok {\n    node [shape=box, style=rounded];\n\n");
+    for root in nodes {
+        declare(root, "    ", &mut out);
+        for chapter in &root.children {
+            out.push_str(&format!("    subgraph cluster_{} {{\n", chapter.id));
+            out.push_str(&format!(
+                "        label=\"{}\";\n",
+                escape_dot_label(chapter.blurb())
+            ));
+            declare(chapter, "        ", &mut out);
+            declare_subtree(&chapter.children, "        ", &mut out);
+            out.push_str("    }\n");
+        }
+    }
+    out.push('\n');
+    connect(nodes, &mut out);
+    for (source, target, kind) in visible_relations(nodes) {
+        out.push_str(&format!(
+            "    n{} -> n{} [style=dotted, constraint=false, label=\"{}\"];\n",
+            source, target, kind
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn escape_dot_label(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "")
 }
 
-pub fn mermaid() {
-    println!("'bok vis mermaid' is not yet implemented");
+/// Replace characters that terminate or confuse mermaid labels with entity codes
+fn escape_mermaid_label(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for c in label.chars() {
+        match c {
+            '"' => escaped.push_str("#quot;"),
+            '#' => escaped.push_str("#35;"),
+            '[' => escaped.push_str("#91;"),
+            ']' => escaped.push_str("#93;"),
+            '(' => escaped.push_str("#40;"),
+            ')' => escaped.push_str("#41;"),
+            '{' => escaped.push_str("#123;"),
+            '}' => escaped.push_str("#125;"),
+            '<' => escaped.push_str("#lt;"),
+            '>' => escaped.push_str("#gt;"),
+            '|' => escaped.push_str("#124;"),
+            ':' => escaped.push_str("#58;"),
+            ';' => escaped.push_str("#59;"),
+            '`' => escaped.push_str("#96;"),
+            '\n' | '\r' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
 }
@@ -1,6 +1,74 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Book configuration file, its directory is the book root
+pub const CONFIG_FILE: &str = "bok.yaml";
+
+/// Environment variable pointing at the book root, `--book` takes precedence
+pub const BOOK_ROOT_ENV: &str = "BOK_ROOT";
+
+/// Directory (in the book root) for bok's own bookkeeping: trash, caches, etc.
+pub const DATA_DIR: &str = ".bok";
+
+static BOOK_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Pin the book root for the rest of the process. Has no effect once [`book_root`] was used.
+pub fn set_book_root(root: PathBuf) {
+    let _ = BOOK_ROOT.set(root);
+}
+
+/// Whether the book root is known, see [`set_book_root`] and [`book_root`]
+pub fn book_root_pinned() -> bool {
+    BOOK_ROOT.get().is_some()
+}
+
+/// The book root given explicitly through `--book` or `BOK_ROOT`
+pub fn explicit_book_root(book_arg: Option<PathBuf>) -> Option<PathBuf> {
+    book_arg.or_else(|| {
+        env::var_os(BOOK_ROOT_ENV)
+            .filter(|root| !root.is_empty())
+            .map(PathBuf::from)
+    })
+}
+
+/// Directory containing bok.yaml, all node paths are resolved relative to it.
+///
+/// Unless pinned with [`set_book_root`], this is `BOK_ROOT` or the closest directory
+/// from the current one upwards that contains a bok.yaml, falling back to the current directory.
+/// Discovered roots are kept relative (`.`, `..`, `../..`) so printed paths stay short.
+pub fn book_root() -> &'static Path {
+    BOOK_ROOT.get_or_init(|| explicit_book_root(None).unwrap_or_else(discover_book_root))
+}
+
+fn discover_book_root() -> PathBuf {
+    let Ok(cwd) = env::current_dir() else {
+        return PathBuf::from(".");
+    };
+    let mut relative = PathBuf::from(".");
+    for dir in cwd.ancestors() {
+        if dir.join(CONFIG_FILE).exists() {
+            return relative;
+        }
+        relative = if relative == Path::new(".") {
+            PathBuf::from("..")
+        } else {
+            relative.join("..")
+        };
+    }
+    PathBuf::from(".")
+}
+
+pub fn config_path() -> PathBuf {
+    book_root().join(CONFIG_FILE)
+}
+
+pub fn data_dir() -> PathBuf {
+    book_root().join(DATA_DIR)
+}
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct LlmSettings {
@@ -8,6 +76,18 @@ pub struct LlmSettings {
     pub model: String,
     pub location: Option<String>,
     pub port: Option<u16>,
+    /// Model the provider embeds node texts with, when not the provider's default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+    /// API root of the anthropic and openai providers, e.g. `http://localhost:8080`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// Used when the provider's environment variable (e.g. `ANTHROPIC_API_KEY`) is not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Upper bound on the length of a response, in tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
 }
 
 impl Default for LlmSettings {
@@ -17,7 +97,131 @@ impl Default for LlmSettings {
             model: "qwen3:8b".to_string(),
             location: Some("http://localhost".to_string()),
             port: Some(11434),
+            embedding_model: None,
+            base_url: None,
+            api_key: None,
+            max_tokens: None,
+        }
+    }
+}
+
+impl LlmSettings {
+    /// The configured embedding model, by default `text-embedding-3-small` for the openai
+    /// provider and `nomic-embed-text` for the others
+    pub fn embedding_model(&self) -> &str {
+        let default = match self.provider.as_str() {
+            "openai" => "text-embedding-3-small",
+            _ => "nomic-embed-text",
+        };
+        self.embedding_model.as_deref().unwrap_or(default)
+    }
+
+    /// The configured API root without a trailing `/` or `/v1`, `default` if there is none
+    pub fn base_url<'a>(&'a self, default: &'a str) -> &'a str {
+        let url = self.base_url.as_deref().unwrap_or(default);
+        let url = url.trim_end_matches('/');
+        url.strip_suffix("/v1").unwrap_or(url)
+    }
+
+    /// The API key from the environment variable, or else from the configuration
+    pub fn api_key(&self, env_var: &str) -> Option<String> {
+        std::env::var(env_var)
+            .ok()
+            .filter(|key| !key.is_empty())
+            .or_else(|| self.api_key.clone())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Spelling {
+    Us,
+    Uk,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DashStyle {
+    /// word—word
+    Closed,
+    /// word — word
+    Spaced,
+    /// word – word, with an en dash
+    SpacedEn,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NumberStyle {
+    /// one to nine, 10 and up
+    WordsBelowTen,
+    /// one to ninety-nine, 100 and up
+    WordsBelowHundred,
+    Numerals,
+}
+
+/// House style used by `bok copyedit`, the `style` section of bok.yaml
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct StyleSheet {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oxford_comma: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spelling: Option<Spelling>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub em_dash: Option<DashStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub numbers: Option<NumberStyle>,
+    /// Variant → preferred spelling of a term, e.g. `e-mail: email`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub preferred_terms: BTreeMap<String, String>,
+    /// Free-form rules passed on to the editor as they are
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+}
+
+impl StyleSheet {
+    /// The style sheet as plain-language rules, for prompts and for showing to the author
+    pub fn rules(&self) -> Vec<String> {
+        let mut rules = Vec::new();
+        if let Some(oxford_comma) = self.oxford_comma {
+            rules.push(if oxford_comma {
+                "Use the Oxford (serial) comma before the last item of a list.".to_string()
+            } else {
+                "Do not use the Oxford (serial) comma.".to_string()
+            });
+        }
+        if let Some(spelling) = self.spelling {
+            rules.push(match spelling {
+                Spelling::Us => "Use US spelling.".to_string(),
+                Spelling::Uk => "Use UK spelling.".to_string(),
+            });
+        }
+        if let Some(em_dash) = self.em_dash {
+            rules.push(match em_dash {
+                DashStyle::Closed => "Write dashes as closed em dashes: word—word.".to_string(),
+                DashStyle::Spaced => "Write dashes as spaced em dashes: word — word.".to_string(),
+                DashStyle::SpacedEn => "Write dashes as spaced en dashes: word – word.".to_string(),
+            });
+        }
+        if let Some(numbers) = self.numbers {
+            rules.push(match numbers {
+                NumberStyle::WordsBelowTen => {
+                    "Spell out numbers below ten, use numerals from 10 on.".to_string()
+                }
+                NumberStyle::WordsBelowHundred => {
+                    "Spell out numbers below one hundred, use numerals from 100 on.".to_string()
+                }
+                NumberStyle::Numerals => "Always use numerals for numbers.".to_string(),
+            });
         }
+        for (variant, preferred) in &self.preferred_terms {
+            rules.push(format!(
+                "Write \"{}\" instead of \"{}\".",
+                preferred, variant
+            ));
+        }
+        rules.extend(self.notes.iter().cloned());
+        rules
     }
 }
 
@@ -28,16 +232,14 @@ pub struct FullConfig {
     pub title: Option<String>,
     pub author: Option<String>,
     pub starting_node: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<StyleSheet>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct Settings {
-    pub llm: LlmSettings,
-}
-
-impl Settings {
-    pub fn new() -> Result<Self, anyhow::Error> {
-        let config_path = PathBuf::from("bok.yaml");
+impl FullConfig {
+    /// Load the whole bok.yaml
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let config_path = config_path();
 
         if !config_path.exists() {
             eprintln!("Error: bok.yaml not found. Please run 'bok init' to create it.");
@@ -45,8 +247,23 @@ impl Settings {
         }
 
         let file_content = fs::read_to_string(config_path)?;
-        let config: FullConfig = serde_yaml::from_str(&file_content)?;
+        Ok(serde_yaml::from_str(&file_content)?)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Settings {
+    pub llm: LlmSettings,
+    pub style: StyleSheet,
+}
+
+impl Settings {
+    pub fn new() -> Result<Self, anyhow::Error> {
+        let config = FullConfig::load()?;
 
-        Ok(Settings { llm: config.llm })
+        Ok(Settings {
+            llm: config.llm,
+            style: config.style.unwrap_or_default(),
+        })
     }
 }
@@ -0,0 +1,147 @@
+use crate::config::{LlmSettings, config_path, data_dir};
+use crate::llm::Llm;
+use crate::node::index::NodeIndex;
+use anyhow::Result;
+use murmur3::murmur3_32;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+/// Texts sent to the provider per request
+const BATCH_SIZE: usize = 32;
+
+/// Node embeddings, cached in `.bok/embeddings.json`.
+///
+/// Each embedding is keyed by a hash of the embedded text, so only nodes whose blurb or
+/// text changed are embedded again. Switching the provider or the embedding model
+/// discards the whole cache, vectors of different models are not comparable.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EmbeddingStore {
+    /// `<provider>/<embedding model>` the vectors were made with
+    model: String,
+    nodes: BTreeMap<String, CachedEmbedding>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedEmbedding {
+    /// murmur3 hash of the embedded text
+    hash: u32,
+    vector: Vec<f32>,
+}
+
+/// What [`EmbeddingStore::embed_book`] did
+pub struct EmbeddingStats {
+    pub embedded: usize,
+    pub unchanged: usize,
+}
+
+impl EmbeddingStore {
+    fn store_path() -> PathBuf {
+        data_dir().join("embeddings.json")
+    }
+
+    /// Load the cache for the configured provider and embedding model
+    pub fn load(settings: &LlmSettings) -> EmbeddingStore {
+        let model = format!("{}/{}", settings.provider, settings.embedding_model());
+        fs::read_to_string(Self::store_path())
+            .ok()
+            .and_then(|content| serde_json::from_str::<EmbeddingStore>(&content).ok())
+            .filter(|store| store.model == model)
+            .unwrap_or(EmbeddingStore {
+                model,
+                nodes: BTreeMap::new(),
+            })
+    }
+
+    /// Only books get a cache on disk, like the node index
+    pub fn save(&self) -> Result<()> {
+        if !config_path().exists() {
+            return Ok(());
+        }
+        fs::create_dir_all(data_dir())?;
+        fs::write(Self::store_path(), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// The text a node is embedded as: its blurb followed by its text
+    pub fn node_text(blurb: &str, content: &str) -> String {
+        format!("{}\n\n{}", blurb, content.trim())
+    }
+
+    fn hash(text: &str) -> u32 {
+        murmur3_32(&mut Cursor::new(text.as_bytes()), 0).unwrap_or(0)
+    }
+
+    /// The cached embedding of a node, if it is up to date with `text`
+    pub fn get(&self, node_hash: &str, text: &str) -> Option<&[f32]> {
+        self.nodes
+            .get(node_hash)
+            .filter(|cached| cached.hash == Self::hash(text))
+            .map(|cached| cached.vector.as_slice())
+    }
+
+    /// Embed the (id, text) pairs whose cached embedding is missing or out of date.
+    /// Returns how many were embedded.
+    pub async fn update(&mut self, llm: &Llm, nodes: &[(String, String)]) -> Result<usize> {
+        let stale: Vec<&(String, String)> = nodes
+            .iter()
+            .filter(|(id, text)| self.get(id, text).is_none())
+            .collect();
+
+        for batch in stale.chunks(BATCH_SIZE) {
+            let texts: Vec<String> = batch.iter().map(|(_, text)| text.clone()).collect();
+            let vectors = llm.embed(&texts).await?;
+            for ((id, text), vector) in batch.iter().zip(vectors) {
+                self.nodes.insert(
+                    id.clone(),
+                    CachedEmbedding {
+                        hash: Self::hash(text),
+                        vector,
+                    },
+                );
+            }
+        }
+        Ok(stale.len())
+    }
+
+    /// Bring the embeddings of all nodes of the book up to date, forget removed nodes
+    /// and save the cache. Returns the (id, blurb) of every node with its embedding.
+    pub async fn embed_book(
+        &mut self,
+        llm: &Llm,
+    ) -> Result<(Vec<(String, String, Vec<f32>)>, EmbeddingStats)> {
+        let nodes: Vec<(String, String, String)> = NodeIndex::current()
+            .entries()
+            .filter_map(|(id, entry)| {
+                let content = fs::read_to_string(entry.full_path().join("text.qmd")).ok()?;
+                let text = Self::node_text(&entry.title, &content);
+                Some((id.clone(), entry.title.clone(), text))
+            })
+            .collect();
+
+        let texts: Vec<(String, String)> = nodes
+            .iter()
+            .map(|(id, _, text)| (id.clone(), text.clone()))
+            .collect();
+        let embedded = self.update(llm, &texts).await?;
+
+        let ids: HashSet<&String> = nodes.iter().map(|(id, _, _)| id).collect();
+        self.nodes.retain(|id, _| ids.contains(id));
+        self.save()?;
+
+        let stats = EmbeddingStats {
+            embedded,
+            unchanged: nodes.len() - embedded,
+        };
+        let embeddings = nodes
+            .into_iter()
+            .filter_map(|(id, blurb, text)| {
+                let vector = self.get(&id, &text)?.to_vec();
+                Some((id, blurb, vector))
+            })
+            .collect();
+        Ok((embeddings, stats))
+    }
+}
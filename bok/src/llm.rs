@@ -1,7 +1,14 @@
-use crate::config::LlmSettings;
+use crate::config::{DashStyle, LlmSettings, StyleSheet};
+use crate::llm_providers::ByteStream;
+use crate::llm_providers::anthropic::{AnthropicProvider, AnthropicStream};
 use crate::llm_providers::ollama::{OllamaProvider, OllamaStream};
+use crate::llm_providers::openai::{OpenAiProvider, OpenAiStream};
+use crate::node::RelationKind;
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
+use murmur3::murmur3_32;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
 
 #[async_trait]
 pub trait AsyncIterator {
@@ -9,6 +16,53 @@ pub trait AsyncIterator {
     async fn next(&mut self) -> Option<Self::Item>;
 }
 
+/// One copyediting issue, located by its 1-based line and column (in characters)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CopyeditSuggestion {
+    pub line: usize,
+    pub column: usize,
+    /// The exact text to replace, starting at line:column
+    pub original: String,
+    pub replacement: String,
+    /// spelling, grammar, punctuation, capitalization, numbers or terminology
+    pub category: String,
+    #[serde(default)]
+    pub explanation: String,
+}
+
+/// What a chapter lacks, as judged from its outline
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompletenessReport {
+    /// Gaps in the argument or coverage
+    #[serde(default)]
+    pub gaps: Vec<String>,
+    /// Topics the text announces but never delivers
+    #[serde(default)]
+    pub missing: Vec<String>,
+    #[serde(default)]
+    pub suggested_nodes: Vec<SuggestedNode>,
+}
+
+/// A child node the chapter could use
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuggestedNode {
+    pub blurb: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// A relation proposed between two node texts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationSuggestion {
+    pub kind: RelationKind,
+    /// True when the relation goes from the second text to the first
+    #[serde(default)]
+    pub reversed: bool,
+    /// One line explaining the relation
+    #[serde(default)]
+    pub reason: String,
+}
+
 #[async_trait]
 pub trait LlmProvider {
     type Iterator<'a>: AsyncIterator<Item = Result<(String, String)>> + Send + 'a
@@ -19,8 +73,52 @@ pub trait LlmProvider {
         &'a self,
         markdown_content: &'a str,
     ) -> Result<Self::Iterator<'a>>;
+
+    /// Line edit a text sentence by sentence (grammar, clarity, flow) while keeping
+    /// its meaning, voice and markdown structure. Returns the whole edited text.
+    async fn line_edit(&self, text: &str) -> Result<String>;
+
+    /// Copyedit a text against the book's style sheet. Returns one suggestion per issue
+    /// instead of a rewritten text, so that they can be applied selectively.
+    async fn copy_edit(&self, text: &str, style: &StyleSheet) -> Result<Vec<CopyeditSuggestion>>;
+
+    /// Judge whether a node's blurb still describes its text.
+    /// Returns the reason when it does not, None when it does.
+    async fn assess_blurb(&self, blurb: &str, text: &str) -> Result<Option<String>>;
+
+    /// Judge whether a chapter is complete, given its outline: the blurbs and texts of the
+    /// chapter and its subtree as markdown with one heading per node
+    async fn assess_completeness(&self, outline: &str) -> Result<CompletenessReport>;
+
+    /// Judge whether two node texts cover the same ground, so that one of them is redundant.
+    /// Returns the reason when they do, None when they do not.
+    async fn assess_duplicate(&self, first: &str, second: &str) -> Result<Option<String>>;
+
+    /// Embed each text as a vector, in the order of `texts`. Vectors of the same model
+    /// can be compared with [`cosine_similarity`].
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+
+    /// Judge whether two node texts from different parts of the book are related.
+    /// Returns the relation from the first to the second text (or the reverse), None when
+    /// they are not meaningfully related.
+    async fn suggest_relation(
+        &self,
+        first: &str,
+        second: &str,
+    ) -> Result<Option<RelationSuggestion>>;
+}
+
+/// Cosine of the angle between two vectors, 0 when either is zero
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 { 0.0 } else { dot / norms }
 }
 
+/// Dimensions of the dummy provider's embeddings
+const DUMMY_DIMENSIONS: usize = 64;
+
 pub struct DummyLlmProvider;
 
 pub struct DummyIterator<'a> {
@@ -64,21 +162,227 @@ impl LlmProvider for DummyLlmProvider {
     ) -> Result<Self::Iterator<'a>> {
         Ok(DummyIterator::new(markdown_content))
     }
+
+    /// Deterministic stand-in for an editor: collapses runs of spaces, drops spaces
+    /// before punctuation and capitalizes the first letter of every sentence
+    async fn line_edit(&self, text: &str) -> Result<String> {
+        let mut edited = String::with_capacity(text.len());
+        let mut sentence_start = true;
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                ' ' if edited.ends_with(' ') => {}
+                ' ' if matches!(chars.peek(), Some(',' | '.' | ';' | ':' | '!' | '?')) => {}
+                '.' | '!' | '?' | '\n' => {
+                    sentence_start = true;
+                    edited.push(c);
+                }
+                c if sentence_start && c.is_alphabetic() => {
+                    sentence_start = false;
+                    edited.extend(c.to_uppercase());
+                }
+                c => {
+                    if !c.is_whitespace() && !matches!(c, '#' | '-' | '*' | '>') {
+                        sentence_start = false;
+                    }
+                    edited.push(c);
+                }
+            }
+        }
+        Ok(edited)
+    }
+
+    /// Deterministic stand-in for a copyeditor: flags doubled words, the variants listed
+    /// in `preferred_terms` and, if a dash style is set, `--` used as a dash
+    async fn copy_edit(&self, text: &str, style: &StyleSheet) -> Result<Vec<CopyeditSuggestion>> {
+        let mut suggestions = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let suggest = |start: usize, original: &str, replacement: &str, category: &str| {
+                CopyeditSuggestion {
+                    line: index + 1,
+                    column: line[..start].chars().count() + 1,
+                    original: original.to_string(),
+                    replacement: replacement.to_string(),
+                    category: category.to_string(),
+                    explanation: String::new(),
+                }
+            };
+
+            let words: Vec<(usize, &str)> = line
+                .split_whitespace()
+                .map(|word| (word.as_ptr() as usize - line.as_ptr() as usize, word))
+                .collect();
+            for pair in words.windows(2) {
+                let ((start, first), (second_start, second)) = (pair[0], pair[1]);
+                if first.chars().all(char::is_alphabetic) && first.eq_ignore_ascii_case(second) {
+                    let original = &line[start..second_start + second.len()];
+                    suggestions.push(suggest(start, original, first, "grammar"));
+                }
+            }
+
+            let is_word_char = |c: char| c.is_alphanumeric();
+            for (variant, preferred) in &style.preferred_terms {
+                for (start, _) in line.match_indices(variant.as_str()) {
+                    let end = start + variant.len();
+                    if !line[..start].ends_with(is_word_char)
+                        && !line[end..].starts_with(is_word_char)
+                    {
+                        suggestions.push(suggest(start, variant, preferred, "terminology"));
+                    }
+                }
+            }
+
+            if let Some(dash_style) = style.em_dash {
+                let dash = match dash_style {
+                    DashStyle::Closed => "—",
+                    DashStyle::Spaced => " — ",
+                    DashStyle::SpacedEn => " – ",
+                };
+                let mut search = 0;
+                while let Some(offset) = line[search..].find("--") {
+                    let mut start = search + offset;
+                    let mut end = start + 2;
+                    if line[..start].ends_with(' ') && line[end..].starts_with(' ') {
+                        start -= 1;
+                        end += 1;
+                    }
+                    suggestions.push(suggest(start, &line[start..end], dash, "punctuation"));
+                    search = end;
+                }
+            }
+        }
+        suggestions.sort_by_key(|s| (s.line, s.column));
+        Ok(suggestions)
+    }
+
+    /// A blurb fits when at least one of its words of four or more letters occurs in the text
+    async fn assess_blurb(&self, blurb: &str, text: &str) -> Result<Option<String>> {
+        let text = text.to_lowercase();
+        let words: Vec<String> = blurb
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.chars().count() >= 4)
+            .map(str::to_lowercase)
+            .collect();
+        if words.is_empty() || words.iter().any(|word| text.contains(word.as_str())) {
+            Ok(None)
+        } else {
+            Ok(Some(format!(
+                "none of the words of the blurb '{}' occur in the text",
+                blurb
+            )))
+        }
+    }
+
+    /// Headings without text are gaps, `TODO: <topic>` lines are missing topics
+    /// that become suggested nodes
+    async fn assess_completeness(&self, outline: &str) -> Result<CompletenessReport> {
+        let mut report = CompletenessReport::default();
+        let mut current: Option<(&str, bool)> = None;
+        let flush = |current: Option<(&str, bool)>, report: &mut CompletenessReport| {
+            if let Some((heading, false)) = current {
+                report.gaps.push(format!("'{}' has no text yet", heading));
+            }
+        };
+
+        for line in outline.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with('#') {
+                flush(current.take(), &mut report);
+                current = Some((trimmed.trim_start_matches('#').trim(), false));
+            } else if let Some(topic) = trimmed.strip_prefix("TODO:") {
+                let topic = topic.trim();
+                report
+                    .missing
+                    .push(format!("announced but not covered: {}", topic));
+                report.suggested_nodes.push(SuggestedNode {
+                    blurb: topic.to_string(),
+                    reason: "marked as TODO".to_string(),
+                });
+            } else if !trimmed.is_empty()
+                && let Some((_, has_text)) = current.as_mut()
+            {
+                *has_text = true;
+            }
+        }
+        flush(current, &mut report);
+        Ok(report)
+    }
+
+    /// Two texts are duplicates when every word of the shorter one occurs in the longer one
+    async fn assess_duplicate(&self, first: &str, second: &str) -> Result<Option<String>> {
+        let words = |text: &str| -> std::collections::BTreeSet<String> {
+            text.split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .map(str::to_lowercase)
+                .collect()
+        };
+        let (first, second) = (words(first), words(second));
+        let (shorter, longer) = if first.len() <= second.len() {
+            (&first, &second)
+        } else {
+            (&second, &first)
+        };
+        if !shorter.is_empty() && shorter.is_subset(longer) {
+            Ok(Some(
+                "every word of one text occurs in the other".to_string(),
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Feature hashing: every lowercased word adds or subtracts one in the dimension its
+    /// hash picks, so texts sharing words point in similar directions
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts
+            .iter()
+            .map(|text| {
+                let mut vector = vec![0.0; DUMMY_DIMENSIONS];
+                for word in text
+                    .split(|c: char| !c.is_alphanumeric())
+                    .filter(|word| !word.is_empty())
+                {
+                    let hash = murmur3_32(&mut Cursor::new(word.to_lowercase()), 0).unwrap_or(0);
+                    let sign = if hash & 1 == 0 { 1.0 } else { -1.0 };
+                    vector[(hash >> 1) as usize % DUMMY_DIMENSIONS] += sign;
+                }
+                vector
+            })
+            .collect())
+    }
+
+    /// Texts sharing at least two words of five or more letters are related as see-also
+    async fn suggest_relation(
+        &self,
+        first: &str,
+        second: &str,
+    ) -> Result<Option<RelationSuggestion>> {
+        let words = |text: &str| -> std::collections::BTreeSet<String> {
+            text.split(|c: char| !c.is_alphanumeric())
+                .filter(|word| word.chars().count() >= 5)
+                .map(str::to_lowercase)
+                .collect()
+        };
+        let shared: Vec<String> = words(first).intersection(&words(second)).cloned().collect();
+        Ok((shared.len() >= 2).then(|| RelationSuggestion {
+            kind: RelationKind::SeeAlso,
+            reversed: false,
+            reason: format!("both are about {}", shared.join(", ")),
+        }))
+    }
 }
 
 pub enum Llm {
     Ollama(OllamaProvider),
+    Anthropic(AnthropicProvider),
+    OpenAi(OpenAiProvider),
     Dummy(DummyLlmProvider),
 }
 
 pub enum LlmIterator<'a> {
-    Ollama(
-        OllamaStream<
-            std::pin::Pin<
-                Box<dyn futures::Stream<Item = Result<bytes::Bytes, std::io::Error>> + Send>,
-            >,
-        >,
-    ),
+    Ollama(OllamaStream<ByteStream>),
+    Anthropic(AnthropicStream),
+    OpenAi(OpenAiStream),
     Dummy(DummyIterator<'a>),
 }
 
@@ -89,6 +393,8 @@ impl<'a> AsyncIterator for LlmIterator<'a> {
     async fn next(&mut self) -> Option<Self::Item> {
         match self {
             LlmIterator::Ollama(iter) => iter.next().await,
+            LlmIterator::Anthropic(iter) => iter.next().await,
+            LlmIterator::OpenAi(iter) => iter.next().await,
             LlmIterator::Dummy(iter) => iter.next().await,
         }
     }
@@ -104,33 +410,110 @@ impl Llm {
                 let iterator = provider.dissect_markdown(markdown_content).await?;
                 Ok(LlmIterator::Ollama(iterator))
             }
+            Llm::Anthropic(provider) => {
+                let iterator = provider.dissect_markdown(markdown_content).await?;
+                Ok(LlmIterator::Anthropic(iterator))
+            }
+            Llm::OpenAi(provider) => {
+                let iterator = provider.dissect_markdown(markdown_content).await?;
+                Ok(LlmIterator::OpenAi(iterator))
+            }
             Llm::Dummy(provider) => {
                 let iterator = provider.dissect_markdown(markdown_content).await?;
                 Ok(LlmIterator::Dummy(iterator))
             }
         }
     }
+
+    pub async fn line_edit(&self, text: &str) -> Result<String> {
+        match self {
+            Llm::Ollama(provider) => provider.line_edit(text).await,
+            Llm::Anthropic(provider) => provider.line_edit(text).await,
+            Llm::OpenAi(provider) => provider.line_edit(text).await,
+            Llm::Dummy(provider) => provider.line_edit(text).await,
+        }
+    }
+
+    pub async fn copy_edit(
+        &self,
+        text: &str,
+        style: &StyleSheet,
+    ) -> Result<Vec<CopyeditSuggestion>> {
+        match self {
+            Llm::Ollama(provider) => provider.copy_edit(text, style).await,
+            Llm::Anthropic(provider) => provider.copy_edit(text, style).await,
+            Llm::OpenAi(provider) => provider.copy_edit(text, style).await,
+            Llm::Dummy(provider) => provider.copy_edit(text, style).await,
+        }
+    }
+
+    pub async fn assess_blurb(&self, blurb: &str, text: &str) -> Result<Option<String>> {
+        match self {
+            Llm::Ollama(provider) => provider.assess_blurb(blurb, text).await,
+            Llm::Anthropic(provider) => provider.assess_blurb(blurb, text).await,
+            Llm::OpenAi(provider) => provider.assess_blurb(blurb, text).await,
+            Llm::Dummy(provider) => provider.assess_blurb(blurb, text).await,
+        }
+    }
+
+    pub async fn assess_completeness(&self, outline: &str) -> Result<CompletenessReport> {
+        match self {
+            Llm::Ollama(provider) => provider.assess_completeness(outline).await,
+            Llm::Anthropic(provider) => provider.assess_completeness(outline).await,
+            Llm::OpenAi(provider) => provider.assess_completeness(outline).await,
+            Llm::Dummy(provider) => provider.assess_completeness(outline).await,
+        }
+    }
+
+    pub async fn assess_duplicate(&self, first: &str, second: &str) -> Result<Option<String>> {
+        match self {
+            Llm::Ollama(provider) => provider.assess_duplicate(first, second).await,
+            Llm::Anthropic(provider) => provider.assess_duplicate(first, second).await,
+            Llm::OpenAi(provider) => provider.assess_duplicate(first, second).await,
+            Llm::Dummy(provider) => provider.assess_duplicate(first, second).await,
+        }
+    }
+
+    pub async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        match self {
+            Llm::Ollama(provider) => provider.embed(texts).await,
+            Llm::Anthropic(provider) => provider.embed(texts).await,
+            Llm::OpenAi(provider) => provider.embed(texts).await,
+            Llm::Dummy(provider) => provider.embed(texts).await,
+        }
+    }
+
+    pub async fn suggest_relation(
+        &self,
+        first: &str,
+        second: &str,
+    ) -> Result<Option<RelationSuggestion>> {
+        match self {
+            Llm::Ollama(provider) => provider.suggest_relation(first, second).await,
+            Llm::Anthropic(provider) => provider.suggest_relation(first, second).await,
+            Llm::OpenAi(provider) => provider.suggest_relation(first, second).await,
+            Llm::Dummy(provider) => provider.suggest_relation(first, second).await,
+        }
+    }
 }
 
 pub fn get_llm_provider(settings: &LlmSettings) -> Result<Llm> {
     match settings.provider.as_str() {
         "ollama" => {
-            println!("Using Ollama provider.");
+            eprintln!("Using Ollama provider.");
             Ok(Llm::Ollama(OllamaProvider::new(settings.clone())))
         }
         "dummy" => {
-            println!("Using Dummy provider.");
+            eprintln!("Using Dummy provider.");
             Ok(Llm::Dummy(DummyLlmProvider))
         }
         "anthropic" => {
-            // TODO: Implement AnthropicProvider
-            println!("Using Anthropic provider (dummy implementation)");
-            Ok(Llm::Dummy(DummyLlmProvider))
+            eprintln!("Using Anthropic provider.");
+            Ok(Llm::Anthropic(AnthropicProvider::new(settings.clone())?))
         }
         "openai" => {
-            // TODO: Implement OpenAIProvider
-            println!("Using OpenAI provider (dummy implementation)");
-            Ok(Llm::Dummy(DummyLlmProvider))
+            eprintln!("Using OpenAI provider.");
+            Ok(Llm::OpenAi(OpenAiProvider::new(settings.clone())))
         }
         _ => Err(anyhow!("Unknown LLM provider: {}", settings.provider)),
     }
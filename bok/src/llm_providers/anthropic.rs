@@ -0,0 +1,241 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use reqwest::{Client, Response};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{LlmSettings, StyleSheet};
+use crate::llm::{
+    AsyncIterator, CompletenessReport, CopyeditSuggestion, LlmProvider, RelationSuggestion,
+};
+use crate::llm_providers::prompts::{self, Prompt};
+use crate::llm_providers::{ApiError, EventData, UnitLines, check_status, truncated};
+
+const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+const API_VERSION: &str = "2023-06-01";
+const API_KEY_VAR: &str = "ANTHROPIC_API_KEY";
+const DEFAULT_MAX_TOKENS: u32 = 8192;
+
+/// Claude through the Anthropic Messages API
+#[derive(Debug)]
+pub struct AnthropicProvider {
+    pub settings: LlmSettings,
+    api_key: String,
+}
+
+impl AnthropicProvider {
+    pub fn new(settings: LlmSettings) -> Result<Self> {
+        let api_key = settings.api_key(API_KEY_VAR).ok_or_else(|| {
+            anyhow!(
+                "The anthropic provider needs an API key: set {} or llm.api_key in bok.yaml",
+                API_KEY_VAR
+            )
+        })?;
+        Ok(AnthropicProvider { settings, api_key })
+    }
+
+    fn max_tokens(&self) -> u32 {
+        self.settings.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS)
+    }
+
+    async fn send(&self, prompt: &Prompt, stream: bool) -> Result<Response> {
+        eprintln!("Using {}", self.settings.model);
+        let response = Client::new()
+            .post(format!(
+                "{}/v1/messages",
+                self.settings.base_url(DEFAULT_BASE_URL)
+            ))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
+            .json(&MessagesRequest {
+                model: &self.settings.model,
+                max_tokens: self.max_tokens(),
+                system: prompt.system,
+                messages: [Message {
+                    role: "user",
+                    content: &prompt.user,
+                }],
+                stream,
+            })
+            .send()
+            .await?;
+        check_status(response, "Anthropic").await
+    }
+
+    /// Send a prompt and wait for the whole, non-streamed response
+    async fn complete(&self, prompt: &Prompt) -> Result<String> {
+        let response: MessagesResponse = self.send(prompt, false).await?.json().await?;
+        if response.stop_reason.as_deref() == Some("max_tokens") {
+            return Err(truncated());
+        }
+        Ok(response
+            .content
+            .into_iter()
+            .filter_map(|block| block.text)
+            .collect())
+    }
+}
+
+#[derive(Serialize)]
+struct MessagesRequest<'a> {
+    model: &'a str,
+    max_tokens: u32,
+    system: &'a str,
+    messages: [Message<'a>; 1],
+    stream: bool,
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    #[serde(default)]
+    stop_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContentBlock {
+    /// Only set for text blocks
+    #[serde(default)]
+    text: Option<String>,
+}
+
+/// The data of a server-sent event of a streamed response
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    ContentBlockDelta {
+        delta: ContentBlock,
+    },
+    MessageDelta {
+        delta: MessageDelta,
+    },
+    MessageStop,
+    Error {
+        error: ApiError,
+    },
+    /// message_start, content_block_start and _stop, ping
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageDelta {
+    #[serde(default)]
+    stop_reason: Option<String>,
+}
+
+pub struct AnthropicStream {
+    events: EventData,
+    lines: UnitLines,
+    done: bool,
+    truncated: bool,
+}
+
+#[async_trait]
+impl AsyncIterator for AnthropicStream {
+    type Item = Result<(String, String)>;
+
+    async fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(unit) = self.lines.next_unit() {
+                return Some(unit);
+            }
+            if self.done {
+                if std::mem::take(&mut self.truncated) {
+                    return Some(Err(truncated()));
+                }
+                return self.lines.finish();
+            }
+
+            let data = match self.events.next().await {
+                Some(Ok(data)) => data,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.done = true;
+                    continue;
+                }
+            };
+            match serde_json::from_str::<StreamEvent>(&data) {
+                Ok(StreamEvent::ContentBlockDelta { delta }) => {
+                    if let Some(text) = delta.text {
+                        self.lines.push(&text);
+                    }
+                }
+                Ok(StreamEvent::MessageDelta { delta }) => {
+                    self.truncated = delta.stop_reason.as_deref() == Some("max_tokens");
+                }
+                Ok(StreamEvent::MessageStop) => self.done = true,
+                Ok(StreamEvent::Error { error }) => {
+                    self.done = true;
+                    return Some(Err(anyhow!("Anthropic API error: {}", error.message)));
+                }
+                Ok(StreamEvent::Other) => {}
+                Err(e) => {
+                    return Some(Err(anyhow!("Failed to parse stream event: {}", e)));
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for AnthropicProvider {
+    type Iterator<'a> = AnthropicStream;
+
+    async fn dissect_markdown<'a>(
+        &'a self,
+        markdown_content: &'a str,
+    ) -> Result<Self::Iterator<'a>> {
+        let response = self.send(&prompts::dissect(markdown_content), true).await?;
+        Ok(AnthropicStream {
+            events: EventData::new(response),
+            lines: UnitLines::default(),
+            done: false,
+            truncated: false,
+        })
+    }
+
+    async fn line_edit(&self, text: &str) -> Result<String> {
+        self.complete(&prompts::line_edit(text)).await
+    }
+
+    async fn copy_edit(&self, text: &str, style: &StyleSheet) -> Result<Vec<CopyeditSuggestion>> {
+        let prompt = prompts::copy_edit(text, style);
+        prompts::parse_copy_edit(&self.complete(&prompt).await?)
+    }
+
+    async fn assess_blurb(&self, blurb: &str, text: &str) -> Result<Option<String>> {
+        let prompt = prompts::assess_blurb(blurb, text);
+        prompts::parse_assess_blurb(&self.complete(&prompt).await?)
+    }
+
+    async fn assess_completeness(&self, outline: &str) -> Result<CompletenessReport> {
+        let prompt = prompts::assess_completeness(outline);
+        prompts::parse_assess_completeness(&self.complete(&prompt).await?)
+    }
+
+    async fn assess_duplicate(&self, first: &str, second: &str) -> Result<Option<String>> {
+        let prompt = prompts::assess_duplicate(first, second);
+        prompts::parse_assess_duplicate(&self.complete(&prompt).await?)
+    }
+
+    async fn embed(&self, _texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Err(anyhow!(
+            "The Anthropic API has no embeddings, use the ollama or openai provider for commands that need them"
+        ))
+    }
+
+    async fn suggest_relation(
+        &self,
+        first: &str,
+        second: &str,
+    ) -> Result<Option<RelationSuggestion>> {
+        let prompt = prompts::suggest_relation(first, second);
+        prompts::parse_suggest_relation(&self.complete(&prompt).await?)
+    }
+}
@@ -1 +1,126 @@
+pub mod anthropic;
 pub mod ollama;
+pub mod openai;
+pub mod prompts;
+
+use std::io;
+use std::pin::Pin;
+
+use anyhow::{Result, anyhow};
+use bytes::Bytes;
+use futures::TryStreamExt;
+use futures::stream::Stream;
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio_util::io::StreamReader;
+
+/// Boxed byte stream of an HTTP response body
+pub type ByteStream = Pin<Box<dyn Stream<Item = Result<Bytes, io::Error>> + Send>>;
+
+/// Collects streamed text and splits it into the lines of the dissection protocol:
+/// one `["blurb", "content"]` JSON array per line.
+#[derive(Default)]
+pub struct UnitLines {
+    buffer: String,
+}
+
+impl UnitLines {
+    pub fn push(&mut self, text: &str) {
+        self.buffer.push_str(text);
+    }
+
+    /// The next complete line as a (blurb, content) pair, skipping blank lines
+    pub fn next_unit(&mut self) -> Option<Result<(String, String)>> {
+        while let Some(newline_pos) = self.buffer.find('\n') {
+            let line: String = self.buffer.drain(..=newline_pos).collect();
+            if !line.trim().is_empty() {
+                return Some(parse_unit(&line));
+            }
+        }
+        None
+    }
+
+    /// The last line, once the stream has ended without a trailing newline
+    pub fn finish(&mut self) -> Option<Result<(String, String)>> {
+        let line = std::mem::take(&mut self.buffer);
+        (!line.trim().is_empty()).then(|| parse_unit(&line))
+    }
+}
+
+/// The `data:` payloads of a server-sent event stream. The other fields of an event
+/// (`event:`, `id:`) are skipped, the payloads carry everything the providers need.
+pub struct EventData {
+    reader: BufReader<StreamReader<ByteStream, Bytes>>,
+}
+
+impl EventData {
+    pub fn new(response: Response) -> Self {
+        let byte_stream = response.bytes_stream().map_err(io::Error::other);
+        let stream_reader = StreamReader::new(Box::pin(byte_stream) as ByteStream);
+        EventData {
+            reader: BufReader::new(stream_reader),
+        }
+    }
+
+    /// The next payload, None once the stream has ended
+    pub async fn next(&mut self) -> Option<Result<String>> {
+        loop {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line).await {
+                Ok(0) => return None,
+                Ok(_) => {
+                    if let Some(data) = line.strip_prefix("data:") {
+                        return Some(Ok(data.trim().to_string()));
+                    }
+                }
+                Err(e) => return Some(Err(anyhow!("IO error reading stream: {}", e))),
+            }
+        }
+    }
+}
+
+/// The `{"error": {"message": ...}}` body both the Anthropic and OpenAI APIs fail with
+#[derive(Debug, Deserialize)]
+pub struct ErrorResponse {
+    pub error: ApiError,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiError {
+    pub message: String,
+}
+
+/// Turn an unsuccessful response into an error carrying the API's message
+pub async fn check_status(response: Response, api: &str) -> Result<Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    Err(status_error(api, status, body))
+}
+
+/// The error for a failed request whose body has been read already
+pub fn status_error(api: &str, status: StatusCode, body: String) -> anyhow::Error {
+    let message = serde_json::from_str::<ErrorResponse>(&body)
+        .map(|e| e.error.message)
+        .unwrap_or(body);
+    anyhow!("{} API returned {}: {}", api, status, message)
+}
+
+/// The error for a response that hit the token limit, it would be incomplete
+pub fn truncated() -> anyhow::Error {
+    anyhow!("The response was cut off at the token limit, raise llm.max_tokens in bok.yaml")
+}
+
+fn parse_unit(line: &str) -> Result<(String, String)> {
+    match serde_json::from_str::<Vec<String>>(line.trim()) {
+        Ok(parts) if parts.len() == 2 => {
+            let mut parts = parts.into_iter();
+            Ok((parts.next().unwrap(), parts.next().unwrap()))
+        }
+        Ok(_) => Err(anyhow!("Expected JSON array with 2 elements")),
+        Err(e) => Err(anyhow!("Failed to parse JSON array: {}", e)),
+    }
+}
@@ -1,5 +1,4 @@
 use std::io;
-use std::pin::Pin;
 
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
@@ -11,8 +10,12 @@ use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio_util::io::StreamReader;
 
-use crate::config::LlmSettings;
-use crate::llm::{AsyncIterator, LlmProvider};
+use crate::config::{LlmSettings, StyleSheet};
+use crate::llm::{
+    AsyncIterator, CompletenessReport, CopyeditSuggestion, LlmProvider, RelationSuggestion,
+};
+use crate::llm_providers::prompts::{self, Prompt};
+use crate::llm_providers::{ByteStream, UnitLines};
 
 #[derive(Debug)]
 pub struct OllamaProvider {
@@ -23,6 +26,46 @@ impl OllamaProvider {
     pub fn new(settings: LlmSettings) -> Self {
         OllamaProvider { settings }
     }
+
+    fn generate_url(&self) -> String {
+        self.api_url("generate")
+    }
+
+    fn api_url(&self, endpoint: &str) -> String {
+        let ollama_url = self
+            .settings
+            .location
+            .as_deref()
+            .unwrap_or("http://localhost");
+        let ollama_port = self.settings.port.unwrap_or(11434);
+        format!("{}:{}/api/{}", ollama_url, ollama_port, endpoint)
+    }
+
+    /// Send a prompt and wait for the whole, non-streamed response
+    async fn generate(&self, prompt: &Prompt, format: Option<&str>) -> Result<String> {
+        eprintln!("Using {}", self.settings.model);
+        let request_body = serde_json::to_string(&ChatRequest {
+            model: &self.settings.model,
+            stream: false,
+            think: false,
+            prompt: &prompt.combined(),
+            format,
+        })?;
+
+        let response: GenerateResponse = Client::new()
+            .post(self.generate_url())
+            .header("Content-Type", "application/json")
+            .body(request_body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        response
+            .response
+            .ok_or_else(|| anyhow!("Ollama returned no response"))
+    }
 }
 
 #[derive(Serialize)]
@@ -31,6 +74,20 @@ struct ChatRequest<'a> {
     stream: bool,
     think: bool,
     prompt: &'a str,
+    /// `json` constrains the response to a JSON value
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct EmbedRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbedResponse {
+    embeddings: Vec<Vec<f32>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -44,7 +101,7 @@ where
     S: Stream<Item = Result<Bytes, io::Error>> + Unpin + Send,
 {
     reader: BufReader<StreamReader<S, Bytes>>,
-    buffer: String,
+    lines: UnitLines,
 }
 
 #[async_trait]
@@ -56,59 +113,23 @@ where
 
     async fn next(&mut self) -> Option<Self::Item> {
         loop {
+            if let Some(unit) = self.lines.next_unit() {
+                return Some(unit);
+            }
             // Read next line from the stream (each line is a GenerateResponse JSON)
             let mut line = String::new();
             match self.reader.read_line(&mut line).await {
-                Ok(0) => {
-                    // End of stream
-                    return None;
-                }
-                Ok(_) => {
-                    // Parse the line as GenerateResponse
-                    match serde_json::from_str::<GenerateResponse>(&line) {
-                        Ok(gen_response) => {
-                            if let Some(response_text) = gen_response.response {
-                                self.buffer.push_str(&response_text);
-
-                                // Check if this response contains a newline
-                                if response_text.contains('\n') || response_text.contains('\r') {
-                                    // We have a complete line, parse it
-                                    if let Some(newline_pos) = self.buffer.find('\n') {
-                                        let complete_line = self.buffer[..newline_pos]
-                                            .trim_end_matches('\r')
-                                            .to_string();
-                                        self.buffer.drain(..=newline_pos);
-
-                                        // Parse as JSON array ["blurb", "content"]
-                                        match serde_json::from_str::<Vec<String>>(&complete_line) {
-                                            Ok(parts) if parts.len() == 2 => {
-                                                return Some(Ok((
-                                                    parts[0].clone(),
-                                                    parts[1].clone(),
-                                                )));
-                                            }
-                                            Ok(_) => {
-                                                return Some(Err(anyhow!(
-                                                    "Expected JSON array with 2 elements"
-                                                )));
-                                            }
-                                            Err(e) => {
-                                                return Some(Err(anyhow!(
-                                                    "Failed to parse JSON array: {}",
-                                                    e
-                                                )));
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            // Continue reading more responses
-                        }
-                        Err(e) => {
-                            return Some(Err(anyhow!("Failed to parse GenerateResponse: {}", e)));
+                Ok(0) => return self.lines.finish(),
+                Ok(_) => match serde_json::from_str::<GenerateResponse>(&line) {
+                    Ok(gen_response) => {
+                        if let Some(response_text) = gen_response.response {
+                            self.lines.push(&response_text);
                         }
                     }
-                }
+                    Err(e) => {
+                        return Some(Err(anyhow!("Failed to parse GenerateResponse: {}", e)));
+                    }
+                },
                 Err(e) => {
                     return Some(Err(anyhow!("IO error reading stream: {}", e)));
                 }
@@ -119,54 +140,93 @@ where
 
 #[async_trait]
 impl LlmProvider for OllamaProvider {
-    type Iterator<'a> = OllamaStream<Pin<Box<dyn Stream<Item = Result<Bytes, io::Error>> + Send>>>;
+    type Iterator<'a> = OllamaStream<ByteStream>;
 
     async fn dissect_markdown<'a>(
         &'a self,
         markdown_content: &'a str,
     ) -> Result<Self::Iterator<'a>> {
-        let ollama_url = self
-            .settings
-            .location
-            .as_deref()
-            .unwrap_or("http://localhost")
-            .to_string();
-        let ollama_port = self.settings.port.unwrap_or(11434);
-        let full_url = format!("{}:{}/api/generate", ollama_url, ollama_port);
-
-        let system_prompt = r#"You are a helpful assistant that dissects markdown content into its smallest atomic semantic units. For each unit, provide a short blurb (up to 50 characters) in form of "subject verb characteristic" and the full content of the unit. For example for text 'Here's the idea. I'm not thinking in a linear manner and (hypothesis) I think this hinders my attempts to write my book. The idea is to create a recursive mind-map-like graph system, where each node  represents and idea or a concept and can have its own sub-graph of concepts that will elaborate the parent concept further.' the blurb could be 'non-linear thinking requires specialized tooling'. For each unit respond with a one-line JSON array that contains two strings: ["blurb", "dissected_unit_content"] followed by a new line. Do not include any other text in your response, just one two-item array per line."#;
-        let user_prompt = format!("Dissect the following markdown:\n\n{}", markdown_content);
-
-        let client = Client::new();
-        let model_name: &str = self.settings.model.as_str();
-        let prompt = format!("{} {}", system_prompt, user_prompt);
-        eprintln!("Using {}", model_name);
+        let prompt = prompts::dissect(markdown_content).combined();
+        eprintln!("Using {}", self.settings.model);
         let request_body = serde_json::to_string(&ChatRequest {
-            model: model_name,
+            model: &self.settings.model,
             stream: true,
             think: false,
             prompt: &prompt,
+            format: None,
         })?;
 
-        let res = client
-            .post(&full_url)
+        let res = Client::new()
+            .post(self.generate_url())
             .header("Content-Type", "application/json")
             .body(request_body)
             .send()
             .await?
             .error_for_status()?;
 
-        let byte_stream = res
-            .bytes_stream()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e));
-        let stream_reader =
-            StreamReader::new(Box::pin(byte_stream)
-                as Pin<Box<dyn Stream<Item = Result<Bytes, io::Error>> + Send>>);
+        let byte_stream = res.bytes_stream().map_err(io::Error::other);
+        let stream_reader = StreamReader::new(Box::pin(byte_stream) as ByteStream);
         let reader = BufReader::new(stream_reader);
 
         Ok(OllamaStream {
             reader,
-            buffer: String::new(),
+            lines: UnitLines::default(),
         })
     }
+
+    async fn line_edit(&self, text: &str) -> Result<String> {
+        self.generate(&prompts::line_edit(text), None).await
+    }
+
+    async fn copy_edit(&self, text: &str, style: &StyleSheet) -> Result<Vec<CopyeditSuggestion>> {
+        let prompt = prompts::copy_edit(text, style);
+        prompts::parse_copy_edit(&self.generate(&prompt, Some("json")).await?)
+    }
+
+    async fn assess_blurb(&self, blurb: &str, text: &str) -> Result<Option<String>> {
+        let prompt = prompts::assess_blurb(blurb, text);
+        prompts::parse_assess_blurb(&self.generate(&prompt, Some("json")).await?)
+    }
+
+    async fn assess_completeness(&self, outline: &str) -> Result<CompletenessReport> {
+        let prompt = prompts::assess_completeness(outline);
+        prompts::parse_assess_completeness(&self.generate(&prompt, Some("json")).await?)
+    }
+
+    async fn assess_duplicate(&self, first: &str, second: &str) -> Result<Option<String>> {
+        let prompt = prompts::assess_duplicate(first, second);
+        prompts::parse_assess_duplicate(&self.generate(&prompt, Some("json")).await?)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let response: EmbedResponse = Client::new()
+            .post(self.api_url("embed"))
+            .json(&EmbedRequest {
+                model: self.settings.embedding_model(),
+                input: texts,
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if response.embeddings.len() != texts.len() {
+            return Err(anyhow!(
+                "Ollama returned {} embeddings for {} texts",
+                response.embeddings.len(),
+                texts.len()
+            ));
+        }
+        Ok(response.embeddings)
+    }
+
+    async fn suggest_relation(
+        &self,
+        first: &str,
+        second: &str,
+    ) -> Result<Option<RelationSuggestion>> {
+        let prompt = prompts::suggest_relation(first, second);
+        prompts::parse_suggest_relation(&self.generate(&prompt, Some("json")).await?)
+    }
 }
@@ -0,0 +1,329 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use reqwest::{Client, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{LlmSettings, StyleSheet};
+use crate::llm::{
+    AsyncIterator, CompletenessReport, CopyeditSuggestion, LlmProvider, RelationSuggestion,
+};
+use crate::llm_providers::prompts::{self, Prompt};
+use crate::llm_providers::{ApiError, EventData, UnitLines, check_status, status_error, truncated};
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com";
+const API_KEY_VAR: &str = "OPENAI_API_KEY";
+
+/// Any server speaking the OpenAI chat completions API: OpenAI itself, llama.cpp server,
+/// vLLM, LM Studio and the like
+#[derive(Debug)]
+pub struct OpenAiProvider {
+    pub settings: LlmSettings,
+    /// Local servers usually need none
+    api_key: Option<String>,
+    /// Cleared once the server rejects `response_format`, JSON is then only asked for
+    /// in the prompt
+    json_mode: AtomicBool,
+}
+
+impl OpenAiProvider {
+    pub fn new(settings: LlmSettings) -> Self {
+        let api_key = settings.api_key(API_KEY_VAR);
+        OpenAiProvider {
+            settings,
+            api_key,
+            json_mode: AtomicBool::new(true),
+        }
+    }
+
+    fn url(&self, endpoint: &str) -> String {
+        format!(
+            "{}/v1/{}",
+            self.settings.base_url(DEFAULT_BASE_URL),
+            endpoint
+        )
+    }
+
+    fn post(&self, endpoint: &str) -> reqwest::RequestBuilder {
+        let request = Client::new().post(self.url(endpoint));
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+
+    async fn send(&self, prompt: &Prompt, stream: bool, json: bool) -> Result<Response> {
+        eprintln!("Using {}", self.settings.model);
+        let json = json && self.json_mode.load(Ordering::Relaxed);
+        let response = self
+            .post("chat/completions")
+            .json(&ChatRequest {
+                model: &self.settings.model,
+                messages: [
+                    ChatMessage {
+                        role: "system",
+                        content: prompt.system,
+                    },
+                    ChatMessage {
+                        role: "user",
+                        content: &prompt.user,
+                    },
+                ],
+                stream,
+                max_tokens: self.settings.max_tokens,
+                response_format: json.then_some(ResponseFormat {
+                    kind: "json_object",
+                }),
+            })
+            .send()
+            .await?;
+
+        // Servers without a JSON mode reject the request instead of ignoring the field
+        let status = response.status();
+        if json
+            && matches!(
+                status,
+                StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY
+            )
+        {
+            let body = response.text().await.unwrap_or_default();
+            if !body.contains("response_format") {
+                return Err(status_error("OpenAI", status, body));
+            }
+            eprintln!("The server does not support JSON mode, asking for JSON in the prompt only.");
+            self.json_mode.store(false, Ordering::Relaxed);
+            return Box::pin(self.send(prompt, stream, false)).await;
+        }
+        check_status(response, "OpenAI").await
+    }
+
+    /// Send a prompt and wait for the whole, non-streamed response
+    async fn complete(&self, prompt: &Prompt, json: bool) -> Result<String> {
+        let response: ChatResponse = self.send(prompt, false, json).await?.json().await?;
+        let choice = response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("OpenAI API returned no choices"))?;
+        if choice.finish_reason.as_deref() == Some("length") {
+            return Err(truncated());
+        }
+        Ok(choice.message.content.unwrap_or_default())
+    }
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: [ChatMessage<'a>; 2],
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<ResponseFormat>,
+}
+
+#[derive(Serialize)]
+struct ChatMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Serialize)]
+struct ResponseFormat {
+    #[serde(rename = "type")]
+    kind: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatResponse {
+    choices: Vec<Choice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Choice {
+    message: ChoiceMessage,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ChoiceMessage {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// The data of a server-sent event of a streamed response
+#[derive(Debug, Deserialize)]
+struct StreamChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    #[serde(default)]
+    error: Option<ApiError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkChoice {
+    #[serde(default)]
+    delta: ChoiceMessage,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<Embedding>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Embedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+pub struct OpenAiStream {
+    events: EventData,
+    lines: UnitLines,
+    done: bool,
+    truncated: bool,
+}
+
+#[async_trait]
+impl AsyncIterator for OpenAiStream {
+    type Item = Result<(String, String)>;
+
+    async fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(unit) = self.lines.next_unit() {
+                return Some(unit);
+            }
+            if self.done {
+                if std::mem::take(&mut self.truncated) {
+                    return Some(Err(truncated()));
+                }
+                return self.lines.finish();
+            }
+
+            let data = match self.events.next().await {
+                Some(Ok(data)) => data,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.done = true;
+                    continue;
+                }
+            };
+            if data == "[DONE]" {
+                self.done = true;
+                continue;
+            }
+            match serde_json::from_str::<StreamChunk>(&data) {
+                Ok(StreamChunk {
+                    error: Some(error), ..
+                }) => {
+                    self.done = true;
+                    return Some(Err(anyhow!("OpenAI API error: {}", error.message)));
+                }
+                Ok(chunk) => {
+                    for choice in chunk.choices {
+                        if let Some(content) = choice.delta.content {
+                            self.lines.push(&content);
+                        }
+                        if choice.finish_reason.as_deref() == Some("length") {
+                            self.truncated = true;
+                        }
+                    }
+                }
+                Err(e) => {
+                    return Some(Err(anyhow!("Failed to parse stream chunk: {}", e)));
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiProvider {
+    type Iterator<'a> = OpenAiStream;
+
+    async fn dissect_markdown<'a>(
+        &'a self,
+        markdown_content: &'a str,
+    ) -> Result<Self::Iterator<'a>> {
+        let response = self
+            .send(&prompts::dissect(markdown_content), true, false)
+            .await?;
+        Ok(OpenAiStream {
+            events: EventData::new(response),
+            lines: UnitLines::default(),
+            done: false,
+            truncated: false,
+        })
+    }
+
+    async fn line_edit(&self, text: &str) -> Result<String> {
+        self.complete(&prompts::line_edit(text), false).await
+    }
+
+    async fn copy_edit(&self, text: &str, style: &StyleSheet) -> Result<Vec<CopyeditSuggestion>> {
+        let prompt = prompts::copy_edit(text, style);
+        prompts::parse_copy_edit(&self.complete(&prompt, true).await?)
+    }
+
+    async fn assess_blurb(&self, blurb: &str, text: &str) -> Result<Option<String>> {
+        let prompt = prompts::assess_blurb(blurb, text);
+        prompts::parse_assess_blurb(&self.complete(&prompt, true).await?)
+    }
+
+    async fn assess_completeness(&self, outline: &str) -> Result<CompletenessReport> {
+        let prompt = prompts::assess_completeness(outline);
+        prompts::parse_assess_completeness(&self.complete(&prompt, true).await?)
+    }
+
+    async fn assess_duplicate(&self, first: &str, second: &str) -> Result<Option<String>> {
+        let prompt = prompts::assess_duplicate(first, second);
+        prompts::parse_assess_duplicate(&self.complete(&prompt, true).await?)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let response = self
+            .post("embeddings")
+            .json(&EmbeddingRequest {
+                model: self.settings.embedding_model(),
+                input: texts,
+            })
+            .send()
+            .await?;
+        let mut response: EmbeddingResponse =
+            check_status(response, "OpenAI").await?.json().await?;
+
+        if response.data.len() != texts.len() {
+            return Err(anyhow!(
+                "OpenAI API returned {} embeddings for {} texts",
+                response.data.len(),
+                texts.len()
+            ));
+        }
+        response.data.sort_by_key(|embedding| embedding.index);
+        Ok(response
+            .data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect())
+    }
+
+    async fn suggest_relation(
+        &self,
+        first: &str,
+        second: &str,
+    ) -> Result<Option<RelationSuggestion>> {
+        let prompt = prompts::suggest_relation(first, second);
+        prompts::parse_suggest_relation(&self.complete(&prompt, true).await?)
+    }
+}
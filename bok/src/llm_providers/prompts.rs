@@ -0,0 +1,158 @@
+//! Prompts shared by the HTTP providers and the parsing of their responses.
+//! Only the transport differs between providers.
+
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use crate::config::StyleSheet;
+use crate::llm::{CompletenessReport, CopyeditSuggestion, RelationSuggestion};
+use crate::node::RelationKind;
+
+/// Instructions and the input they apply to. Providers with a system role send them
+/// separately, the others as one prompt, see [`Prompt::combined`].
+pub struct Prompt {
+    pub system: &'static str,
+    pub user: String,
+}
+
+impl Prompt {
+    pub fn combined(&self) -> String {
+        format!("{}\n\n{}", self.system, self.user)
+    }
+}
+
+pub fn dissect(markdown: &str) -> Prompt {
+    Prompt {
+        system: r#"You are a helpful assistant that dissects markdown content into its smallest atomic semantic units. For each unit, provide a short blurb (up to 50 characters) in form of "subject verb characteristic" and the full content of the unit. For example for text 'Here's the idea. I'm not thinking in a linear manner and (hypothesis) I think this hinders my attempts to write my book. The idea is to create a recursive mind-map-like graph system, where each node  represents and idea or a concept and can have its own sub-graph of concepts that will elaborate the parent concept further.' the blurb could be 'non-linear thinking requires specialized tooling'. For each unit respond with a one-line JSON array that contains two strings: ["blurb", "dissected_unit_content"] followed by a new line. Do not include any other text in your response, just one two-item array per line."#,
+        user: format!("Dissect the following markdown:\n\n{}", markdown),
+    }
+}
+
+pub fn line_edit(text: &str) -> Prompt {
+    Prompt {
+        system: r#"You are a careful line editor. Edit the following markdown sentence by sentence for grammar, clarity, rhythm and word choice. Keep the author's meaning and voice, keep every markdown construct (headings, lists, links, code, Quarto attributes) and the line breaks as they are, and do not add or remove content. Respond with the full edited text only, without any commentary or code fences."#,
+        user: text.to_string(),
+    }
+}
+
+pub fn copy_edit(text: &str, style: &StyleSheet) -> Prompt {
+    let rules = style.rules();
+    let house_style = if rules.is_empty() {
+        "No house style, follow general conventions.".to_string()
+    } else {
+        rules.join("\n")
+    };
+    // Line numbers in the margin so that the model does not have to count
+    let numbered: Vec<String> = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| format!("{:>4}| {}", i + 1, line))
+        .collect();
+    Prompt {
+        system: r#"You are a meticulous copyeditor. Find spelling, grammar, punctuation, capitalization and number style issues in the following markdown and check it against the house style. Do not rewrite sentences for style and leave markdown syntax alone. Respond with a JSON object {"suggestions": [...]} where each suggestion is {"line": <1-based line number as given in the margin>, "column": <1-based character column where the issue starts>, "original": "<exact text to replace, never empty>", "replacement": "<corrected text>", "category": "spelling|grammar|punctuation|capitalization|numbers|terminology", "explanation": "<one short sentence>"}. Respond with {"suggestions": []} if there is nothing to fix."#,
+        user: format!(
+            "House style:\n{}\n\nText:\n{}",
+            house_style,
+            numbered.join("\n")
+        ),
+    }
+}
+
+pub fn parse_copy_edit(response: &str) -> Result<Vec<CopyeditSuggestion>> {
+    #[derive(Deserialize)]
+    struct Suggestions {
+        suggestions: Vec<CopyeditSuggestion>,
+    }
+
+    let parsed: Suggestions = parse_json(response, "copyedit suggestions")?;
+    Ok(parsed.suggestions)
+}
+
+pub fn assess_blurb(blurb: &str, text: &str) -> Prompt {
+    Prompt {
+        system: r#"You review the outline of a book. Each section has a short blurb that should summarize what its text is about. Decide whether the blurb below still describes the text. Respond with a JSON object {"fits": true|false, "reason": "<one short sentence, only when it does not fit>"}."#,
+        user: format!("Blurb: {}\n\nText:\n{}", blurb, text),
+    }
+}
+
+pub fn parse_assess_blurb(response: &str) -> Result<Option<String>> {
+    #[derive(Deserialize)]
+    struct Assessment {
+        fits: bool,
+        #[serde(default)]
+        reason: String,
+    }
+
+    let assessment: Assessment = parse_json(response, "blurb assessment")?;
+    Ok((!assessment.fits).then_some(assessment.reason))
+}
+
+pub fn assess_completeness(outline: &str) -> Prompt {
+    Prompt {
+        system: r#"You are a developmental editor. Below is the outline of one chapter of a book: every section is a heading followed by its text, nested sections have deeper headings. Judge whether the chapter is complete. Report gaps in its argument or coverage, topics the text promises or announces but never delivers, and new sections that would fill them. Respond with a JSON object {"gaps": ["<one sentence>", ...], "missing": ["<one sentence>", ...], "suggested_nodes": [{"blurb": "<short section title, up to 50 characters>", "reason": "<one sentence>"}, ...]}. Use empty lists when the chapter is complete."#,
+        user: format!("Chapter outline:\n\n{}", outline),
+    }
+}
+
+pub fn parse_assess_completeness(response: &str) -> Result<CompletenessReport> {
+    parse_json(response, "completeness report")
+}
+
+pub fn assess_duplicate(first: &str, second: &str) -> Prompt {
+    Prompt {
+        system: r#"You review the sections of a book for redundancy. Decide whether the two section texts below cover the same ground, so that one of them could be merged into the other. Texts that merely share a topic but say different things are not duplicates. Respond with a JSON object {"duplicate": true|false, "reason": "<one short sentence>"}."#,
+        user: format!("First text:\n{}\n\nSecond text:\n{}", first, second),
+    }
+}
+
+pub fn parse_assess_duplicate(response: &str) -> Result<Option<String>> {
+    #[derive(Deserialize)]
+    struct Assessment {
+        duplicate: bool,
+        #[serde(default)]
+        reason: String,
+    }
+
+    let assessment: Assessment = parse_json(response, "duplicate assessment")?;
+    Ok(assessment.duplicate.then_some(assessment.reason))
+}
+
+pub fn suggest_relation(first: &str, second: &str) -> Prompt {
+    Prompt {
+        system: r#"You help an author link ideas across the sections of a book. Below are two sections from different parts of it. Decide whether a reader of one would benefit from a cross-reference to the other. If so, pick the kind of relation: "see-also" (related topic), "contradicts" (they disagree), "elaborates" (one explains the other in more depth) or "example-of" (one is an example of the other), and which section the relation starts from: for "elaborates" and "example-of" it is the section that elaborates or is the example. Respond with a JSON object {"related": true|false, "kind": "<kind>", "from": "first"|"second", "reason": "<one short sentence>"}."#,
+        user: format!("First section:\n{}\n\nSecond section:\n{}", first, second),
+    }
+}
+
+pub fn parse_suggest_relation(response: &str) -> Result<Option<RelationSuggestion>> {
+    #[derive(Deserialize)]
+    struct Assessment {
+        related: bool,
+        kind: Option<RelationKind>,
+        #[serde(default)]
+        from: String,
+        #[serde(default)]
+        reason: String,
+    }
+
+    let assessment: Assessment = parse_json(response, "relation assessment")?;
+    if !assessment.related {
+        return Ok(None);
+    }
+    Ok(Some(RelationSuggestion {
+        kind: assessment.kind.unwrap_or(RelationKind::SeeAlso),
+        reversed: assessment.from == "second",
+        reason: assessment.reason,
+    }))
+}
+
+/// Parse the JSON object in a response. Models without a JSON mode tend to wrap it in
+/// code fences or a sentence, so everything outside the outermost braces is ignored.
+fn parse_json<T: DeserializeOwned>(response: &str, what: &str) -> Result<T> {
+    let json = match (response.find('{'), response.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &response[start..=end],
+        _ => response,
+    };
+    serde_json::from_str(json).map_err(|e| anyhow!("Failed to parse {}: {}", what, e))
+}
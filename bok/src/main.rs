@@ -1,29 +1,109 @@
-use clap::{CommandFactory, Parser, Subcommand, builder::PossibleValuesParser};
+use clap::{
+    Args, CommandFactory, Parser, Subcommand,
+    builder::{PossibleValue, PossibleValuesParser, RangedU64ValueParser, TypedValueParser},
+};
 use clap_complete::{generate, shells};
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::sync::OnceLock;
 
 mod commands;
 mod config;
+mod embeddings;
 mod llm;
 mod llm_providers;
+mod markdown;
 mod node;
 
+use commands::copyedit::CopyeditAction;
+use commands::import::{ImportOptions, MismatchAction};
+use commands::lineedit::LineeditAction;
+use commands::suggest::{SuggestAction, SuggestLinksOptions};
+use node::{Placement, RelationKind};
+
 // HACK: This is a workaround for clap's dynamic completions.
-// It leaks memory, but this function is only called when generating completions,
-// so it's a small, one-time leak.
+// Every node argument asks for the possible values, so the list is built once per process
+// and kept in a static to hand out `&'static str`s.
 fn get_node_hashes_for_clap() -> Vec<&'static str> {
-    let nodes = commands::node::get_all_nodes_flat();
-    let formatted_nodes: Vec<String> = nodes
-        .into_iter()
-        .map(|(id, blurb)| format!("{}-{}", id, blurb.replace(' ', "-")))
-        .collect();
-    let leaked: &'static Vec<String> = Box::leak(Box::new(formatted_nodes));
-    leaked.iter().map(|s| s.as_str()).collect()
+    static NODE_HASHES: OnceLock<Vec<String>> = OnceLock::new();
+    NODE_HASHES
+        .get_or_init(|| {
+            commands::node::get_all_nodes_flat()
+                .into_iter()
+                .map(|(id, blurb)| format!("{}-{}", id, blurb.replace(' ', "-")))
+                .collect()
+        })
+        .iter()
+        .map(|s| s.as_str())
+        .collect()
+}
+
+/// Node arguments take the `<id>-<blurb>` values of the book's nodes, which also end up in
+/// the shell completions. Until the book root is pinned any value is taken, so that the first
+/// pass over the arguments in [`pin_book_root`] does not read the nodes of the wrong book.
+#[derive(Clone)]
+struct NodeValueParser;
+
+impl TypedValueParser for NodeValueParser {
+    type Value = String;
+
+    fn parse_ref(
+        &self,
+        cmd: &clap::Command,
+        arg: Option<&clap::Arg>,
+        value: &OsStr,
+    ) -> Result<String, clap::Error> {
+        if !config::book_root_pinned() {
+            return Ok(value.to_string_lossy().into_owned());
+        }
+        PossibleValuesParser::new(get_node_hashes_for_clap()).parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        if !config::book_root_pinned() {
+            return None;
+        }
+        Some(Box::new(
+            get_node_hashes_for_clap()
+                .into_iter()
+                .map(PossibleValue::new),
+        ))
+    }
+}
+
+/// Pin the book root before clap checks the node values: a first pass over the arguments
+/// reads `--book` wherever it is given. `init` never looks for an enclosing book.
+fn pin_book_root() {
+    let matches = Cli::command().ignore_errors(true).try_get_matches().ok();
+    let book = matches
+        .as_ref()
+        .and_then(|matches| matches.get_one::<PathBuf>("book").cloned());
+    let subcommand = matches
+        .as_ref()
+        .and_then(|matches| matches.subcommand_name());
+
+    match config::explicit_book_root(book) {
+        Some(root) => config::set_book_root(root),
+        None if subcommand == Some("init") => config::set_book_root(PathBuf::from(".")),
+        None => {
+            config::book_root();
+        }
+    }
+}
+
+/// Extract the node id from a completion value formatted as `<id>-<blurb-with-hyphens>`
+fn parse_node_id(value: &str) -> String {
+    value.split('-').next().unwrap_or("").to_string()
 }
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
 struct Cli {
+    /// The book directory (defaults to $BOK_ROOT, then the closest directory upwards with a bok.yaml)
+    #[arg(long, global = true, value_name = "PATH")]
+    book: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -50,25 +130,119 @@ enum Commands {
         #[command(subcommand)]
         format: GenerateFormat,
     },
-    /// Line edits a node
+    /// Line edits a node with the LLM and reviews the proposed edits
     Lineedit {
         /// The node to line edit
+        #[arg(value_parser = NodeValueParser)]
         node: String,
+        /// Apply all proposed edits without asking
+        #[arg(long, conflicts_with = "propose")]
+        accept: bool,
+        /// Save the proposed edits as text.qmd.proposed instead of applying them
+        #[arg(long)]
+        propose: bool,
+        /// Review the edits saved by an earlier --propose instead of asking the LLM
+        #[arg(long, conflicts_with = "propose")]
+        review: bool,
     },
-    /// Copy edits a node
+    /// Copy edits a node against the style sheet in bok.yaml
     Copyedit {
         /// The node to copy edit
+        #[arg(value_parser = NodeValueParser)]
         node: String,
+        /// Apply all suggestions without asking
+        #[arg(long, conflicts_with_all = ["dry_run", "json"])]
+        accept: bool,
+        /// Only list the suggestions
+        #[arg(long, conflicts_with = "json")]
+        dry_run: bool,
+        /// Print the suggestions as JSON, without applying any
+        #[arg(long)]
+        json: bool,
     },
     /// Runs checks on the book
-    Check,
-    /// Imports a qmd file
+    #[command(args_conflicts_with_subcommands = true)]
+    Check {
+        /// Run only these checks (see --list)
+        #[arg(long, value_delimiter = ',')]
+        only: Vec<String>,
+        /// Also run the checks that need an LLM
+        #[arg(long)]
+        llm: bool,
+        /// Print the findings as JSON
+        #[arg(long)]
+        json: bool,
+        /// Fail on warnings too
+        #[arg(long)]
+        strict: bool,
+        /// List the available checks
+        #[arg(long)]
+        list: bool,
+        #[command(subcommand)]
+        command: Option<CheckCommand>,
+    },
+    /// Searches the titles and texts of the nodes
+    Search {
+        /// What to look for; all words must occur unless --semantic is given
+        #[arg(required = true)]
+        query: Vec<String>,
+        /// Rank the nodes by the similarity of their embeddings to the query's
+        #[arg(long)]
+        semantic: bool,
+        /// Show at most this many results
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        /// Print the results as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Computes the node embeddings that are missing or out of date
+    Embed,
+    /// Suggests improvements to the book's graph
+    Suggest {
+        #[command(subcommand)]
+        what: SuggestCommand,
+    },
+    /// Imports a qmd file, or a directory of them
     Import {
-        /// The qmd file to import
-        file: String,
+        /// The qmd file to import, or a directory with --recursive
+        #[arg(required_unless_present = "undo")]
+        file: Option<String>,
         /// The parent node hash under which to create the new nodes
-        #[arg(long, value_parser = PossibleValuesParser::new(get_node_hashes_for_clap()))]
+        #[arg(long, value_parser = NodeValueParser)]
         under: Option<String>,
+        /// What to do when the nodes do not add up to the file, e.g. because the LLM
+        /// paraphrased or dropped text
+        #[arg(long, value_enum, default_value = "ask")]
+        on_mismatch: MismatchAction,
+        /// Add the nodes created before a failure instead of nothing, skipping parts that
+        /// could not be read
+        #[arg(long)]
+        keep_partial: bool,
+        /// Create all nodes as siblings instead of nesting them by the file's headings
+        #[arg(long)]
+        flat: bool,
+        /// Split the file on its headings and paragraphs, without the LLM
+        #[arg(long)]
+        no_llm: bool,
+        /// Import every .md and .qmd file in the directory, with a node for every folder
+        /// and file
+        #[arg(long)]
+        recursive: bool,
+        /// How many files to import at the same time, and so LLM requests in flight
+        #[arg(long, default_value_t = 4, requires = "recursive")]
+        jobs: usize,
+        /// Read the files as an Obsidian vault: wiki-links between them become relations,
+        /// embedded files are copied into the nodes and tags go to the node metadata
+        #[arg(long)]
+        obsidian: bool,
+        /// Move the nodes added by an earlier import to the trash
+        #[arg(
+            long,
+            value_name = "IMPORT_ID",
+            conflicts_with_all = ["file", "under", "keep_partial", "flat", "no_llm", "recursive", "obsidian"]
+        )]
+        undo: Option<String>,
     },
     /// Generate shell completions
     Completion {
@@ -82,27 +256,183 @@ enum NodeAction {
     /// Adds a new node
     Add {
         /// The parent node hash under which to create the new node
-        #[arg(long, value_parser = PossibleValuesParser::new(get_node_hashes_for_clap()))]
+        #[arg(long, value_parser = NodeValueParser)]
         under: Option<String>,
         /// A short blurb for the new node
         blurb: Vec<String>,
     },
-    /// Removes a node
+    /// Moves a node and its subtree to the trash
     Rm {
         /// The node to remove
-        #[arg(value_parser = PossibleValuesParser::new(get_node_hashes_for_clap()))]
+        #[arg(value_parser = NodeValueParser)]
+        node: String,
+        /// Don't ask for confirmation
+        #[arg(long, short)]
+        yes: bool,
+        /// Only list what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Restores a removed node from the trash, or lists the trash without a node
+    Restore {
+        /// The id of the removed node
+        node: Option<String>,
+    },
+    /// Changes the blurb of a node, keeping its id
+    Rename {
+        /// The node to rename
+        #[arg(value_parser = NodeValueParser)]
         node: String,
+        /// The new blurb
+        #[arg(required = true)]
+        blurb: Vec<String>,
+    },
+    /// Moves a node under another parent and/or reorders it among its siblings
+    Mv {
+        /// The node to move
+        #[arg(value_parser = NodeValueParser)]
+        node: String,
+        /// The new parent node (defaults to the current parent)
+        #[arg(long, value_parser = NodeValueParser)]
+        under: Option<String>,
+        /// The sibling after which to place the node (defaults to the last position)
+        #[arg(long, value_parser = NodeValueParser, conflicts_with = "first")]
+        after: Option<String>,
+        /// Place the node before all its siblings
+        #[arg(long)]
+        first: bool,
+    },
+    /// Merges a node into another: appends its text, moves its children and relations over
+    /// and moves it to the trash
+    Merge {
+        /// The node to keep
+        #[arg(value_parser = NodeValueParser)]
+        into: String,
+        /// The node to merge into it
+        #[arg(value_parser = NodeValueParser)]
+        from: String,
     },
     /// Lists the node hierarchy
     Ls,
+    /// Adds a non-hierarchical relation from one node to another
+    Link {
+        /// The node the relation starts from
+        #[arg(value_parser = NodeValueParser)]
+        source: String,
+        /// The related node
+        #[arg(value_parser = NodeValueParser)]
+        target: String,
+        /// The kind of relation
+        #[arg(long, value_enum, default_value = "see-also")]
+        kind: RelationKind,
+    },
+    /// Removes non-hierarchical relations from one node to another
+    Unlink {
+        /// The node the relation starts from
+        #[arg(value_parser = NodeValueParser)]
+        source: String,
+        /// The related node
+        #[arg(value_parser = NodeValueParser)]
+        target: String,
+        /// Only remove relations of this kind (defaults to all kinds)
+        #[arg(long, value_enum)]
+        kind: Option<RelationKind>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SuggestCommand {
+    /// Proposes relations between similar nodes in different subtrees
+    Links {
+        /// Only suggest relations from or to this node
+        #[arg(long, value_parser = NodeValueParser)]
+        node: Option<String>,
+        /// Suggest at most this many relations
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        /// Minimum cosine similarity of the node embeddings, between 0 and 1
+        #[arg(long, default_value_t = commands::suggest::DEFAULT_THRESHOLD)]
+        threshold: f64,
+        /// Suggest see-also relations for similar embeddings without asking the LLM
+        #[arg(long)]
+        no_llm: bool,
+        /// Add all suggested relations without asking
+        #[arg(long, conflicts_with_all = ["dry_run", "json"])]
+        accept: bool,
+        /// Only list the suggestions
+        #[arg(long, conflicts_with = "json")]
+        dry_run: bool,
+        /// Print the suggestions as JSON, without adding any
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CheckCommand {
+    /// Finds clusters of nodes with near-duplicate texts
+    Duplicates {
+        /// Minimum similarity, between 0 and 1 (defaults to 0.5, or 0.9 with --semantic)
+        #[arg(long)]
+        threshold: Option<f64>,
+        /// Compare the node embeddings instead of the words of the texts
+        #[arg(long)]
+        semantic: bool,
+        /// Keep only the pairs the LLM confirms as duplicates
+        #[arg(long)]
+        llm: bool,
+        /// Print the clusters as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Asks the LLM for gaps, missing topics and missing child nodes in each chapter
+    Completeness {
+        /// Only assess this chapter
+        #[arg(long, value_parser = NodeValueParser)]
+        node: Option<String>,
+        /// Create the suggested child nodes as empty stubs
+        #[arg(long)]
+        create_stubs: bool,
+        /// Print the reports as JSON
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 #[derive(Subcommand)]
 enum VisFormat {
     /// Generates a d3 json file
-    D3,
+    D3 {
+        #[command(flatten)]
+        scope: VisScope,
+        /// Nested tree for d3.hierarchy or nodes and links for a force graph
+        #[arg(long, value_enum, default_value = "tree")]
+        shape: commands::vis::D3Shape,
+    },
     /// Generates a mermaid diagram
-    Mermaid,
+    Mermaid {
+        #[command(flatten)]
+        scope: VisScope,
+        /// Draw a mindmap instead of a top-down graph
+        #[arg(long)]
+        mindmap: bool,
+    },
+    /// Generates a graphviz dot digraph
+    Dot {
+        #[command(flatten)]
+        scope: VisScope,
+    },
+}
+
+/// Selection of the nodes to visualize
+#[derive(Args)]
+struct VisScope {
+    /// The node whose subtree to visualize (defaults to all root-level nodes)
+    #[arg(long, value_parser = NodeValueParser)]
+    root: Option<String>,
+    /// Number of node levels to include, starting at 1 for the root level
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    depth: Option<usize>,
 }
 
 #[derive(Subcommand)]
@@ -113,44 +443,262 @@ enum GenerateFormat {
 
 #[tokio::main]
 async fn main() {
+    pin_book_root();
     let cli = Cli::parse();
 
     match &cli.command {
         Commands::Init { blurb } => {
-            if blurb.is_empty() {
-                commands::init::run(None)
-            } else {
-                commands::init::run(Some(&blurb.join(" ")))
+            let blurb = (!blurb.is_empty()).then(|| blurb.join(" "));
+            if let Err(e) = commands::init::run(blurb.as_deref()) {
+                eprintln!("Error initializing book: {}", e);
+                std::process::exit(1);
             }
         }
         Commands::Node { action } => match action {
             NodeAction::Add { blurb, under } => {
-                let parsed_under = under
-                    .as_ref()
-                    .map(|s| s.splitn(2, '-').next().unwrap_or("").to_string());
+                let parsed_under = under.as_deref().map(parse_node_id);
                 commands::node::add(&blurb.join(" "), parsed_under.as_deref())
             }
-            NodeAction::Rm { node } => {
-                let parsed_node = node.splitn(2, '-').next().unwrap_or("").to_string();
-                commands::node::rm(&parsed_node)
+            NodeAction::Rm { node, yes, dry_run } => {
+                commands::node::rm(&parse_node_id(node), *yes, *dry_run)
+            }
+            NodeAction::Restore { node } => {
+                commands::node::restore(node.as_deref().map(parse_node_id).as_deref())
+            }
+            NodeAction::Rename { node, blurb } => {
+                commands::node::rename(&parse_node_id(node), &blurb.join(" "))
+            }
+            NodeAction::Mv {
+                node,
+                under,
+                after,
+                first,
+            } => {
+                let placement = match after {
+                    Some(after) => Placement::After(parse_node_id(after)),
+                    None if *first => Placement::First,
+                    None => Placement::Last,
+                };
+                commands::node::mv(
+                    &parse_node_id(node),
+                    under.as_deref().map(parse_node_id).as_deref(),
+                    placement,
+                )
+            }
+            NodeAction::Merge { into, from } => {
+                commands::node::merge(&parse_node_id(into), &parse_node_id(from))
             }
             NodeAction::Ls => commands::node::ls(),
+            NodeAction::Link {
+                source,
+                target,
+                kind,
+            } => commands::node::link(&parse_node_id(source), &parse_node_id(target), *kind),
+            NodeAction::Unlink {
+                source,
+                target,
+                kind,
+            } => commands::node::unlink(&parse_node_id(source), &parse_node_id(target), *kind),
         },
         Commands::Vis { format } => match format {
-            VisFormat::D3 => commands::vis::d3(),
-            VisFormat::Mermaid => commands::vis::mermaid(),
+            VisFormat::D3 { scope, shape } => commands::vis::d3(
+                scope.root.as_deref().map(parse_node_id).as_deref(),
+                scope.depth,
+                *shape,
+            ),
+            VisFormat::Mermaid { scope, mindmap } => commands::vis::mermaid(
+                scope.root.as_deref().map(parse_node_id).as_deref(),
+                scope.depth,
+                *mindmap,
+            ),
+            VisFormat::Dot { scope } => commands::vis::dot(
+                scope.root.as_deref().map(parse_node_id).as_deref(),
+                scope.depth,
+            ),
         },
         Commands::Generate { format } => match format {
-            GenerateFormat::Quarto => commands::generate::quarto(),
+            GenerateFormat::Quarto => {
+                if let Err(e) = commands::generate::quarto() {
+                    eprintln!("Error generating quarto book: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
+        Commands::Lineedit {
+            node,
+            accept,
+            propose,
+            review,
+        } => {
+            let action = if *accept {
+                LineeditAction::Accept
+            } else if *propose {
+                LineeditAction::Propose
+            } else {
+                LineeditAction::Interactive
+            };
+            if let Err(e) = commands::lineedit::run(&parse_node_id(node), action, *review).await {
+                eprintln!("Error line editing node: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Copyedit {
+            node,
+            accept,
+            dry_run,
+            json,
+        } => {
+            let action = if *accept {
+                CopyeditAction::Accept
+            } else if *dry_run {
+                CopyeditAction::DryRun
+            } else if *json {
+                CopyeditAction::Json
+            } else {
+                CopyeditAction::Interactive
+            };
+            if let Err(e) = commands::copyedit::run(&parse_node_id(node), action).await {
+                eprintln!("Error copy editing node: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Check {
+            only,
+            llm,
+            json,
+            strict,
+            list,
+            command,
+        } => {
+            if let Some(command) = command {
+                let result = match command {
+                    CheckCommand::Duplicates {
+                        threshold,
+                        semantic,
+                        llm,
+                        json,
+                    } => commands::check::duplicates::run(*threshold, *semantic, *llm, *json).await,
+                    CheckCommand::Completeness {
+                        node,
+                        create_stubs,
+                        json,
+                    } => {
+                        let node = node.as_deref().map(parse_node_id);
+                        commands::check::completeness::run(node.as_deref(), *create_stubs, *json)
+                            .await
+                    }
+                };
+                if let Err(e) = result {
+                    eprintln!("Error running checks: {}", e);
+                    std::process::exit(2);
+                }
+                return;
+            }
+            if *list {
+                commands::check::list();
+                return;
+            }
+            let options = commands::check::CheckOptions {
+                only: only.clone(),
+                llm: *llm,
+                json: *json,
+                strict: *strict,
+            };
+            match commands::check::run(&options).await {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("Error running checks: {}", e);
+                    std::process::exit(2);
+                }
+            }
+        }
+        Commands::Search {
+            query,
+            semantic,
+            limit,
+            json,
+        } => {
+            if let Err(e) = commands::search::run(&query.join(" "), *semantic, *limit, *json).await
+            {
+                eprintln!("Error searching: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Suggest { what } => match what {
+            SuggestCommand::Links {
+                node,
+                limit,
+                threshold,
+                no_llm,
+                accept,
+                dry_run,
+                json,
+            } => {
+                let action = if *accept {
+                    SuggestAction::Accept
+                } else if *dry_run {
+                    SuggestAction::DryRun
+                } else if *json {
+                    SuggestAction::Json
+                } else {
+                    SuggestAction::Interactive
+                };
+                let node = node.as_deref().map(parse_node_id);
+                let options = SuggestLinksOptions {
+                    node: node.as_deref(),
+                    limit: *limit,
+                    threshold: *threshold,
+                    no_llm: *no_llm,
+                    action,
+                };
+                if let Err(e) = commands::suggest::links(&options).await {
+                    eprintln!("Error suggesting links: {}", e);
+                    std::process::exit(1);
+                }
+            }
         },
-        Commands::Lineedit { node } => commands::lineedit::run(node),
-        Commands::Copyedit { node } => commands::copyedit::run(node),
-        Commands::Check => commands::check::run(),
-        Commands::Import { file, under } => {
-            let parsed_under = under
-                .as_ref()
-                .map(|s| s.splitn(2, '-').next().unwrap_or("").to_string());
-            if let Err(e) = commands::import::run(file, parsed_under.as_deref()).await {
+        Commands::Embed => {
+            if let Err(e) = commands::embed::run().await {
+                eprintln!("Error embedding nodes: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Import {
+            undo: Some(import_id),
+            ..
+        } => {
+            if let Err(e) = commands::import::undo(import_id) {
+                eprintln!("Error undoing import: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Import {
+            file,
+            under,
+            on_mismatch,
+            keep_partial,
+            flat,
+            no_llm,
+            recursive,
+            jobs,
+            obsidian,
+            undo: None,
+        } => {
+            let file = file
+                .as_deref()
+                .expect("clap requires a file without --undo");
+            let options = ImportOptions {
+                under: under.as_deref().map(parse_node_id),
+                on_mismatch: *on_mismatch,
+                keep_partial: *keep_partial,
+                flat: *flat,
+                no_llm: *no_llm,
+                recursive: *recursive,
+                jobs: *jobs,
+                obsidian: *obsidian,
+            };
+            if let Err(e) = commands::import::run(file, &options).await {
                 eprintln!("Error importing file: {}", e);
                 std::process::exit(1);
             }
@@ -0,0 +1,118 @@
+//! Markdown helpers shared by importing and generating books
+
+/// Longest blurb made from a heading or paragraph, in characters
+const BLURB_LEN: usize = 60;
+
+/// A blurb for a node from a heading title or the start of a paragraph: the first line
+/// without leading markdown markers, cut at a word to [`BLURB_LEN`] characters and with
+/// path separators replaced
+pub fn blurb(text: &str) -> String {
+    let line = text
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("");
+    let line = line.trim_start_matches(|c: char| !c.is_alphanumeric());
+    let mut blurb = String::new();
+    for word in line.split_whitespace() {
+        let length = blurb.chars().count() + word.chars().count() + 1;
+        if !blurb.is_empty() && length > BLURB_LEN {
+            break;
+        }
+        if !blurb.is_empty() {
+            blurb.push(' ');
+        }
+        blurb.push_str(word);
+    }
+    let blurb: String = blurb
+        .chars()
+        .take(BLURB_LEN)
+        .map(|c| if c == '/' || c == '\\' { '-' } else { c })
+        .collect();
+    if blurb.is_empty() {
+        "Untitled".to_string()
+    } else {
+        blurb
+    }
+}
+
+/// Whether lines are inside a fenced code block
+#[derive(Default)]
+pub struct Fence {
+    /// The opening fence while inside a block
+    open: Option<String>,
+}
+
+impl Fence {
+    /// Account for the line, returns whether it belongs to a code block (fences included)
+    pub fn track(&mut self, line: &str) -> bool {
+        let trimmed = line.trim_start_matches(' ');
+        let indent = line.len() - trimmed.len();
+        let marker: String = match trimmed.chars().next() {
+            Some(c @ ('`' | '~')) if indent <= 3 => {
+                trimmed.chars().take_while(|&next| next == c).collect()
+            }
+            _ => String::new(),
+        };
+        match &self.open {
+            Some(open) => {
+                let closes =
+                    marker.starts_with(open.as_str()) && trimmed[marker.len()..].trim().is_empty();
+                if closes {
+                    self.open = None;
+                }
+                true
+            }
+            None if marker.len() >= 3 => {
+                self.open = Some(marker);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Level and title of an ATX heading line
+pub fn atx_heading(line: &str) -> Option<(usize, String)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let level = trimmed.chars().take_while(|&c| c == '#').count();
+    let rest = &trimmed[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+    let title = rest.trim();
+    // A closing sequence of #s only counts after a space
+    let without_closing = title.trim_end_matches('#');
+    let title = if without_closing.is_empty() || without_closing.ends_with([' ', '\t']) {
+        without_closing.trim_end()
+    } else {
+        title
+    };
+    Some((level, title.to_string()))
+}
+
+/// A markdown link `[label](target)` at the start of the text, `!` of an image excluded:
+/// the label, the target without angle brackets or title, and the length of the link
+pub fn inline_link(text: &str) -> Option<(&str, &str, usize)> {
+    let rest = text.strip_prefix('[')?;
+    let close = rest.find(']')?;
+    let label = &rest[..close];
+    let destination = rest[close + 1..].strip_prefix('(')?;
+    let start = 1 + close + 2;
+    let (target, end) = match destination.strip_prefix('<') {
+        Some(bracketed) => {
+            let end = bracketed.find('>')?;
+            (&bracketed[..end], 1 + end + 1)
+        }
+        None => {
+            let end = destination
+                .find(|c: char| c == ')' || c.is_whitespace())
+                .unwrap_or(destination.len());
+            (&destination[..end], end)
+        }
+    };
+    let close = destination[end..].find(')')?;
+    Some((label, target, start + end + close + 1))
+}
@@ -0,0 +1,232 @@
+use crate::config::{book_root, config_path, data_dir};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directories modified this close (in ns) to the moment the index was written are rescanned
+/// anyway: with coarse filesystem timestamps a later change could keep the same mtime.
+const RACY_WINDOW_NS: u64 = 2_000_000_000;
+
+/// The index as loaded by this process, see [`NodeIndex::current`]
+static CURRENT: Mutex<Option<Arc<NodeIndex>>> = Mutex::new(None);
+
+/// Cached location of every node, stored in `.bok/index`.
+///
+/// Validated by directory mtimes: a directory's mtime changes whenever a node directory is
+/// created, removed or renamed in it, so only directories with a changed mtime are re-read.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NodeIndex {
+    /// When the index was written, in ns since the Unix epoch
+    written_at: u64,
+    /// mtime (ns since the Unix epoch) of the book root and of every node directory,
+    /// keyed by the path relative to the book root
+    dirs: HashMap<PathBuf, u64>,
+    nodes: BTreeMap<String, IndexEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Node directory relative to the book root, see [`IndexEntry::full_path`]
+    pub path: PathBuf,
+    pub title: String,
+    /// None for nodes at the top level of the book
+    pub parent: Option<String>,
+}
+
+impl IndexEntry {
+    /// The node directory as seen from the current directory
+    pub fn full_path(&self) -> PathBuf {
+        book_root().join(&self.path)
+    }
+}
+
+impl NodeIndex {
+    fn index_path() -> PathBuf {
+        data_dir().join("index")
+    }
+
+    /// The index, loaded and brought up to date once per process. Code that creates,
+    /// renames or removes node directories calls [`NodeIndex::invalidate`].
+    pub fn current() -> Arc<NodeIndex> {
+        CURRENT
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| Arc::new(Self::load()))
+            .clone()
+    }
+
+    /// Drop the loaded index after changing node directories, the next
+    /// [`NodeIndex::current`] refreshes it from the filesystem
+    pub fn invalidate() {
+        *CURRENT.lock().unwrap() = None;
+    }
+
+    /// Load the index and bring it up to date with the filesystem
+    fn load() -> NodeIndex {
+        let mut index: NodeIndex = fs::read_to_string(Self::index_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        if index.refresh() {
+            index.save();
+        }
+        index
+    }
+
+    pub fn get(&self, node_hash: &str) -> Option<&IndexEntry> {
+        self.nodes.get(node_hash)
+    }
+
+    /// All (id, entry) pairs, ordered by id
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &IndexEntry)> {
+        self.nodes.iter()
+    }
+
+    /// Titles from the top of the book down to the node, the node's own title last
+    pub fn tree_path(&self, node_hash: &str) -> Vec<String> {
+        let mut titles = Vec::new();
+        let mut current = self.nodes.get(node_hash);
+        while let Some(entry) = current {
+            titles.push(entry.title.clone());
+            current = entry
+                .parent
+                .as_ref()
+                .and_then(|parent| self.nodes.get(parent));
+        }
+        titles.reverse();
+        titles
+    }
+
+    /// Only books get an index on disk, so that running bok elsewhere leaves no traces
+    fn save(&mut self) {
+        if !config_path().exists() {
+            return;
+        }
+        self.written_at = now_ns();
+        if fs::create_dir_all(data_dir()).is_err() {
+            return;
+        }
+        // Written next to the index and renamed over it, so that a concurrent bok never
+        // reads half an index
+        let Ok(json) = serde_json::to_string(self) else {
+            return;
+        };
+        let temp_path = data_dir().join(format!("index.{}.tmp", std::process::id()));
+        if fs::write(&temp_path, json).is_err()
+            || fs::rename(&temp_path, Self::index_path()).is_err()
+        {
+            let _ = fs::remove_file(&temp_path);
+        }
+    }
+
+    /// Walk the node directories, re-reading only those whose mtime changed.
+    /// Returns whether anything changed.
+    fn refresh(&mut self) -> bool {
+        let mut children_of: HashMap<Option<String>, Vec<(String, PathBuf)>> = HashMap::new();
+        for (id, entry) in &self.nodes {
+            children_of
+                .entry(entry.parent.clone())
+                .or_default()
+                .push((id.clone(), entry.path.clone()));
+        }
+
+        let mut refreshed = Refreshed::default();
+        self.refresh_dir(Path::new(""), None, &children_of, &mut refreshed);
+
+        let changed = refreshed.changed
+            || refreshed.dirs.len() != self.dirs.len()
+            || refreshed.nodes.len() != self.nodes.len();
+        self.dirs = refreshed.dirs;
+        self.nodes = refreshed.nodes;
+        changed
+    }
+
+    fn refresh_dir(
+        &self,
+        dir: &Path,
+        parent: Option<&String>,
+        children_of: &HashMap<Option<String>, Vec<(String, PathBuf)>>,
+        refreshed: &mut Refreshed,
+    ) {
+        let Some(mtime) = mtime(&book_root().join(dir)) else {
+            refreshed.changed = true;
+            return;
+        };
+        let unchanged = self.dirs.get(dir) == Some(&mtime)
+            && self.written_at.saturating_sub(mtime) > RACY_WINDOW_NS;
+        refreshed.dirs.insert(dir.to_path_buf(), mtime);
+
+        let children = if unchanged {
+            children_of
+                .get(&parent.cloned())
+                .cloned()
+                .unwrap_or_default()
+        } else {
+            refreshed.changed = true;
+            Self::scan_children(dir)
+        };
+
+        for (id, path) in children {
+            let title = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.split_once(' '))
+                .map(|(_, title)| title.to_string())
+                .unwrap_or_default();
+            refreshed.nodes.insert(
+                id.clone(),
+                IndexEntry {
+                    path: path.clone(),
+                    title,
+                    parent: parent.cloned(),
+                },
+            );
+            self.refresh_dir(&path, Some(&id), children_of, refreshed);
+        }
+    }
+
+    /// Node directories (`<id> <blurb>` with a meta.yaml) directly inside `dir`,
+    /// both relative to the book root
+    fn scan_children(dir: &Path) -> Vec<(String, PathBuf)> {
+        let Ok(entries) = fs::read_dir(book_root().join(dir)) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().join("meta.yaml").exists())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_str()?.to_string();
+                let (id, _) = name.split_once(' ')?;
+                Some((id.to_string(), dir.join(&name)))
+            })
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct Refreshed {
+    changed: bool,
+    dirs: HashMap<PathBuf, u64>,
+    nodes: BTreeMap<String, IndexEntry>,
+}
+
+fn to_ns(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+fn now_ns() -> u64 {
+    to_ns(SystemTime::now())
+}
+
+fn mtime(path: &Path) -> Option<u64> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .map(to_ns)
+}
@@ -1,5 +1,9 @@
+pub mod index;
+#[allow(clippy::module_inception)]
 pub mod node;
 pub mod node_manager;
+pub mod trash;
 
-pub use node::{Meta, Node};
-pub use node_manager::NodeManager;
+pub use node::{Node, RelationKind};
+pub use node_manager::{NodeManager, Placement};
+pub use trash::Trash;
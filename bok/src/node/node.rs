@@ -1,6 +1,8 @@
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Metadata structure for a node, stored in meta.yaml
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,6 +12,50 @@ pub struct Meta {
     /// Optional ID of sibling node that should precede this one (for ordering)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub after: Option<String>,
+    /// Non-hierarchical relations to other nodes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relations: Vec<Relation>,
+    /// Free-form labels, e.g. the tags of an imported note
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Part of the parent's text rather than a section of its own, so that generated
+    /// books show the text without a heading, e.g. a paragraph split off by an import
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_heading: bool,
+}
+
+/// A non-hierarchical, directed relation from the owning node to `target`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Relation {
+    /// ID of the related node
+    pub target: String,
+    pub kind: RelationKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum RelationKind {
+    SeeAlso,
+    Contradicts,
+    Elaborates,
+    ExampleOf,
+}
+
+impl RelationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RelationKind::SeeAlso => "see-also",
+            RelationKind::Contradicts => "contradicts",
+            RelationKind::Elaborates => "elaborates",
+            RelationKind::ExampleOf => "example-of",
+        }
+    }
+}
+
+impl fmt::Display for RelationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 /// Wrapper structure for the meta.yaml file format
@@ -20,7 +66,13 @@ struct MetaFile {
 
 impl Meta {
     pub fn new(title: String, after: Option<String>) -> Self {
-        Meta { title, after }
+        Meta {
+            title,
+            after,
+            relations: Vec::new(),
+            tags: Vec::new(),
+            no_heading: false,
+        }
     }
 }
 
@@ -28,6 +80,8 @@ impl Meta {
 pub struct Node {
     pub id: String,
     pub meta: Meta,
+    /// Directory holding the node's text.qmd and meta.yaml
+    pub path: PathBuf,
     pub children: Vec<Node>,
 }
 
@@ -41,13 +95,30 @@ impl Node {
     pub fn after(&self) -> Option<&String> {
         self.meta.after.as_ref()
     }
+
+    /// Accessor for relations (delegates to meta.relations)
+    pub fn relations(&self) -> &[Relation] {
+        &self.meta.relations
+    }
+
+    /// Path of the node's meta.yaml
+    pub fn meta_path(&self) -> PathBuf {
+        self.path.join("meta.yaml")
+    }
+
+    /// Read the node's text.qmd
+    pub fn content(&self) -> Result<String, String> {
+        fs::read_to_string(self.path.join("text.qmd"))
+            .map_err(|e| format!("Error reading text.qmd for node '{}': {}", self.id, e))
+    }
 }
 
 impl Node {
-    pub fn new(id: String, blurb: String, after: Option<String>) -> Self {
+    pub fn new(id: String, blurb: String, after: Option<String>, path: PathBuf) -> Self {
         Node {
             id,
             meta: Meta::new(blurb, after),
+            path,
             children: Vec::new(),
         }
     }
@@ -63,6 +134,10 @@ impl Node {
         Ok(Node {
             id,
             meta: meta_file.meta,
+            path: meta_path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
             children: Vec::new(),
         })
     }
@@ -1,7 +1,10 @@
-use super::node::Node;
-use glob::glob;
+use super::index::NodeIndex;
+use super::node::{Node, Relation, RelationKind};
+use super::trash::{RemovedRelation, RetargetedRelation, Trash, TrashEntry};
+use crate::config::{book_root, config_path};
 use murmur3::murmur3_32;
 use serde::Deserialize;
+use std::collections::HashSet;
 use std::fs;
 use std::io::Cursor;
 use std::path::{Path, PathBuf};
@@ -13,30 +16,91 @@ struct BokConfig {
 
 pub struct NodeManager;
 
-impl NodeManager {
-    /// Find a node by its hash/id and return its path
-    pub fn find_node_path(node_hash: &str) -> Result<PathBuf, String> {
-        let pattern = format!("./**/{}*", node_hash);
-        for entry in glob(&pattern).map_err(|e| format!("Failed to read glob pattern: {}", e))? {
-            if let Ok(p) = entry {
-                if p.is_dir() {
-                    return Ok(p);
-                }
+/// A node about to be removed, see [`NodeManager::plan_removal`]
+pub struct Removal {
+    /// The node with its whole subtree
+    pub node: Node,
+    /// Relations from other nodes into the removed subtree
+    pub relations: Vec<RemovedRelation>,
+}
+
+/// Where a moved node goes among its new siblings
+pub enum Placement {
+    First,
+    Last,
+    After(String),
+}
+
+/// The metadata files an operation wrote, with what they held before, so that the
+/// operation can be rolled back when a later step fails
+#[derive(Default)]
+struct MetaJournal {
+    written: Vec<(PathBuf, Option<String>)>,
+}
+
+impl MetaJournal {
+    fn save(&mut self, node: &Node) -> Result<(), String> {
+        let meta_path = node.meta_path();
+        let before = fs::read_to_string(&meta_path).ok();
+        node.save_meta(&meta_path)?;
+        self.written.push((meta_path, before));
+        Ok(())
+    }
+
+    /// Persist the metadata of the nodes whose id is in `changed`
+    fn save_changed<'a>(
+        &mut self,
+        nodes: impl Iterator<Item = &'a Node>,
+        changed: &[String],
+    ) -> Result<(), String> {
+        for node in nodes {
+            if changed.contains(&node.id) {
+                self.save(node)?;
             }
         }
-        Err(format!("Node with hash '{}' not found.", node_hash))
+        Ok(())
     }
 
-    /// Find a node by its hash/id with a specific pattern
-    pub fn find_node_path_with_pattern(pattern: &str) -> Result<PathBuf, String> {
-        for entry in glob(pattern).map_err(|e| format!("Failed to read glob pattern: {}", e))? {
-            if let Ok(p) = entry {
-                if p.is_dir() {
-                    return Ok(p);
-                }
-            }
+    /// Put back what the written files held, newest first
+    fn roll_back(self) {
+        for (meta_path, before) in self.written.into_iter().rev() {
+            let _ = match before {
+                Some(before) => fs::write(&meta_path, before),
+                None => fs::remove_file(&meta_path),
+            };
+        }
+    }
+}
+
+/// Rename a node directory, describing the failure as `doing`
+fn rename_dir(from: &Path, to: &Path, doing: &str) -> Result<(), String> {
+    NodeIndex::invalidate();
+    fs::rename(from, to).map_err(|e| {
+        format!(
+            "Error {} '{}' to '{}': {}",
+            doing,
+            from.display(),
+            to.display(),
+            e
+        )
+    })
+}
+
+impl NodeManager {
+    /// Find a node by its exact hash/id and return its path
+    pub fn find_node_path(node_hash: &str) -> Result<PathBuf, String> {
+        NodeIndex::current()
+            .get(node_hash)
+            .map(|entry| entry.full_path())
+            .ok_or_else(|| format!("Node with hash '{}' not found.", node_hash))
+    }
+
+    /// Directory containing the node at `node_path`
+    pub fn parent_dir(node_path: &Path) -> PathBuf {
+        match node_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => book_root().to_path_buf(),
         }
-        Err("Node not found with pattern".to_string())
     }
 
     /// Get the content of a node's text.qmd file
@@ -47,6 +111,58 @@ impl NodeManager {
             .map_err(|e| format!("Error reading text.qmd for node '{}': {}", node_hash, e))
     }
 
+    /// Load a single node (without children) by its hash
+    pub fn load_node(node_hash: &str) -> Result<Node, String> {
+        let node_path = Self::find_node_path(node_hash)?;
+        Node::from_meta(&node_path.join("meta.yaml"), node_hash.to_string())
+    }
+
+    /// Add a relation from `source` to `target`, both of which must exist
+    pub fn add_relation(source: &str, target: &str, kind: RelationKind) -> Result<(), String> {
+        if source == target {
+            return Err("A node cannot be related to itself.".to_string());
+        }
+        let mut node = Self::load_node(source)?;
+        Self::find_node_path(target)?;
+
+        let relation = Relation {
+            target: target.to_string(),
+            kind,
+        };
+        if node.meta.relations.contains(&relation) {
+            return Err(format!(
+                "Node '{}' already has a {} relation to '{}'.",
+                source, kind, target
+            ));
+        }
+        node.meta.relations.push(relation);
+        node.save_meta(&node.meta_path())
+    }
+
+    /// Remove relations from `source` to `target`, optionally only those of one kind.
+    /// Returns the number of removed relations.
+    pub fn remove_relation(
+        source: &str,
+        target: &str,
+        kind: Option<RelationKind>,
+    ) -> Result<usize, String> {
+        let mut node = Self::load_node(source)?;
+        let before = node.meta.relations.len();
+        node.meta
+            .relations
+            .retain(|r| r.target != target || kind.is_some_and(|k| k != r.kind));
+
+        let removed = before - node.meta.relations.len();
+        if removed == 0 {
+            return Err(format!(
+                "Node '{}' has no such relation to '{}'.",
+                source, target
+            ));
+        }
+        node.save_meta(&node.meta_path())?;
+        Ok(removed)
+    }
+
     /// Create a new node with the given blurb, content, and optional parent
     pub fn create_node(
         blurb: &str,
@@ -54,41 +170,549 @@ impl NodeManager {
         under: Option<&str>,
         after: Option<&str>,
     ) -> Result<u32, String> {
-        let mut reader = Cursor::new(blurb.as_bytes());
-        let node_id = murmur3_32(&mut reader, 0).unwrap();
-        let node_dir_name = format!("{} {}", node_id, blurb);
+        let parent = match under {
+            Some(under_hash) => Self::find_node_path(under_hash)
+                .map_err(|_| format!("Parent node with hash {} not found.", under_hash))?,
+            None => book_root().to_path_buf(),
+        };
+        let node_id = Self::create_node_in(&parent, blurb, content, after, &HashSet::new())?;
+        NodeIndex::invalidate();
+        Ok(node_id)
+    }
 
-        let mut path = PathBuf::new();
-        if let Some(under_hash) = under {
-            let parent_pattern = format!("./{} *", under_hash);
-            let parent_path = Self::find_node_path_with_pattern(&parent_pattern)
-                .map_err(|_| format!("Parent node with hash {} not found.", under_hash))?;
-            path.push(parent_path);
-        }
-        path.push(node_dir_name);
+    /// Create a node directory in `dir`, which does not have to be part of the book yet,
+    /// e.g. a staging directory. The id avoids `reserved` on top of the ids in use.
+    pub fn create_node_in(
+        dir: &Path,
+        blurb: &str,
+        content: &str,
+        after: Option<&str>,
+        reserved: &HashSet<String>,
+    ) -> Result<u32, String> {
+        Self::validate_blurb(blurb)?;
+        let node_id = Self::generate_node_id(blurb, reserved);
+        let path = dir.join(format!("{} {}", node_id, blurb));
 
-        fs::create_dir_all(&path).unwrap();
+        fs::create_dir_all(&path)
+            .map_err(|e| format!("Failed to create '{}': {}", path.display(), e))?;
 
         let text_file_path = path.join("text.qmd");
-        fs::write(text_file_path, content).unwrap();
+        fs::write(text_file_path, content)
+            .map_err(|e| format!("Failed to write text.qmd: {}", e))?;
 
         let meta_file_path = path.join("meta.yaml");
         let node = Node::new(
             node_id.to_string(),
             blurb.to_string(),
             after.map(String::from),
+            path,
         );
         node.save_meta(&meta_file_path)?;
 
         Ok(node_id)
     }
 
-    /// Remove a node by its hash
-    pub fn remove_node(node_hash: &str) -> Result<PathBuf, String> {
+    /// Hash the blurb into a node id, re-seeding until the id is not used anywhere in the tree
+    fn generate_node_id(blurb: &str, reserved: &HashSet<String>) -> u32 {
+        // Ids in the trash are still taken so that the nodes can be restored
+        let existing_ids: HashSet<String> = Self::get_all_nodes_flat()
+            .into_iter()
+            .map(|(id, _)| id)
+            .chain(Trash::list().into_iter().map(|entry| entry.manifest.id))
+            .collect();
+
+        let mut seed = 0;
+        loop {
+            let mut reader = Cursor::new(blurb.as_bytes());
+            let node_id = murmur3_32(&mut reader, seed).unwrap();
+            let id = node_id.to_string();
+            if !existing_ids.contains(&id) && !reserved.contains(&id) {
+                return node_id;
+            }
+            seed += 1;
+        }
+    }
+
+    /// The blurb becomes part of the directory name, so it must be a valid single path component
+    pub fn validate_blurb(blurb: &str) -> Result<(), String> {
+        if blurb.trim().is_empty() {
+            return Err("Blurb cannot be empty.".to_string());
+        }
+        if blurb.contains('/') || blurb.contains('\\') {
+            return Err(format!("Blurb '{}' cannot contain path separators.", blurb));
+        }
+        Ok(())
+    }
+
+    /// Change a node's blurb: renames its directory and updates meta.title, keeping the id
+    pub fn rename_node(node_hash: &str, blurb: &str) -> Result<PathBuf, String> {
+        Self::validate_blurb(blurb)?;
+        let node_path = Self::find_node_path(node_hash)?;
+        let mut node = Node::from_meta(&node_path.join("meta.yaml"), node_hash.to_string())?;
+
+        let target_path = node_path.with_file_name(format!("{} {}", node_hash, blurb));
+        let renamed = target_path != node_path;
+        if renamed {
+            if target_path.exists() {
+                return Err(format!("'{}' already exists.", target_path.display()));
+            }
+            rename_dir(&node_path, &target_path, "renaming")?;
+        }
+
+        node.meta.title = blurb.to_string();
+        node.path = target_path;
+        if let Err(e) = node.save_meta(&node.meta_path()) {
+            if renamed {
+                let _ = fs::rename(&node.path, &node_path);
+            }
+            return Err(e);
+        }
+        Ok(node.path)
+    }
+
+    /// Everything that removing a node affects: the node with its subtree
+    /// and the relations of other nodes pointing into that subtree
+    pub fn plan_removal(node_hash: &str) -> Result<Removal, String> {
+        let node_path = Self::find_node_path(node_hash)?;
+        let mut node = Node::from_meta(&node_path.join("meta.yaml"), node_hash.to_string())?;
+        node.children = Self::get_nodes_recursive(&node_path);
+
+        let mut removed_ids = HashSet::new();
+        Self::collect_ids(std::slice::from_ref(&node), &mut removed_ids);
+
+        let mut relations = Vec::new();
+        Self::collect_relations_into(
+            &Self::get_nodes_recursive(book_root()),
+            &removed_ids,
+            &mut relations,
+        );
+
+        Ok(Removal { node, relations })
+    }
+
+    fn collect_ids(nodes: &[Node], ids: &mut HashSet<String>) {
+        for node in nodes {
+            ids.insert(node.id.clone());
+            Self::collect_ids(&node.children, ids);
+        }
+    }
+
+    /// Relations from nodes outside of `targets` to nodes inside it
+    fn collect_relations_into(
+        nodes: &[Node],
+        targets: &HashSet<String>,
+        relations: &mut Vec<RemovedRelation>,
+    ) {
+        for node in nodes {
+            if targets.contains(&node.id) {
+                continue;
+            }
+            for relation in node.relations() {
+                if targets.contains(&relation.target) {
+                    relations.push(RemovedRelation {
+                        source: node.id.clone(),
+                        relation: relation.clone(),
+                    });
+                }
+            }
+            Self::collect_relations_into(&node.children, targets, relations);
+        }
+    }
+
+    /// Move a node and its subtree to the trash, re-linking the sibling that came after it
+    /// and dropping relations that pointed into the removed subtree
+    pub fn remove_node(node_hash: &str) -> Result<TrashEntry, String> {
+        let removal = Self::plan_removal(node_hash)?;
+        let node = removal.node;
+        let parent = Self::parent_dir(&node.path);
+
+        let mut siblings: Vec<Node> = Self::get_nodes_recursive(&parent)
+            .into_iter()
+            .filter(|n| n.id != node.id)
+            .collect();
+        let changed = Self::detach_from_chain(&mut siblings, &node);
+
+        // One pending copy per node, a follower may also have relations into the subtree
+        let mut updates: Vec<Node> = siblings
+            .into_iter()
+            .filter(|sibling| changed.contains(&sibling.id))
+            .collect();
+        for removed in &removal.relations {
+            if updates.iter().all(|update| update.id != removed.source) {
+                updates.push(Self::load_node(&removed.source)?);
+            }
+        }
+        let mut removed_ids = HashSet::new();
+        Self::collect_ids(std::slice::from_ref(&node), &mut removed_ids);
+        for update in &mut updates {
+            update
+                .meta
+                .relations
+                .retain(|r| !removed_ids.contains(&r.target));
+        }
+
+        // The node leaves first, the other nodes are only changed once it is in the trash
+        let entry = Trash::put(&node, &parent, removal.relations, Vec::new())?;
+        let mut journal = MetaJournal::default();
+        let saved = updates.iter().try_for_each(|update| journal.save(update));
+        if let Err(e) = saved {
+            journal.roll_back();
+            if fs::rename(entry.node_dir(), &node.path).is_ok() {
+                let _ = Trash::discard(&entry);
+            }
+            return Err(e);
+        }
+        Ok(entry)
+    }
+
+    /// Put the most recently removed node with the given id back where it was
+    pub fn restore_node(node_hash: &str) -> Result<PathBuf, String> {
+        if Self::find_node_path(node_hash).is_ok() {
+            return Err(format!("Node with hash '{}' already exists.", node_hash));
+        }
+        let entry = Trash::find(node_hash)?;
+
+        let mut parent = book_root().join(&entry.manifest.parent);
+        if parent != book_root() && !parent.join("meta.yaml").exists() {
+            eprintln!(
+                "Warning: original parent '{}' no longer exists, restoring at the top level.",
+                parent.display()
+            );
+            parent = book_root().to_path_buf();
+        }
+
+        let node_dir = entry.node_dir();
+        let target_path = parent.join(node_dir.file_name().unwrap());
+        if target_path.exists() {
+            return Err(format!("'{}' already exists.", target_path.display()));
+        }
+
+        let mut node = Node::from_meta(&node_dir.join("meta.yaml"), node_hash.to_string())?;
+        let mut siblings = Self::get_nodes_recursive(&parent);
+        let placement = match node.after() {
+            Some(after) if siblings.iter().any(|n| n.id == *after) => {
+                Placement::After(after.clone())
+            }
+            Some(_) => Placement::Last,
+            None => Placement::First,
+        };
+        let changed = Self::attach_to_chain(&mut siblings, &mut node, placement)?;
+
+        rename_dir(&node_dir, &target_path, "restoring")?;
+        node.path = target_path;
+        let mut journal = MetaJournal::default();
+        let saved = journal
+            .save(&node)
+            .and_then(|_| journal.save_changed(siblings.iter(), &changed));
+        if let Err(e) = saved {
+            journal.roll_back();
+            let _ = fs::rename(&node.path, &node_dir);
+            return Err(e);
+        }
+
+        for removed in &entry.manifest.relations {
+            if let Ok(mut source) = Self::load_node(&removed.source)
+                && !source.meta.relations.contains(&removed.relation)
+            {
+                source.meta.relations.push(removed.relation.clone());
+                source.save_meta(&source.meta_path())?;
+            }
+        }
+        // Undo what merging the node into another did to the relations pointing at it
+        for retargeted in &entry.manifest.retargeted {
+            if let Ok(mut source) = Self::load_node(&retargeted.source) {
+                if let Some(into) = &retargeted.into {
+                    source
+                        .meta
+                        .relations
+                        .retain(|r| r.target != *into || r.kind != retargeted.relation.kind);
+                }
+                if !source.meta.relations.contains(&retargeted.relation) {
+                    source.meta.relations.push(retargeted.relation.clone());
+                }
+                source.save_meta(&source.meta_path())?;
+            }
+        }
+
+        Trash::discard(&entry)?;
+        Ok(node.path)
+    }
+
+    /// Move a node under a new parent (or keep its parent when `under` is None) and place it
+    /// among the new siblings, repairing the `after` chain at the old and the new location
+    pub fn move_node(
+        node_hash: &str,
+        under: Option<&str>,
+        placement: Placement,
+    ) -> Result<PathBuf, String> {
         let node_path = Self::find_node_path(node_hash)?;
-        fs::remove_dir_all(&node_path)
-            .map_err(|e| format!("Error removing node '{}': {}", node_path.display(), e))?;
-        Ok(node_path)
+        let mut node = Node::from_meta(&node_path.join("meta.yaml"), node_hash.to_string())?;
+        let old_parent = Self::parent_dir(&node_path);
+        let new_parent = match under {
+            Some(parent_hash) => Self::find_node_path(parent_hash)?,
+            None => old_parent.clone(),
+        };
+
+        if new_parent.starts_with(&node_path) {
+            return Err(format!(
+                "Cannot move node '{}' into its own subtree.",
+                node_hash
+            ));
+        }
+
+        let same_parent = new_parent == old_parent;
+        let mut old_siblings: Vec<Node> = Self::get_nodes_recursive(&old_parent)
+            .into_iter()
+            .filter(|n| n.id != node.id)
+            .collect();
+        let mut changed = Self::detach_from_chain(&mut old_siblings, &node);
+
+        let mut new_siblings = if same_parent {
+            std::mem::take(&mut old_siblings)
+        } else {
+            Self::get_nodes_recursive(&new_parent)
+        };
+
+        changed.extend(Self::attach_to_chain(
+            &mut new_siblings,
+            &mut node,
+            placement,
+        )?);
+
+        let target_path = new_parent.join(node_path.file_name().unwrap());
+        if !same_parent && target_path.exists() {
+            return Err(format!("'{}' already exists.", target_path.display()));
+        }
+
+        // The directory moves first, the metadata is only written once it is in place
+        if !same_parent {
+            rename_dir(&node_path, &target_path, "moving")?;
+            node.path = target_path;
+        }
+        let mut journal = MetaJournal::default();
+        let saved = journal
+            .save_changed(old_siblings.iter().chain(new_siblings.iter()), &changed)
+            .and_then(|_| journal.save(&node));
+        if let Err(e) = saved {
+            journal.roll_back();
+            if !same_parent {
+                let _ = fs::rename(&node.path, &node_path);
+            }
+            return Err(e);
+        }
+
+        Ok(node.path)
+    }
+
+    /// Merge node `from` into node `into`: append its text, move its children to the end of
+    /// `into`'s children, take over its relations and point relations to it at `into`.
+    /// `from` is taken out of its siblings' `after` chain and moved to the trash.
+    pub fn merge_nodes(into_hash: &str, from_hash: &str) -> Result<TrashEntry, String> {
+        if into_hash == from_hash {
+            return Err("Cannot merge a node into itself.".to_string());
+        }
+        let into_path = Self::find_node_path(into_hash)?;
+        let from_path = Self::find_node_path(from_hash)?;
+        if into_path.starts_with(&from_path) || from_path.starts_with(&into_path) {
+            return Err(format!(
+                "Cannot merge '{}' and '{}', one contains the other.",
+                into_hash, from_hash
+            ));
+        }
+        let from = Node::from_meta(&from_path.join("meta.yaml"), from_hash.to_string())?;
+        let into = Node::from_meta(&into_path.join("meta.yaml"), into_hash.to_string())?;
+        let from_parent = Self::parent_dir(&from_path);
+        let from_text = from.content()?;
+        let into_text = fs::read_to_string(into_path.join("text.qmd")).ok();
+
+        // Everything is worked out before the first file changes
+        let children = Self::get_nodes_recursive(&from_path);
+        for child in &children {
+            let target_path = into_path.join(child.path.file_name().unwrap());
+            if target_path.exists() {
+                return Err(format!("'{}' already exists.", target_path.display()));
+            }
+        }
+
+        // Close the gap `from` leaves among its siblings, `into` may be one of them
+        let mut siblings: Vec<Node> = Self::get_nodes_recursive(&from_parent)
+            .into_iter()
+            .filter(|n| n.id != from.id)
+            .collect();
+        let changed = Self::detach_from_chain(&mut siblings, &from);
+        let mut updates: Vec<Node> = siblings
+            .into_iter()
+            .filter(|n| changed.contains(&n.id))
+            .collect();
+
+        let mut previous = Self::get_nodes_recursive(&into_path)
+            .last()
+            .map(|last| last.id.clone());
+        for child in &children {
+            Self::pending(&mut updates, child).meta.after = previous.replace(child.id.clone());
+        }
+
+        let pending_into = Self::pending(&mut updates, &into);
+        for relation in from.relations() {
+            if relation.target != into.id
+                && relation.target != from.id
+                && !pending_into.meta.relations.contains(relation)
+            {
+                pending_into.meta.relations.push(relation.clone());
+            }
+        }
+
+        let mut retargeted = Vec::new();
+        Self::retarget_relations(
+            &Self::get_nodes_recursive(book_root()),
+            &from.id,
+            &into.id,
+            &mut updates,
+            &mut retargeted,
+        );
+
+        // The children's subtrees move along with them
+        for node in &mut updates {
+            if let Ok(rest) = node.path.strip_prefix(&from_path)
+                && !rest.as_os_str().is_empty()
+            {
+                node.path = into_path.join(rest);
+            }
+        }
+
+        let merged_text = match (
+            into_text.as_deref().unwrap_or_default().trim_end(),
+            from_text.trim(),
+        ) {
+            (into_text, "") => format!("{}\n", into_text),
+            ("", from_text) => format!("{}\n", from_text),
+            (into_text, from_text) => format!("{}\n\n{}\n", into_text, from_text),
+        };
+
+        let mut moved: Vec<(&Path, PathBuf)> = Vec::new();
+        let mut journal = MetaJournal::default();
+        let merge = || {
+            for child in &children {
+                let target_path = into_path.join(child.path.file_name().unwrap());
+                rename_dir(&child.path, &target_path, "moving")?;
+                moved.push((&child.path, target_path));
+            }
+            fs::write(into_path.join("text.qmd"), &merged_text)
+                .map_err(|e| format!("Failed to write text.qmd: {}", e))?;
+            updates.iter().try_for_each(|node| journal.save(node))?;
+            Trash::put(&from, &from_parent, Vec::new(), retargeted)
+        };
+        let result = merge();
+        if result.is_err() {
+            journal.roll_back();
+            let _ = match &into_text {
+                Some(into_text) => fs::write(into_path.join("text.qmd"), into_text),
+                None => fs::remove_file(into_path.join("text.qmd")),
+            };
+            for (child_path, moved_to) in moved.iter().rev() {
+                let _ = fs::rename(moved_to, child_path);
+            }
+        }
+        result
+    }
+
+    /// The copy of `node` in `updates`, added on first use
+    fn pending<'a>(updates: &'a mut Vec<Node>, node: &Node) -> &'a mut Node {
+        let index = match updates.iter().position(|n| n.id == node.id) {
+            Some(index) => index,
+            None => {
+                updates.push(node.clone());
+                updates.len() - 1
+            }
+        };
+        &mut updates[index]
+    }
+
+    /// Point the relations to `from` at `into` instead, dropping the ones that would become
+    /// duplicates or relations of `into` to itself. The changed nodes go to `updates`.
+    fn retarget_relations(
+        nodes: &[Node],
+        from: &str,
+        into: &str,
+        updates: &mut Vec<Node>,
+        retargeted: &mut Vec<RetargetedRelation>,
+    ) {
+        for node in nodes {
+            if node.id != from && node.relations().iter().any(|r| r.target == from) {
+                let node = Self::pending(updates, node);
+                let original = std::mem::take(&mut node.meta.relations);
+                for relation in &original {
+                    if relation.target != from {
+                        node.meta.relations.push(relation.clone());
+                        continue;
+                    }
+                    let moved = Relation {
+                        target: into.to_string(),
+                        kind: relation.kind,
+                    };
+                    let keep = moved.target != node.id && !original.contains(&moved);
+                    if keep {
+                        node.meta.relations.push(moved);
+                    }
+                    retargeted.push(RetargetedRelation {
+                        source: node.id.clone(),
+                        relation: relation.clone(),
+                        into: keep.then(|| into.to_string()),
+                    });
+                }
+            }
+            Self::retarget_relations(&node.children, from, into, updates, retargeted);
+        }
+    }
+
+    /// Take `node` out of its siblings' `after` chain: whoever came after it now comes after
+    /// its predecessor. Returns the ids of the siblings that changed.
+    fn detach_from_chain(siblings: &mut [Node], node: &Node) -> Vec<String> {
+        let mut changed = Vec::new();
+        for sibling in siblings.iter_mut() {
+            if sibling.after() == Some(&node.id) {
+                sibling.meta.after = node.meta.after.clone();
+                changed.push(sibling.id.clone());
+            }
+        }
+        changed
+    }
+
+    /// Insert `node` into its new siblings' `after` chain at the given placement.
+    /// Returns the ids of the siblings that changed.
+    fn attach_to_chain(
+        siblings: &mut [Node],
+        node: &mut Node,
+        placement: Placement,
+    ) -> Result<Vec<String>, String> {
+        let mut changed = Vec::new();
+        node.meta.after = match placement {
+            Placement::First => {
+                if let Some(first) = siblings.first_mut() {
+                    first.meta.after = Some(node.id.clone());
+                    changed.push(first.id.clone());
+                }
+                None
+            }
+            Placement::Last => siblings.last().map(|last| last.id.clone()),
+            Placement::After(sibling_hash) => {
+                if !siblings.iter().any(|n| n.id == sibling_hash) {
+                    return Err(format!(
+                        "Node '{}' is not a sibling at the target location.",
+                        sibling_hash
+                    ));
+                }
+                if let Some(follower) = siblings
+                    .iter_mut()
+                    .find(|n| n.after() == Some(&sibling_hash))
+                {
+                    follower.meta.after = Some(node.id.clone());
+                    changed.push(follower.id.clone());
+                }
+                Some(sibling_hash)
+            }
+        };
+        Ok(changed)
     }
 
     /// Build node tree recursively from filesystem
@@ -134,7 +758,7 @@ impl NodeManager {
     /// Topological sort based on "after" attribute
     pub fn sort_by_after_attribute(nodes: &mut Vec<Node>) {
         let mut sorted = Vec::new();
-        let mut remaining: Vec<_> = nodes.drain(..).collect();
+        let mut remaining: Vec<_> = std::mem::take(nodes);
 
         while !remaining.is_empty() {
             let mut progress = false;
@@ -161,7 +785,7 @@ impl NodeManager {
             // If no progress was made, we have a circular dependency or missing reference
             // Just add remaining nodes in their current order
             if !progress && !remaining.is_empty() {
-                sorted.extend(remaining.drain(..));
+                sorted.append(&mut remaining);
                 break;
             }
         }
@@ -169,74 +793,106 @@ impl NodeManager {
         *nodes = sorted;
     }
 
-    /// Get all nodes as a flat list (id, blurb pairs)
-    pub fn get_all_nodes_flat() -> Vec<(String, String)> {
-        let mut nodes_flat = Vec::new();
-        Self::get_all_nodes_flat_recursive(Path::new("."), &mut nodes_flat);
-        nodes_flat
+    /// Make the `after` chain of sorted siblings spell out their current order, which is
+    /// otherwise left to the directory order when several of them have no `after`
+    pub fn pin_order(siblings: &mut [Node]) -> Result<(), String> {
+        let mut journal = MetaJournal::default();
+        let mut previous: Option<String> = None;
+        for node in siblings.iter_mut() {
+            if node.meta.after != previous {
+                node.meta.after = previous.clone();
+                if let Err(e) = journal.save(node) {
+                    journal.roll_back();
+                    return Err(e);
+                }
+            }
+            previous = Some(node.id.clone());
+        }
+        Ok(())
     }
 
-    /// Helper for get_all_nodes_flat
-    fn get_all_nodes_flat_recursive(dir: &Path, nodes_flat: &mut Vec<(String, String)>) {
-        if let Ok(entries) = fs::read_dir(dir) {
-            for entry in entries.filter_map(Result::ok) {
-                let path = entry.path();
-                if path.is_dir() {
-                    let meta_path = path.join("meta.yaml");
-                    if meta_path.exists() {
-                        let node_name = path.file_name().unwrap().to_str().unwrap();
-                        let parts: Vec<&str> = node_name.splitn(2, ' ').collect();
-                        if parts.len() == 2 {
-                            let id = parts[0].to_string();
-                            let blurb = parts[1].to_string();
-                            nodes_flat.push((id, blurb));
-                            Self::get_all_nodes_flat_recursive(&path, nodes_flat);
-                        }
-                    }
-                }
+    /// Find a node by id in an already built node tree
+    pub fn find_in_tree<'a>(nodes: &'a [Node], id: &str) -> Option<&'a Node> {
+        for node in nodes {
+            if node.id == id {
+                return Some(node);
+            }
+            if let Some(found) = Self::find_in_tree(&node.children, id) {
+                return Some(found);
             }
         }
+        None
+    }
+
+    /// Get all nodes as a flat list (id, blurb pairs)
+    pub fn get_all_nodes_flat() -> Vec<(String, String)> {
+        NodeIndex::current()
+            .entries()
+            .map(|(id, entry)| (id.clone(), entry.title.clone()))
+            .collect()
     }
 
     /// Print nodes recursively with indentation
     pub fn print_nodes_recursive(nodes: &[Node], prefix: &str) {
         for node in nodes {
-            println!("{}{} {}", prefix, node.id, node.blurb());
+            println!("{}{}", prefix, Self::describe_node(node));
             Self::print_nodes_recursive(&node.children, &format!("  {}", prefix));
         }
     }
 
-    /// List nodes starting from the configured starting node
-    pub fn list_nodes() -> Result<(), String> {
-        let content = fs::read_to_string("bok.yaml")
+    /// One-line description of a node: `<id> <blurb>`, followed by its relations if there are any
+    pub fn describe_node(node: &Node) -> String {
+        if node.relations().is_empty() {
+            return format!("{} {}", node.id, node.blurb());
+        }
+        let relations: Vec<String> = node
+            .relations()
+            .iter()
+            .map(|r| format!("{}: {}", r.kind, r.target))
+            .collect();
+        format!("{} {} [{}]", node.id, node.blurb(), relations.join(", "))
+    }
+
+    /// Read the configured starting node id from bok.yaml
+    pub fn starting_node_id() -> Result<String, String> {
+        let content = fs::read_to_string(config_path())
             .map_err(|e| format!("Unable to load bok.yaml: {}", e))?;
 
         let bok_config: BokConfig = serde_yaml::from_str(&content)
             .map_err(|e| format!("Unable to parse bok.yaml: {}", e))?;
 
-        let starting_node_id = bok_config.starting_node;
+        Ok(bok_config.starting_node)
+    }
 
-        let mut all_nodes = Self::get_nodes_recursive(Path::new("."));
+    /// Build the node tree and split the root level into the starting node and the remaining roots
+    pub fn get_book_tree() -> Result<(Option<Node>, Vec<Node>), String> {
+        let starting_node_id = Self::starting_node_id()?;
 
-        let mut starting_node_index = None;
-        for (i, node) in all_nodes.iter().enumerate() {
-            if node.id == starting_node_id {
-                starting_node_index = Some(i);
-                break;
-            }
+        let mut all_nodes = Self::get_nodes_recursive(book_root());
+        let starting_node = all_nodes
+            .iter()
+            .position(|node| node.id == starting_node_id)
+            .map(|index| all_nodes.remove(index));
+
+        if starting_node.is_none() {
+            eprintln!("Starting node with id {} not found.", starting_node_id);
         }
 
-        if let Some(index) = starting_node_index {
-            let starting_node = all_nodes.remove(index);
-            println!("{} {}", starting_node.id, starting_node.blurb());
+        Ok((starting_node, all_nodes))
+    }
+
+    /// List nodes starting from the configured starting node
+    pub fn list_nodes() -> Result<(), String> {
+        let (starting_node, other_nodes) = Self::get_book_tree()?;
+
+        if let Some(starting_node) = starting_node {
+            println!("{}", Self::describe_node(&starting_node));
             Self::print_nodes_recursive(&starting_node.children, "  ");
-        } else {
-            eprintln!("Starting node with id {} not found.", starting_node_id);
         }
 
         // Print remaining root nodes
-        for node in all_nodes {
-            println!("{} {}", node.id, node.blurb());
+        for node in other_nodes {
+            println!("{}", Self::describe_node(&node));
             Self::print_nodes_recursive(&node.children, "  ");
         }
 
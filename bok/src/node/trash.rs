@@ -0,0 +1,144 @@
+use super::index::NodeIndex;
+use super::node::{Node, Relation};
+use crate::config::{book_root, data_dir};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Describes a removed node, stored as trash.yaml next to the node directory in the trash
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrashManifest {
+    pub id: String,
+    pub title: String,
+    /// Directory the node was removed from, relative to the book root
+    pub parent: PathBuf,
+    /// Seconds since the Unix epoch
+    pub removed_at: u64,
+    /// Relations of other nodes that pointed into the removed subtree
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relations: Vec<RemovedRelation>,
+    /// Relations of other nodes that a merge pointed at the node merged into
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retargeted: Vec<RetargetedRelation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemovedRelation {
+    pub source: String,
+    #[serde(flatten)]
+    pub relation: Relation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetargetedRelation {
+    pub source: String,
+    /// The relation as it was, pointing at the trashed node
+    #[serde(flatten)]
+    pub relation: Relation,
+    /// The node the relation points at instead, None when the merge dropped it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub into: Option<String>,
+}
+
+/// A node directory in the trash together with its manifest
+pub struct TrashEntry {
+    pub dir: PathBuf,
+    pub manifest: TrashManifest,
+}
+
+impl TrashEntry {
+    /// The trashed node directory (`<id> <blurb>`) inside the entry
+    pub fn node_dir(&self) -> PathBuf {
+        self.dir
+            .join(format!("{} {}", self.manifest.id, self.manifest.title))
+    }
+}
+
+pub struct Trash;
+
+impl Trash {
+    fn trash_dir() -> PathBuf {
+        data_dir().join("trash")
+    }
+
+    /// Move a node directory (with its subtree) into the trash
+    pub fn put(
+        node: &Node,
+        parent: &Path,
+        relations: Vec<RemovedRelation>,
+        retargeted: Vec<RetargetedRelation>,
+    ) -> Result<TrashEntry, String> {
+        let removed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let manifest = TrashManifest {
+            id: node.id.clone(),
+            title: node.blurb().to_string(),
+            parent: parent
+                .strip_prefix(book_root())
+                .unwrap_or(parent)
+                .to_path_buf(),
+            removed_at,
+            relations,
+            retargeted,
+        };
+        let entry = TrashEntry {
+            dir: Self::trash_dir().join(format!("{}-{}", removed_at, node.id)),
+            manifest,
+        };
+
+        fs::create_dir_all(&entry.dir)
+            .map_err(|e| format!("Failed to create '{}': {}", entry.dir.display(), e))?;
+        let yaml = serde_yaml::to_string(&entry.manifest)
+            .map_err(|e| format!("Failed to serialize trash manifest: {}", e))?;
+        fs::write(entry.dir.join("trash.yaml"), yaml)
+            .map_err(|e| format!("Failed to write trash manifest: {}", e))?;
+        NodeIndex::invalidate();
+        if let Err(e) = fs::rename(&node.path, entry.node_dir()) {
+            let _ = fs::remove_dir_all(&entry.dir);
+            return Err(format!(
+                "Error moving '{}' to the trash: {}",
+                node.path.display(),
+                e
+            ));
+        }
+
+        Ok(entry)
+    }
+
+    /// All trash entries, oldest first
+    pub fn list() -> Vec<TrashEntry> {
+        let mut entries: Vec<TrashEntry> = fs::read_dir(Self::trash_dir())
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let content = fs::read_to_string(entry.path().join("trash.yaml")).ok()?;
+                let manifest = serde_yaml::from_str(&content).ok()?;
+                Some(TrashEntry {
+                    dir: entry.path(),
+                    manifest,
+                })
+            })
+            .collect();
+        entries.sort_by_key(|entry| entry.manifest.removed_at);
+        entries
+    }
+
+    /// The most recently trashed entry for a node id
+    pub fn find(node_hash: &str) -> Result<TrashEntry, String> {
+        Self::list()
+            .into_iter()
+            .rev()
+            .find(|entry| entry.manifest.id == node_hash)
+            .ok_or_else(|| format!("Node with hash '{}' not found in the trash.", node_hash))
+    }
+
+    /// Drop an entry once its node has been restored
+    pub fn discard(entry: &TrashEntry) -> Result<(), String> {
+        fs::remove_dir_all(&entry.dir)
+            .map_err(|e| format!("Failed to remove '{}': {}", entry.dir.display(), e))
+    }
+}
@@ -0,0 +1,175 @@
+#![allow(dead_code)]
+
+use assert_cmd::cargo_bin;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::Command;
+use std::thread::{self, JoinHandle};
+
+/// A `bok` command running in the given book directory
+pub fn bok(dir: &Path) -> Command {
+    let mut cmd = Command::new(cargo_bin!("bok"));
+    cmd.current_dir(dir);
+    cmd
+}
+
+/// Find the id of the node directory whose name ends with the given blurb
+pub fn node_id(dir: &Path, blurb: &str) -> String {
+    for entry in fs::read_dir(dir).unwrap().filter_map(Result::ok) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some((id, title)) = name.split_once(' ') {
+            if title == blurb {
+                return id.to_string();
+            }
+            if entry.path().join("meta.yaml").exists() {
+                let found = node_id(&entry.path(), blurb);
+                if !found.is_empty() {
+                    return found;
+                }
+            }
+        }
+    }
+    String::new()
+}
+
+/// The completion-style argument (`<id>-<blurb-with-hyphens>`) for a node
+pub fn node_arg(dir: &Path, blurb: &str) -> String {
+    format!("{}-{}", node_id(dir, blurb), blurb.replace(' ', "-"))
+}
+
+/// Run `bok init` followed by `bok node add` for every (parent, blurb) pair
+pub fn init_book(dir: &Path, title: &str, nodes: &[(&str, &str)]) {
+    bok(dir).args(["init", title]).assert_success();
+    for (parent, blurb) in nodes {
+        let parent_arg = node_arg(dir, parent);
+        bok(dir)
+            .args(["node", "add", "--under", &parent_arg, blurb])
+            .assert_success();
+    }
+}
+
+pub trait AssertSuccess {
+    fn assert_success(&mut self);
+}
+
+impl AssertSuccess for Command {
+    fn assert_success(&mut self) {
+        let output = self.output().unwrap();
+        assert!(
+            output.status.success(),
+            "command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+/// Switch the book's LLM provider to the deterministic dummy provider
+pub fn use_dummy_llm(dir: &Path) {
+    let config_path = dir.join("bok.yaml");
+    let config = fs::read_to_string(&config_path).unwrap();
+    fs::write(
+        config_path,
+        config.replace("provider: ollama", "provider: dummy"),
+    )
+    .unwrap();
+}
+
+/// The text.qmd of the node with the given blurb
+pub fn node_text_path(dir: &Path, blurb: &str) -> std::path::PathBuf {
+    fn find(dir: &Path, name: &str) -> Option<std::path::PathBuf> {
+        for entry in fs::read_dir(dir).ok()?.filter_map(Result::ok) {
+            if !entry.path().join("meta.yaml").exists() {
+                continue;
+            }
+            if entry.file_name().to_string_lossy() == name {
+                return Some(entry.path());
+            }
+            if let Some(found) = find(&entry.path(), name) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    let name = format!("{} {}", node_id(dir, blurb), blurb);
+    find(dir, &name).unwrap().join("text.qmd")
+}
+
+/// A local HTTP server answering one request with each (status, content type, body) in turn.
+/// Returns its port and a handle that yields the raw requests once all were answered.
+pub fn mock_server(responses: Vec<(u16, &'static str, String)>) -> (u16, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handle = thread::spawn(move || {
+        let mut requests = Vec::new();
+        for (status, content_type, body) in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some((name, value)) = line.split_once(':')
+                    && name.eq_ignore_ascii_case("content-length")
+                {
+                    content_length = value.trim().parse().unwrap();
+                }
+                request.push_str(&line);
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+            }
+            let mut request_body = vec![0; content_length];
+            reader.read_exact(&mut request_body).unwrap();
+            request.push_str(&String::from_utf8_lossy(&request_body));
+            requests.push(request);
+
+            write!(
+                stream,
+                "HTTP/1.1 {} Mock\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                content_type,
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+        requests
+    });
+    (port, handle)
+}
+
+/// Point the book at the openai provider served by a [`mock_server`] on the given port
+pub fn use_openai(dir: &Path, port: u16) {
+    let config_path = dir.join("bok.yaml");
+    let config = fs::read_to_string(&config_path).unwrap();
+    fs::write(
+        config_path,
+        config
+            .replace(
+                "provider: ollama",
+                &format!("provider: openai\n  base_url: http://127.0.0.1:{}/v1", port),
+            )
+            .replace("model: qwen3:8b", "model: local-model"),
+    )
+    .unwrap();
+}
+
+/// A streamed chat completion delivering the text in the given chunks
+pub fn chat_stream(chunks: &[&str]) -> String {
+    let mut stream: String = chunks
+        .iter()
+        .map(|chunk| {
+            let event = serde_json::json!({"choices": [{"index": 0, "delta": {"content": chunk}}]});
+            format!("data: {}\n\n", event)
+        })
+        .collect();
+    stream.push_str(
+        "data: {\"choices\": [{\"index\": 0, \"delta\": {}, \"finish_reason\": \"stop\"}]}\n\n",
+    );
+    stream.push_str("data: [DONE]\n\n");
+    stream
+}
@@ -117,7 +117,7 @@ NODE_TO_BE_REMOVED_HASH=$(basename "$NODE_TO_BE_REMOVED_DIR" | awk '{print $1}')
 HYPHENATED_RM_BLURB="${NODE_TO_BE_REMOVED_HASH}-Node-To-Be-Removed-Blurb"
 
 # Attempt to remove the node using the hyphenated blurb
-"$BOK_EXE" node rm "$HYPHENATED_RM_BLURB"
+"$BOK_EXE" node rm --yes "$HYPHENATED_RM_BLURB"
 
 # Verify that the node is removed
 if [ -d "$NODE_TO_BE_REMOVED_DIR" ]; then
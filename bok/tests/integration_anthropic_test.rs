@@ -0,0 +1,164 @@
+mod common;
+
+use assert_cmd::prelude::*;
+use common::{bok, init_book, mock_server, node_text_path};
+use predicates::prelude::*;
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+/// Point the book at the anthropic provider served on the given local port
+fn use_anthropic(dir: &Path, port: u16) {
+    let config_path = dir.join("bok.yaml");
+    let config = fs::read_to_string(&config_path).unwrap();
+    fs::write(
+        config_path,
+        config.replace(
+            "provider: ollama",
+            &format!("provider: anthropic\n  base_url: http://127.0.0.1:{}", port),
+        ),
+    )
+    .unwrap();
+}
+
+/// A streamed Messages API response delivering the text in the given chunks
+fn event_stream(chunks: &[&str]) -> String {
+    let mut events = vec![
+        json!({"type": "message_start", "message": {"id": "msg_1", "content": []}}),
+        json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+        json!({"type": "ping"}),
+    ];
+    events.extend(chunks.iter().map(|chunk| {
+        json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": chunk}})
+    }));
+    events.extend([
+        json!({"type": "content_block_stop", "index": 0}),
+        json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}}),
+        json!({"type": "message_stop"}),
+    ]);
+    events
+        .iter()
+        .map(|event| {
+            format!(
+                "event: {}\ndata: {}\n\n",
+                event["type"].as_str().unwrap(),
+                event
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_anthropic_import_streams_units() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[]);
+    // Units split across deltas, the last one without a trailing newline
+    let (port, server) = mock_server(vec![(
+        200,
+        "text/event-stream",
+        event_stream(&[
+            "[\"Roses need pruning\", \"Prune ",
+            "roses in spring.\"]\n[\"Tulips",
+            "\", \"Plant tulips in autumn.\"]",
+        ]),
+    )]);
+    use_anthropic(temp_path, port);
+    fs::write(
+        temp_path.join("garden.md"),
+        "Prune roses in spring.\n\nPlant tulips in autumn.",
+    )?;
+
+    bok(temp_path)
+        .args(["import", "garden.md"])
+        .env("ANTHROPIC_API_KEY", "test-key")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Using Anthropic provider."))
+        .stdout(predicate::str::contains(
+            "Successfully imported 2 parts from 'garden.md'.",
+        ));
+
+    assert_eq!(
+        fs::read_to_string(node_text_path(temp_path, "Roses need pruning"))?,
+        "Prune roses in spring."
+    );
+    assert_eq!(
+        fs::read_to_string(node_text_path(temp_path, "Tulips"))?,
+        "Plant tulips in autumn."
+    );
+
+    let requests = server.join().unwrap();
+    let request = &requests[0];
+    assert!(request.starts_with("POST /v1/messages "));
+    assert!(request.contains("x-api-key: test-key"));
+    assert!(request.contains("anthropic-version: 2023-06-01"));
+    assert!(request.contains("\"stream\":true"));
+    assert!(request.contains("Prune roses in spring."));
+
+    Ok(())
+}
+
+#[test]
+fn test_anthropic_json_in_code_fence() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[("Book", "Gardening basics")]);
+    fs::write(
+        node_text_path(temp_path, "Gardening basics"),
+        "How to file your taxes.",
+    )?;
+    let message = json!({
+        "content": [{
+            "type": "text",
+            "text": "```json\n{\"fits\": false, \"reason\": \"the text is about taxes\"}\n```"
+        }],
+        "stop_reason": "end_turn"
+    });
+    let (port, server) = mock_server(vec![(200, "application/json", message.to_string())]);
+    use_anthropic(temp_path, port);
+    // The key may come from bok.yaml as well
+    let config = fs::read_to_string(temp_path.join("bok.yaml"))?;
+    fs::write(
+        temp_path.join("bok.yaml"),
+        config.replace(
+            "provider: anthropic",
+            "provider: anthropic\n  api_key: config-key",
+        ),
+    )?;
+
+    bok(temp_path)
+        .args(["check", "--llm", "--only", "blurb-fit"])
+        .env_remove("ANTHROPIC_API_KEY")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("the text is about taxes"));
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].contains("x-api-key: config-key"));
+    assert!(requests[0].contains("\"stream\":false"));
+
+    Ok(())
+}
+
+#[test]
+fn test_anthropic_needs_api_key() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[]);
+    use_anthropic(temp_path, 9);
+    fs::write(temp_path.join("notes.md"), "Some notes.")?;
+
+    bok(temp_path)
+        .args(["import", "notes.md"])
+        .env_remove("ANTHROPIC_API_KEY")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("ANTHROPIC_API_KEY"));
+
+    Ok(())
+}
@@ -0,0 +1,314 @@
+mod common;
+
+use assert_cmd::prelude::*;
+use common::{bok, init_book, node_arg, node_id, node_text_path, use_dummy_llm};
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_check_reports_structural_problems() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[("Book", "One"), ("Book", "Two")]);
+    bok(temp_path)
+        .arg("check")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No problems found."));
+
+    let one_id = node_id(temp_path, "One");
+    let two_id = node_id(temp_path, "Two");
+    let one_dir = node_text_path(temp_path, "One")
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    let two_dir = node_text_path(temp_path, "Two")
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    let book_dir = one_dir.parent().unwrap().to_path_buf();
+
+    // One and Two placed after each other, Two pointing at a missing node
+    fs::write(
+        one_dir.join("meta.yaml"),
+        format!("meta:\n  title: One\n  after: '{}'\n", two_id),
+    )?;
+    fs::write(
+        two_dir.join("meta.yaml"),
+        format!(
+            "meta:\n  title: Two\n  after: '{}'\n  relations:\n  - target: '42'\n    kind: see-also\n",
+            one_id
+        ),
+    )?;
+    fs::write(one_dir.join("text.qmd"), "")?;
+    fs::create_dir(book_dir.join("7 Stray"))?;
+
+    bok(temp_path)
+        .arg("check")
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("[ordering]"))
+        .stdout(predicate::str::contains("7 Stray: meta.yaml is missing"))
+        .stdout(predicate::str::contains("[empty-nodes]"))
+        .stdout(predicate::str::contains("[dangling-relations]"));
+
+    let output = bok(temp_path)
+        .args([
+            "check",
+            "--json",
+            "--only",
+            "empty-nodes,dangling-relations",
+        ])
+        .output()?;
+    // Warnings alone pass unless --strict
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(report["errors"], 0);
+    assert_eq!(report["warnings"], 2);
+    assert_eq!(report["findings"][1]["check"], "empty-nodes");
+    assert_eq!(report["findings"][1]["node"], one_id.as_str());
+
+    bok(temp_path)
+        .args(["check", "--strict", "--only", "empty-nodes"])
+        .assert()
+        .code(1);
+
+    Ok(())
+}
+
+#[test]
+fn test_check_with_llm() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[("Book", "Gardening basics")]);
+    use_dummy_llm(temp_path);
+    fs::write(
+        node_text_path(temp_path, "Gardening basics"),
+        "How to file your taxes.",
+    )?;
+
+    // LLM checks are opt-in
+    bok(temp_path).arg("check").assert().success();
+    bok(temp_path)
+        .args(["check", "--llm"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[blurb-fit]"));
+
+    Ok(())
+}
+
+#[test]
+fn test_check_completeness_reports_missing_text() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[("Book", "Soil"), ("Soil", "Compost")]);
+    use_dummy_llm(temp_path);
+    fs::remove_file(node_text_path(temp_path, "Compost"))?;
+
+    bok(temp_path)
+        .args(["check", "--only", "completeness"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "text.qmd is missing, the chapter was assessed without it",
+        ));
+
+    // The flags of `bok check` do not apply to its subcommands
+    bok(temp_path)
+        .args(["check", "--strict", "completeness"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    Ok(())
+}
+
+#[test]
+fn test_check_completeness_creates_stubs() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(
+        temp_path,
+        "Book",
+        &[("Book", "Soil"), ("Soil", "Compost"), ("Book", "Tools")],
+    );
+    use_dummy_llm(temp_path);
+    fs::write(
+        node_text_path(temp_path, "Soil"),
+        "Soil is alive.\n\nTODO: Mulching\n",
+    )?;
+    fs::write(node_text_path(temp_path, "Tools"), "A spade will do.\n")?;
+
+    let output = bok(temp_path)
+        .args(["check", "completeness", "--json"])
+        .output()?;
+    assert!(output.status.success());
+    let reports: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let chapter = |blurb: &str| {
+        reports
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|report| report["blurb"] == blurb)
+            .unwrap()
+            .clone()
+    };
+    let soil = chapter("Soil");
+    assert_eq!(soil["gaps"][0], "'Compost' has no text yet");
+    assert_eq!(soil["suggested_nodes"][0]["blurb"], "Mulching");
+    assert_eq!(chapter("Tools")["suggested_nodes"], serde_json::json!([]));
+
+    let soil_arg = node_arg(temp_path, "Soil");
+    bok(temp_path)
+        .args([
+            "check",
+            "completeness",
+            "--node",
+            &soil_arg,
+            "--create-stubs",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("suggested node: Mulching"))
+        .stdout(predicate::str::contains("created stub node"));
+
+    let stub = node_text_path(temp_path, "Mulching");
+    let soil_dir = node_text_path(temp_path, "Soil")
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    assert_eq!(stub.parent().unwrap().parent().unwrap(), soil_dir);
+    assert_eq!(fs::read_to_string(&stub)?, "");
+    let compost_id = node_id(temp_path, "Compost");
+    let meta = fs::read_to_string(stub.parent().unwrap().join("meta.yaml"))?;
+    assert!(meta.contains(&compost_id));
+
+    // With two unordered children, the stub still goes after the one listed last
+    let tools_arg = node_arg(temp_path, "Tools");
+    bok(temp_path)
+        .args(["node", "add", "Rakes", "--under", &tools_arg])
+        .assert()
+        .success();
+    bok(temp_path)
+        .args(["node", "add", "Shovels", "--under", &tools_arg])
+        .assert()
+        .success();
+    for blurb in ["Rakes", "Shovels"] {
+        let dir = node_text_path(temp_path, blurb)
+            .parent()
+            .unwrap()
+            .to_path_buf();
+        fs::write(
+            dir.join("meta.yaml"),
+            format!("meta:\n  title: {}\n", blurb),
+        )?;
+    }
+    fs::write(node_text_path(temp_path, "Tools"), "TODO: Hoes\n")?;
+    bok(temp_path)
+        .args([
+            "check",
+            "completeness",
+            "--node",
+            &tools_arg,
+            "--create-stubs",
+        ])
+        .assert()
+        .success();
+    let output = bok(temp_path).args(["node", "ls"]).output()?;
+    let listing = String::from_utf8(output.stdout)?;
+    let position = |blurb: &str| listing.find(blurb).unwrap();
+    assert!(position("Hoes") > position("Rakes"));
+    assert!(position("Hoes") > position("Shovels"));
+    // The order of the two is written down now
+    let unordered = ["Rakes", "Shovels"]
+        .iter()
+        .filter(|blurb| {
+            let dir = node_text_path(temp_path, blurb)
+                .parent()
+                .unwrap()
+                .to_path_buf();
+            !fs::read_to_string(dir.join("meta.yaml"))
+                .unwrap()
+                .contains("after:")
+        })
+        .count();
+    assert_eq!(unordered, 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_check_duplicates() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(
+        temp_path,
+        "Book",
+        &[("Book", "Roses"), ("Book", "Pruning"), ("Book", "Soil")],
+    );
+    use_dummy_llm(temp_path);
+    let roses = "Roses need pruning in early spring, just before the buds break. \
+        Cut back dead wood first, then thin the centre of the bush.";
+    fs::write(node_text_path(temp_path, "Roses"), roses)?;
+    fs::write(
+        node_text_path(temp_path, "Pruning"),
+        format!("{} Always use clean shears.", roses),
+    )?;
+    fs::write(
+        node_text_path(temp_path, "Soil"),
+        "Loam drains well and holds nutrients.",
+    )?;
+
+    let output = bok(temp_path)
+        .args(["check", "duplicates", "--json", "--llm"])
+        .output()?;
+    assert!(output.status.success());
+    let clusters: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(clusters.as_array().unwrap().len(), 1);
+    let blurbs: Vec<&str> = clusters[0]["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|node| node["blurb"].as_str().unwrap())
+        .collect();
+    assert_eq!(blurbs.len(), 2);
+    assert!(blurbs.contains(&"Roses") && blurbs.contains(&"Pruning"));
+    assert!(clusters[0]["pairs"][0]["similarity"].as_f64().unwrap() >= 0.5);
+    assert!(clusters[0]["pairs"][0]["reason"].is_string());
+
+    // Also reported by the plain check, and gone after merging
+    bok(temp_path)
+        .args(["check", "--only", "duplicates"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[duplicates]"));
+    bok(temp_path)
+        .args([
+            "node",
+            "merge",
+            &node_arg(temp_path, "Pruning"),
+            &node_arg(temp_path, "Roses"),
+        ])
+        .assert()
+        .success();
+    bok(temp_path)
+        .args(["check", "duplicates"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No duplicates found."));
+
+    bok(temp_path)
+        .args(["check", "duplicates", "--threshold", "1.5"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("between 0 and 1"));
+
+    Ok(())
+}
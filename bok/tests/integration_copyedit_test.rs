@@ -0,0 +1,82 @@
+mod common;
+
+use assert_cmd::prelude::*;
+use common::{bok, init_book, node_arg, node_text_path, use_dummy_llm};
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+const STYLE: &str = "style:
+  em_dash: closed
+  preferred_terms:
+    e-mail: email
+";
+
+const TEXT: &str = "Send an e-mail to the the editor -- today.\nNo e-mails.\n";
+
+#[test]
+fn test_copyedit_suggestions_follow_the_style_sheet() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[("Book", "Chapter")]);
+    use_dummy_llm(temp_path);
+    let config_path = temp_path.join("bok.yaml");
+    fs::write(
+        &config_path,
+        format!("{}{}", fs::read_to_string(&config_path)?, STYLE),
+    )?;
+    let text_path = node_text_path(temp_path, "Chapter");
+    fs::write(&text_path, TEXT)?;
+    let chapter = node_arg(temp_path, "Chapter");
+
+    let output = bok(temp_path)
+        .args(["copyedit", &chapter, "--json"])
+        .output()?;
+    assert!(output.status.success());
+    let suggestions: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let spans: Vec<(u64, u64, &str, &str)> = suggestions
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| {
+            (
+                s["line"].as_u64().unwrap(),
+                s["column"].as_u64().unwrap(),
+                s["original"].as_str().unwrap(),
+                s["category"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    // "e-mails" is a different word and is left alone
+    assert_eq!(
+        spans,
+        vec![
+            (1, 9, "e-mail", "terminology"),
+            (1, 19, "the the", "grammar"),
+            (1, 33, " -- ", "punctuation"),
+        ]
+    );
+
+    bok(temp_path)
+        .args(["copyedit", &chapter, "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "1:19 [grammar] \"the the\" -> \"the\"",
+        ));
+    assert_eq!(fs::read_to_string(&text_path)?, TEXT);
+
+    assert_cmd::Command::from_std(bok(temp_path))
+        .args(["copyedit", &chapter])
+        .write_stdin("y\nn\ny\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Applied 2 of 3 suggestions"));
+    assert_eq!(
+        fs::read_to_string(&text_path)?,
+        "Send an email to the the editor—today.\nNo e-mails.\n"
+    );
+
+    Ok(())
+}
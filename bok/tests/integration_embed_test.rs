@@ -0,0 +1,52 @@
+mod common;
+
+use assert_cmd::prelude::*;
+use common::{bok, init_book, node_text_path, use_dummy_llm};
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_embeddings_are_cached_by_content() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[("Book", "Roses"), ("Book", "Tulips")]);
+    use_dummy_llm(temp_path);
+    let embed = |expected: &str| {
+        bok(temp_path)
+            .arg("embed")
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected.to_string()));
+    };
+
+    embed("Embedded 3 node(s), 0 unchanged.");
+    assert!(temp_path.join(".bok/embeddings.json").exists());
+    embed("Embedded 0 node(s), 3 unchanged.");
+
+    fs::write(node_text_path(temp_path, "Roses"), "Roses like sun.")?;
+    embed("Embedded 1 node(s), 2 unchanged.");
+
+    // Another embedding model makes the cached vectors useless
+    let config_path = temp_path.join("bok.yaml");
+    let config = fs::read_to_string(&config_path)?;
+    fs::write(
+        &config_path,
+        config.replace(
+            "provider: dummy",
+            "provider: dummy\n  embedding_model: other",
+        ),
+    )?;
+    embed("Embedded 3 node(s), 0 unchanged.");
+
+    // Semantic duplicate detection reuses them
+    fs::write(node_text_path(temp_path, "Tulips"), "Roses like sun.")?;
+    bok(temp_path)
+        .args(["check", "duplicates", "--semantic", "--threshold", "0.6"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Cluster 1:"));
+
+    Ok(())
+}
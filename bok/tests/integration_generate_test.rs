@@ -0,0 +1,121 @@
+mod common;
+
+use assert_cmd::prelude::*;
+use common::{bok, init_book, node_arg, node_id, node_text_path};
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_generate_quarto_book() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(
+        temp_path,
+        "My Book",
+        &[("My Book", "First"), ("First", "Deeper")],
+    );
+    let first_id = node_id(temp_path, "First");
+
+    // User-provided files must be referenced but left untouched
+    fs::write(temp_path.join("styles.css"), "body {}")?;
+    fs::write(temp_path.join("index.qmd"), "# My own preface")?;
+
+    bok(temp_path)
+        .args(["generate", "quarto"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Using user-provided index.qmd"));
+
+    let quarto_yml = fs::read_to_string(temp_path.join("_quarto.yml"))?;
+    let chapter_file = format!("chapters/01-{}.qmd", first_id);
+    assert!(quarto_yml.contains("- index.qmd"));
+    assert!(quarto_yml.contains(&chapter_file));
+    assert!(quarto_yml.contains("styles.css"));
+
+    let chapter = fs::read_to_string(temp_path.join(&chapter_file))?;
+    assert!(chapter.contains("# First\n"));
+    assert!(chapter.contains("## Deeper\n"));
+
+    assert_eq!(
+        fs::read_to_string(temp_path.join("index.qmd"))?,
+        "# My own preface"
+    );
+    assert_eq!(fs::read_to_string(temp_path.join("styles.css"))?, "body {}");
+
+    Ok(())
+}
+
+#[test]
+fn test_generate_imported_sections() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[]);
+    let original = "# Garden\n\nNotes from the garden.\n\n## Spring\n\nRoses need pruning.\n\nTulips bloom.\n\n## Autumn\n\nDahlias must be lifted.\n";
+    fs::write(temp_path.join("garden.md"), original)?;
+    bok(temp_path)
+        .args([
+            "import",
+            "garden.md",
+            "--no-llm",
+            "--under",
+            &node_arg(temp_path, "Book"),
+        ])
+        .assert()
+        .success();
+
+    bok(temp_path)
+        .args(["generate", "quarto"])
+        .assert()
+        .success();
+
+    // Headings are not repeated and paragraphs do not become headings
+    let chapter = fs::read_to_string(
+        temp_path.join(format!("chapters/01-{}.qmd", node_id(temp_path, "Garden"))),
+    )?;
+    assert_eq!(
+        chapter,
+        format!(
+            "<!-- generated by bok generate quarto -->\n\n{}\n",
+            original
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_generate_points_images_at_node_dirs() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(
+        temp_path,
+        "Book",
+        &[("Book", "Garden"), ("Garden", "Roses")],
+    );
+    let roses_text = node_text_path(temp_path, "Roses");
+    fs::write(
+        &roses_text,
+        "Roses climb.\n\n![A rose](rose.png){width=200}\n\n![Elsewhere](https://example.com/rose.png)\n",
+    )?;
+    fs::write(roses_text.with_file_name("rose.png"), "png")?;
+
+    bok(temp_path)
+        .args(["generate", "quarto"])
+        .assert()
+        .success();
+
+    let roses_dir = roses_text.parent().unwrap().strip_prefix(temp_path)?;
+    let chapter = fs::read_to_string(
+        temp_path.join(format!("chapters/01-{}.qmd", node_id(temp_path, "Garden"))),
+    )?;
+    assert!(chapter.contains(&format!(
+        "![A rose](<../{}/rose.png>){{width=200}}",
+        roses_dir.display()
+    )));
+    assert!(chapter.contains("![Elsewhere](https://example.com/rose.png)"));
+
+    Ok(())
+}
@@ -1,7 +1,13 @@
-use assert_cmd::cargo::cargo_bin;
+mod common;
+
+use assert_cmd::cargo_bin;
 use assert_cmd::prelude::*;
+use common::{
+    bok, chat_stream, init_book, mock_server, node_id, node_text_path, use_dummy_llm, use_openai,
+};
 use predicates::prelude::*;
 use std::fs;
+use std::path::Path;
 use std::process::Command;
 use tempfile::tempdir;
 
@@ -28,11 +34,11 @@ fn test_import_command_with_dummy_provider() -> Result<(), Box<dyn std::error::E
 
     // Run the import command from the temporary directory
     let mut cmd = Command::new(cargo_bin!("bok"));
-    cmd.current_dir(&temp_path); // Set the current directory for the command
+    cmd.current_dir(temp_path); // Set the current directory for the command
     cmd.arg("import").arg(test_file_name);
     cmd.assert()
         .success()
-        .stdout(predicates::str::contains("Using Dummy provider."))
+        .stderr(predicates::str::contains("Using Dummy provider."))
         .stdout(predicates::str::contains(
             "Successfully imported 3 parts from 'test_import_file.md'.",
         ))
@@ -42,23 +48,21 @@ fn test_import_command_with_dummy_provider() -> Result<(), Box<dyn std::error::E
 
     // Verify node creation
     let mut node_count = 0;
-    for entry in fs::read_dir(&temp_path)? {
+    for entry in fs::read_dir(temp_path)? {
         let entry = entry?;
         let path = entry.path();
-        if path.is_dir() {
-            if let Some(dir_name) = path.file_name().and_then(|s| s.to_str()) {
-                if dir_name
-                    .split_whitespace()
-                    .next()
-                    .unwrap_or("")
-                    .parse::<u32>()
-                    .is_ok()
-                {
-                    node_count += 1;
-                    assert!(path.join("meta.yaml").exists());
-                    assert!(path.join("text.qmd").exists());
-                }
-            }
+        if path.is_dir()
+            && let Some(dir_name) = path.file_name().and_then(|s| s.to_str())
+            && dir_name
+                .split_whitespace()
+                .next()
+                .unwrap_or("")
+                .parse::<u32>()
+                .is_ok()
+        {
+            node_count += 1;
+            assert!(path.join("meta.yaml").exists());
+            assert!(path.join("text.qmd").exists());
         }
     }
     assert_eq!(node_count, 3, "Expected 3 nodes to be created.");
@@ -70,6 +74,558 @@ fn test_import_command_with_dummy_provider() -> Result<(), Box<dyn std::error::E
     Ok(())
 }
 
+const GARDEN: &str = "Roses need pruning in early spring.\n\nTulips are planted in autumn. Water them well.\n\nDahlias must be lifted before the frost.\n";
+
+/// A book importing `garden.md` through an LLM that drops a sentence and rewrites a word
+fn paraphrasing_import(dir: &Path) -> std::thread::JoinHandle<Vec<String>> {
+    init_book(dir, "Book", &[]);
+    fs::write(dir.join("garden.md"), GARDEN).unwrap();
+    let (port, server) = mock_server(vec![(
+        200,
+        "text/event-stream",
+        chat_stream(&[
+            "[\"Roses\", \"Roses need pruning in early spring.\"]\n",
+            "[\"Tulips\", \"Tulips are planted in autumn.\"]\n",
+            "[\"Dahlias\", \"Dahlias should be lifted before the frost.\"]\n",
+        ]),
+    )]);
+    use_openai(dir, port);
+    server
+}
+
+#[test]
+fn test_import_patches_paraphrased_nodes() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+    let server = paraphrasing_import(temp_path);
+
+    assert_cmd::Command::from_std(bok(temp_path))
+        .args(["import", "garden.md"])
+        .write_stdin("p\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Validation failed: the imported nodes differ from 'garden.md' in 2 place(s):",
+        ))
+        .stderr(predicate::str::contains(format!(
+            "  line 3 (node {} Tulips): dropped \"Water them well.\"",
+            node_id(temp_path, "Tulips")
+        )))
+        .stderr(predicate::str::is_match(
+            r#"line 5 \(node \d+ Dahlias\): rewritten "\w*must\w*" as "\w*should\w*""#,
+        )?)
+        .stdout(predicate::str::contains(
+            "Patched 2 node(s) with the original text.",
+        ))
+        .stdout(predicate::str::contains(
+            "Validation successful: Reconstructed content matches original.",
+        ));
+    server.join().unwrap();
+
+    assert_eq!(
+        fs::read_to_string(node_text_path(temp_path, "Roses"))?,
+        "Roses need pruning in early spring."
+    );
+    assert_eq!(
+        fs::read_to_string(node_text_path(temp_path, "Tulips"))?,
+        "Tulips are planted in autumn. Water them well."
+    );
+    assert_eq!(
+        fs::read_to_string(node_text_path(temp_path, "Dahlias"))?,
+        "Dahlias must be lifted before the frost."
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_import_rolls_back_on_mismatch() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+    let server = paraphrasing_import(temp_path);
+
+    bok(temp_path)
+        .args(["import", "garden.md", "--on-mismatch", "rollback"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "The imported nodes did not match 'garden.md', removed all 3 of them.",
+        ));
+    server.join().unwrap();
+
+    for blurb in ["Roses", "Tulips", "Dahlias"] {
+        assert_eq!(node_id(temp_path, blurb), "");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_import_directory_rollback_removes_folder_nodes() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+    let server = paraphrasing_import(temp_path);
+    let shed = temp_path.join("notes/shed");
+    fs::create_dir_all(&shed)?;
+    fs::rename(temp_path.join("garden.md"), shed.join("garden.md"))?;
+
+    bok(temp_path)
+        .args([
+            "import",
+            "notes",
+            "--recursive",
+            "--jobs",
+            "1",
+            "--on-mismatch",
+            "rollback",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Added 0 node(s) to the book"));
+    server.join().unwrap();
+
+    for blurb in ["shed", "garden", "Roses"] {
+        assert_eq!(node_id(temp_path, blurb), "");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_import_cleans_llm_blurbs() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+    init_book(temp_path, "Book", &[]);
+    fs::write(temp_path.join("garden.md"), GARDEN)?;
+    let (port, server) = mock_server(vec![(
+        200,
+        "text/event-stream",
+        chat_stream(&[
+            "[\"Roses/pruning\", \"Roses need pruning in early spring.\"]\n",
+            "[\" \", \"Tulips are planted in autumn. Water them well.\"]\n",
+            "[\"Dahlias\", \"Dahlias must be lifted before the frost.\"]\n",
+        ]),
+    )]);
+    use_openai(temp_path, port);
+
+    bok(temp_path)
+        .args(["import", "garden.md"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Added 3 node(s) to the book"));
+    server.join().unwrap();
+
+    // Path separators are replaced and an empty blurb is made from the text
+    assert_ne!(node_id(temp_path, "Roses-pruning"), "");
+    assert_ne!(
+        node_id(temp_path, "Tulips are planted in autumn. Water them well."),
+        ""
+    );
+
+    Ok(())
+}
+
+/// A book importing `garden.md` through an LLM whose second unit is not valid JSON
+fn failing_import(dir: &Path) -> std::thread::JoinHandle<Vec<String>> {
+    init_book(dir, "Book", &[]);
+    fs::write(dir.join("garden.md"), GARDEN).unwrap();
+    let (port, server) = mock_server(vec![(
+        200,
+        "text/event-stream",
+        chat_stream(&[
+            "[\"Roses\", \"Roses need pruning in early spring.\"]\n",
+            "[\"Tulips\", \"Tulips are planted\n",
+            "[\"Dahlias\", \"Dahlias must be lifted before the frost.\"]\n",
+        ]),
+    )]);
+    use_openai(dir, port);
+    server
+}
+
+/// The import ids recorded in `.bok/imports`
+fn import_ids(dir: &Path) -> Vec<String> {
+    fs::read_dir(dir.join(".bok/imports"))
+        .map(|entries| {
+            entries
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_failed_import_adds_nothing() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+    let server = failing_import(temp_path);
+
+    bok(temp_path)
+        .args(["import", "garden.md"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Error processing part:"))
+        .stderr(predicate::str::contains("nothing was added to the book."));
+    server.join().unwrap();
+
+    assert_eq!(node_id(temp_path, "Roses"), "");
+    assert!(import_ids(temp_path).is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_failed_commit_leaves_no_staging() -> Result<(), Box<dyn std::error::Error>> {
+    let setup = |dir: &Path| -> std::io::Result<()> {
+        init_book(dir, "Book", &[]);
+        use_dummy_llm(dir);
+        fs::write(dir.join("garden.md"), "Roses\n\nTulips\n")
+    };
+    let first_dir = tempdir()?;
+    setup(first_dir.path())?;
+    bok(first_dir.path())
+        .args(["import", "garden.md"])
+        .assert()
+        .success();
+    let roses_text = node_text_path(first_dir.path(), "Roses");
+    let roses_dir = roses_text.parent().unwrap().file_name().unwrap();
+
+    // The same import in a book with something in the way of its first node
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+    setup(temp_path)?;
+    fs::create_dir(temp_path.join(roses_dir))?;
+    bok(temp_path)
+        .args(["import", "garden.md"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists"));
+
+    assert_eq!(node_id(temp_path, "Tulips"), "");
+    assert!(import_ids(temp_path).is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_import_keep_partial_skips_bad_parts() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+    let server = failing_import(temp_path);
+
+    bok(temp_path)
+        .args([
+            "import",
+            "garden.md",
+            "--keep-partial",
+            "--on-mismatch",
+            "keep",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Error processing part:"))
+        .stdout(predicate::str::contains(
+            "Added 2 node(s) to the book as import",
+        ));
+    server.join().unwrap();
+
+    assert_ne!(node_id(temp_path, "Roses"), "");
+    assert_ne!(node_id(temp_path, "Dahlias"), "");
+    assert_eq!(node_id(temp_path, "Tulips"), "");
+
+    Ok(())
+}
+
+#[test]
+fn test_import_undo_moves_nodes_to_trash() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+    let server = paraphrasing_import(temp_path);
+
+    bok(temp_path)
+        .args(["import", "garden.md", "--on-mismatch", "keep"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("undo with 'bok import --undo"));
+    server.join().unwrap();
+
+    let ids = import_ids(temp_path);
+    assert_eq!(ids.len(), 1);
+    let manifest = fs::read_to_string(
+        temp_path
+            .join(".bok/imports")
+            .join(&ids[0])
+            .join("import.yaml"),
+    )?;
+    assert!(manifest.contains("file: garden.md"));
+
+    bok(temp_path)
+        .args(["import", "--undo", &ids[0]])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Moved 3 node(s) of import {} to the trash.",
+            ids[0]
+        )));
+
+    for blurb in ["Roses", "Tulips", "Dahlias"] {
+        assert_eq!(node_id(temp_path, blurb), "");
+    }
+    assert_eq!(fs::read_dir(temp_path.join(".bok/trash"))?.count(), 3);
+    assert!(import_ids(temp_path).is_empty());
+
+    bok(temp_path)
+        .args(["import", "--undo", &ids[0]])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "No import with id '{}'.",
+            ids[0]
+        )));
+
+    Ok(())
+}
+
+const OUTLINED: &str = "# Garden\n\nNotes from the garden.\n\n## Spring\n\nRoses need pruning.\n\nTulips bloom.\n\n## Autumn\n\nDahlias must be lifted.\n";
+
+/// Name of the directory holding the node with the given blurb
+fn parent_dir_name(dir: &Path, blurb: &str) -> String {
+    let text_path = node_text_path(dir, blurb);
+    let parent = text_path.parent().unwrap().parent().unwrap();
+    if parent == dir {
+        return String::new();
+    }
+    parent.file_name().unwrap().to_string_lossy().into_owned()
+}
+
+#[test]
+fn test_import_nests_nodes_by_headings() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+    init_book(temp_path, "Book", &[]);
+    fs::write(temp_path.join("garden.md"), OUTLINED)?;
+    let (port, server) = mock_server(vec![
+        (
+            200,
+            "text/event-stream",
+            chat_stream(&[
+                "[\"Roses\", \"Roses need pruning.\"]\n",
+                "[\"Tulips\", \"Tulips bloom.\"]\n",
+            ]),
+        ),
+        (
+            200,
+            "text/event-stream",
+            chat_stream(&["[\"Dahlias\", \"Dahlias must be lifted.\"]\n"]),
+        ),
+    ]);
+    use_openai(temp_path, port);
+
+    bok(temp_path)
+        .args(["import", "garden.md"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Successfully imported 6 parts from 'garden.md'.",
+        ))
+        .stdout(predicate::str::contains(
+            "Reconstructed content matches original",
+        ))
+        .stdout(predicate::str::contains("Added 1 node(s) to the book"));
+    let requests = server.join().unwrap();
+
+    // Only the leaf sections went to the LLM, without their headings
+    assert!(requests[0].contains("Tulips bloom."));
+    assert!(!requests[0].contains("## Spring"));
+    assert!(!requests[0].contains("Notes from the garden."));
+    assert!(requests[1].contains("Dahlias must be lifted."));
+
+    assert_eq!(
+        fs::read_to_string(node_text_path(temp_path, "Garden"))?,
+        "# Garden\n\nNotes from the garden."
+    );
+    assert_eq!(
+        fs::read_to_string(node_text_path(temp_path, "Spring"))?,
+        "## Spring"
+    );
+    assert_eq!(parent_dir_name(temp_path, "Garden"), "");
+    assert!(parent_dir_name(temp_path, "Spring").ends_with(" Garden"));
+    assert!(parent_dir_name(temp_path, "Autumn").ends_with(" Garden"));
+    assert!(parent_dir_name(temp_path, "Roses").ends_with(" Spring"));
+    assert!(parent_dir_name(temp_path, "Tulips").ends_with(" Spring"));
+    assert!(parent_dir_name(temp_path, "Dahlias").ends_with(" Autumn"));
+
+    Ok(())
+}
+
+#[test]
+fn test_import_without_llm_splits_headings_and_paragraphs() -> Result<(), Box<dyn std::error::Error>>
+{
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+    init_book(temp_path, "Book", &[]);
+    fs::write(temp_path.join("garden.md"), OUTLINED)?;
+
+    bok(temp_path)
+        .args(["import", "garden.md", "--no-llm"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("provider").not())
+        .stdout(predicate::str::contains(
+            "Validation successful: Reconstructed content matches original up to whitespace.",
+        ));
+
+    assert!(parent_dir_name(temp_path, "Roses need pruning.").ends_with(" Spring"));
+    assert!(parent_dir_name(temp_path, "Tulips bloom.").ends_with(" Spring"));
+    assert!(parent_dir_name(temp_path, "Dahlias must be lifted.").ends_with(" Autumn"));
+
+    Ok(())
+}
+
+#[test]
+fn test_flat_import_creates_siblings() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+    init_book(temp_path, "Book", &[]);
+    fs::write(temp_path.join("garden.md"), OUTLINED)?;
+
+    bok(temp_path)
+        .args(["import", "garden.md", "--no-llm", "--flat"])
+        .assert()
+        .success();
+
+    for blurb in [
+        "Garden",
+        "Notes from the garden.",
+        "Spring",
+        "Roses need pruning.",
+        "Tulips bloom.",
+        "Autumn",
+        "Dahlias must be lifted.",
+    ] {
+        assert_eq!(parent_dir_name(temp_path, blurb), "", "{} is nested", blurb);
+    }
+
+    Ok(())
+}
+
+/// A notes folder: a file per note, a nested folder and Obsidian's settings
+fn write_vault(dir: &Path) {
+    let vault = dir.join("vault");
+    fs::create_dir_all(vault.join("People/Friends")).unwrap();
+    fs::create_dir_all(vault.join(".obsidian")).unwrap();
+    fs::write(vault.join("Inbox.md"), "Buy seeds.\n\nCall the plumber.\n").unwrap();
+    fs::write(
+        vault.join("People/Friends/Alice.md"),
+        "# Alice\n\nMet at school.\n",
+    )
+    .unwrap();
+    fs::write(vault.join("People/Bob.qmd"), "Works at the bakery.\n").unwrap();
+    fs::write(vault.join(".obsidian/workspace.md"), "Not a note.\n").unwrap();
+    fs::write(vault.join("photo.png"), "").unwrap();
+}
+
+#[test]
+fn test_import_directory_maps_folders_to_nodes() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+    init_book(temp_path, "Book", &[]);
+    write_vault(temp_path);
+
+    bok(temp_path)
+        .args(["import", "vault"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "'vault' is a directory, import it with --recursive.",
+        ));
+
+    bok(temp_path)
+        .args(["import", "vault", "--recursive", "--no-llm"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Importing 3 file(s) from 'vault'.",
+        ))
+        .stdout(predicate::str::is_match(
+            r"\[\d/3\] People/Bob.qmd: 1 part\(s\)",
+        )?)
+        .stdout(predicate::str::contains(
+            "  Inbox.md: 2 part(s), matches the original up to whitespace",
+        ))
+        .stdout(predicate::str::contains("The report is in"));
+
+    assert_eq!(parent_dir_name(temp_path, "Inbox"), "");
+    assert!(parent_dir_name(temp_path, "Buy seeds.").ends_with(" Inbox"));
+    assert_eq!(parent_dir_name(temp_path, "People"), "");
+    assert!(parent_dir_name(temp_path, "Bob").ends_with(" People"));
+    assert_eq!(
+        fs::read_to_string(node_text_path(temp_path, "Bob"))?,
+        "Works at the bakery."
+    );
+    assert!(parent_dir_name(temp_path, "Friends").ends_with(" People"));
+    // A note with a single heading is not wrapped in a node for the file
+    assert!(parent_dir_name(temp_path, "Alice").ends_with(" Friends"));
+    // The heading stays in the text for validation, generated books show it only once
+    assert_eq!(
+        fs::read_to_string(node_text_path(temp_path, "Alice"))?,
+        "# Alice"
+    );
+    assert!(parent_dir_name(temp_path, "Met at school.").ends_with(" Alice"));
+    let meta = |blurb: &str| {
+        fs::read_to_string(node_text_path(temp_path, blurb).with_file_name("meta.yaml")).unwrap()
+    };
+    assert!(meta("Met at school.").contains("no_heading: true"));
+    assert!(!meta("Alice").contains("no_heading"));
+    assert_eq!(node_id(temp_path, "workspace"), "");
+
+    let ids = import_ids(temp_path);
+    let manifest = fs::read_to_string(
+        temp_path
+            .join(".bok/imports")
+            .join(&ids[0])
+            .join("import.yaml"),
+    )?;
+    assert!(manifest.contains("file: People/Friends/Alice.md"));
+    assert!(manifest.contains("outcome: whitespace"));
+
+    Ok(())
+}
+
+#[test]
+fn test_import_directory_concurrently() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+    init_book(temp_path, "Book", &[]);
+    use_dummy_llm(temp_path);
+    let notes = temp_path.join("notes");
+    fs::create_dir_all(&notes)?;
+    for i in 1..=6 {
+        fs::write(
+            notes.join(format!("note{}.md", i)),
+            format!("Note {} first.\n\nNote {} second.", i, i),
+        )?;
+    }
+
+    bok(temp_path)
+        .args(["import", "notes", "--recursive", "--jobs", "3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[6/6]"))
+        .stdout(predicate::str::contains(
+            "  note6.md: 2 part(s), matches the original",
+        ))
+        .stdout(predicate::str::contains("Added 6 node(s) to the book"));
+
+    for i in 1..=6 {
+        assert!(
+            parent_dir_name(temp_path, &format!("Note {} second.", i))
+                .ends_with(&format!(" note{}", i))
+        );
+    }
+
+    Ok(())
+}
+
 #[cfg(feature = "ollama_tests")]
 #[test]
 fn test_import_command_with_ollama_provider() -> Result<(), Box<dyn std::error::Error>> {
@@ -108,20 +664,18 @@ fn test_import_command_with_ollama_provider() -> Result<(), Box<dyn std::error::
     for entry in fs::read_dir(&current_dir)? {
         let entry = entry?;
         let path = entry.path();
-        if path.is_dir() {
-            if let Some(dir_name) = path.file_name().and_then(|s| s.to_str()) {
-                if dir_name
-                    .split_whitespace()
-                    .next()
-                    .unwrap_or("")
-                    .parse::<u32>()
-                    .is_ok()
-                {
-                    node_count += 1;
-                    assert!(path.join("meta.yaml").exists());
-                    assert!(path.join("text.qmd").exists());
-                }
-            }
+        if path.is_dir()
+            && let Some(dir_name) = path.file_name().and_then(|s| s.to_str())
+            && dir_name
+                .split_whitespace()
+                .next()
+                .unwrap_or("")
+                .parse::<u32>()
+                .is_ok()
+        {
+            node_count += 1;
+            assert!(path.join("meta.yaml").exists());
+            assert!(path.join("text.qmd").exists());
         }
     }
     assert_eq!(node_count, 3, "Expected 3 nodes to be created.");
@@ -132,20 +686,113 @@ fn test_import_command_with_ollama_provider() -> Result<(), Box<dyn std::error::
     for entry in fs::read_dir(&current_dir)? {
         let entry = entry?;
         let path = entry.path();
-        if path.is_dir() {
-            if let Some(dir_name) = path.file_name().and_then(|s| s.to_str()) {
-                if dir_name
-                    .split_whitespace()
-                    .next()
-                    .unwrap_or("")
-                    .parse::<u32>()
-                    .is_ok()
-                {
-                    fs::remove_dir_all(&path)?;
-                }
-            }
+        if path.is_dir()
+            && let Some(dir_name) = path.file_name().and_then(|s| s.to_str())
+            && dir_name
+                .split_whitespace()
+                .next()
+                .unwrap_or("")
+                .parse::<u32>()
+                .is_ok()
+        {
+            fs::remove_dir_all(&path)?;
         }
     }
 
     Ok(())
 }
+
+fn write_obsidian_vault(dir: &Path) {
+    let vault = dir.join("vault");
+    fs::create_dir_all(vault.join("attachments")).unwrap();
+    fs::write(
+        vault.join("Garden.md"),
+        "---\ntags: [plants, home]\naliases: [Yard]\n---\n# Garden\n\nTomatoes need sun, see [[Soil#Compost|compost]] and [[Weather]].\n\n![[tomato.png|200]]\n\n#todo #summer\n",
+    )
+    .unwrap();
+    fs::write(
+        vault.join("Soil.md"),
+        "# Soil\n\nLoam is best for the [[Yard]]. #dirt\n\n## Compost\n\nLeaves and peels.\n",
+    )
+    .unwrap();
+    fs::write(vault.join("attachments/tomato.png"), "png").unwrap();
+    // An image of the same name beside the note embedding it
+    fs::create_dir_all(vault.join("beds")).unwrap();
+    fs::write(
+        vault.join("beds/Bed.md"),
+        "Sun. ![[tomato.png]] ![[attachments/tomato.png]]\n\nShade.\n",
+    )
+    .unwrap();
+    fs::write(vault.join("beds/tomato.png"), "bed png").unwrap();
+}
+
+#[test]
+fn test_import_obsidian_vault() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+    init_book(temp_path, "Book", &[]);
+    write_obsidian_vault(temp_path);
+
+    bok(temp_path)
+        .args(["import", "vault", "--recursive", "--no-llm", "--obsidian"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Converted 2 wiki-link(s) to relations and copied 3 embedded file(s).",
+        ))
+        .stdout(predicate::str::contains(
+            "  Garden.md: 3 part(s), matches the original up to whitespace",
+        ))
+        .stderr(predicate::str::contains(
+            "'Garden.md' links to 'Weather', which was not imported",
+        ))
+        .stderr(predicate::str::contains(
+            "'Garden.md' embeds 'tomato.png', which matches 2 files",
+        ));
+
+    let sentence = "Tomatoes need sun, see compost and Weather.";
+    assert_eq!(
+        fs::read_to_string(node_text_path(temp_path, sentence))?,
+        sentence
+    );
+    let sentence_meta =
+        fs::read_to_string(node_text_path(temp_path, sentence).with_file_name("meta.yaml"))?;
+    assert!(sentence_meta.contains(&format!("target: '{}'", node_id(temp_path, "Compost"))));
+    assert!(sentence_meta.contains("kind: see-also"));
+
+    let garden_meta =
+        fs::read_to_string(node_text_path(temp_path, "Garden").with_file_name("meta.yaml"))?;
+    assert!(garden_meta.contains("tags:\n  - plants\n  - home\n  - todo\n  - summer\n"));
+
+    let soil_dir = node_text_path(temp_path, "Soil");
+    let soil = fs::read_to_string(&soil_dir)?;
+    assert!(soil.contains("Loam is best for the Yard. dirt"));
+    assert!(!soil.contains("[["));
+    let soil_meta = fs::read_to_string(soil_dir.with_file_name("meta.yaml"))?;
+    assert!(soil_meta.contains(&format!("target: '{}'", node_id(temp_path, "Garden"))));
+    assert!(soil_meta.contains("  - dirt"));
+
+    let image = node_text_path(temp_path, "tomato.png");
+    assert_eq!(fs::read_to_string(&image)?, "![](tomato.png){width=200}");
+    assert_eq!(
+        fs::read_to_string(image.with_file_name("tomato.png"))?,
+        "png"
+    );
+
+    // Embeds resolve beside the note first, a second file of the name is renamed
+    let sun = node_text_path(temp_path, "Sun.");
+    assert_eq!(
+        fs::read_to_string(&sun)?,
+        "Sun. ![](tomato.png) ![](tomato-2.png)"
+    );
+    assert_eq!(
+        fs::read_to_string(sun.with_file_name("tomato.png"))?,
+        "bed png"
+    );
+    assert_eq!(
+        fs::read_to_string(sun.with_file_name("tomato-2.png"))?,
+        "png"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use assert_cmd::prelude::*;
+use common::{bok, init_book, node_arg, node_text_path, use_dummy_llm};
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+/// Two paragraphs the dummy provider edits, far enough apart to end up in separate hunks
+const TEXT: &str = "first  paragraph .\n\n1\n2\n3\n4\n5\n6\n7\n\nsecond paragraph\n";
+
+#[test]
+fn test_lineedit_reviews_hunks() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[("Book", "Chapter")]);
+    use_dummy_llm(temp_path);
+    let text_path = node_text_path(temp_path, "Chapter");
+    fs::write(&text_path, TEXT)?;
+
+    assert_cmd::Command::from_std(bok(temp_path))
+        .args(["lineedit", &node_arg(temp_path, "Chapter")])
+        .write_stdin("y\nn\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("+First paragraph."))
+        .stdout(predicate::str::contains("Applied 1 of 2 hunks"));
+
+    assert_eq!(
+        fs::read_to_string(&text_path)?,
+        TEXT.replace("first  paragraph .", "First paragraph.")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_lineedit_propose_and_review_later() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[("Book", "Chapter")]);
+    use_dummy_llm(temp_path);
+    let text_path = node_text_path(temp_path, "Chapter");
+    let proposed_path = text_path.with_file_name("text.qmd.proposed");
+    fs::write(&text_path, TEXT)?;
+    let chapter = node_arg(temp_path, "Chapter");
+
+    bok(temp_path)
+        .args(["lineedit", &chapter, "--propose"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&text_path)?, TEXT);
+    assert!(proposed_path.exists());
+
+    // Without answers nothing is applied and the proposal is kept
+    assert_cmd::Command::from_std(bok(temp_path))
+        .args(["lineedit", &chapter, "--review"])
+        .write_stdin("")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Applied 0 of 2 hunks"));
+    assert_eq!(fs::read_to_string(&text_path)?, TEXT);
+    assert!(proposed_path.exists());
+
+    bok(temp_path)
+        .args(["lineedit", &chapter, "--review", "--accept"])
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&text_path)?,
+        TEXT.replace("first  paragraph .", "First paragraph.")
+            .replace("second", "Second")
+    );
+    assert!(!proposed_path.exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_lineedit_review_refuses_changed_text() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[("Book", "Chapter")]);
+    use_dummy_llm(temp_path);
+    let text_path = node_text_path(temp_path, "Chapter");
+    fs::write(&text_path, TEXT)?;
+    let chapter = node_arg(temp_path, "Chapter");
+
+    bok(temp_path)
+        .args(["lineedit", &chapter, "--propose"])
+        .assert()
+        .success();
+    let edited = TEXT.replace("7\n", "7\n8\n");
+    fs::write(&text_path, &edited)?;
+
+    bok(temp_path)
+        .args(["lineedit", &chapter, "--review", "--accept"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "changed since the edits were proposed",
+        ));
+    assert_eq!(fs::read_to_string(&text_path)?, edited);
+
+    Ok(())
+}
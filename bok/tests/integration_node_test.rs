@@ -0,0 +1,554 @@
+mod common;
+
+use assert_cmd::prelude::*;
+use common::{bok, init_book, node_arg, node_id, node_text_path};
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_node_link_and_unlink() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[("Book", "Idea"), ("Book", "Example")]);
+    let idea_id = node_id(temp_path, "Idea");
+    let example_id = node_id(temp_path, "Example");
+
+    bok(temp_path)
+        .args([
+            "node",
+            "link",
+            &node_arg(temp_path, "Example"),
+            &node_arg(temp_path, "Idea"),
+        ])
+        .args(["--kind", "example-of"])
+        .assert()
+        .success();
+
+    let meta = fs::read_to_string(
+        temp_path
+            .join(format!("{} Book", node_id(temp_path, "Book")))
+            .join(format!("{} Example", example_id))
+            .join("meta.yaml"),
+    )?;
+    assert!(meta.contains(&idea_id));
+    assert!(meta.contains("kind: example-of"));
+
+    bok(temp_path)
+        .args(["node", "ls"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!(
+            "{} Example [example-of: {}]",
+            example_id, idea_id
+        )));
+
+    bok(temp_path)
+        .args(["vis", "mermaid"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(format!(
+            "n{} ==>|example-of| n{}",
+            example_id, idea_id
+        )));
+
+    bok(temp_path)
+        .args([
+            "node",
+            "unlink",
+            &node_arg(temp_path, "Example"),
+            &node_arg(temp_path, "Idea"),
+        ])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Removed 1 relation(s)"));
+
+    bok(temp_path)
+        .args(["node", "ls"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("[example-of").not());
+
+    Ok(())
+}
+
+/// Children of `parent` in `node ls` order
+fn listed_children(dir: &std::path::Path, parent: &str) -> Vec<String> {
+    let output = bok(dir).args(["node", "ls"]).output().unwrap();
+    let listing = String::from_utf8(output.stdout).unwrap();
+    let mut lines = listing
+        .lines()
+        .skip_while(|l| !l.ends_with(&format!(" {}", parent)));
+    let parent_line = lines.next().unwrap();
+    let indent = parent_line.len() - parent_line.trim_start().len() + 2;
+    lines
+        .take_while(|l| l.len() - l.trim_start().len() >= indent)
+        .filter(|l| l.len() - l.trim_start().len() == indent)
+        .map(|l| l.trim().split_once(' ').unwrap().1)
+        .map(|l| l.split(" [").next().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn test_node_mv_reorders_and_reparents() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(
+        temp_path,
+        "Book",
+        &[("Book", "A"), ("Book", "B"), ("Book", "C")],
+    );
+    let mv = |args: &[&str]| {
+        bok(temp_path)
+            .args(["node", "mv"])
+            .args(args)
+            .assert()
+            .success();
+    };
+    let a = node_arg(temp_path, "A");
+    let b = node_arg(temp_path, "B");
+    let c = node_arg(temp_path, "C");
+
+    mv(&[&b, "--after", &a]);
+    mv(&[&c, "--after", &b]);
+    assert_eq!(listed_children(temp_path, "Book"), ["A", "B", "C"]);
+
+    mv(&[&c, "--first"]);
+    assert_eq!(listed_children(temp_path, "Book"), ["C", "A", "B"]);
+
+    // Moving A away must make B follow C
+    mv(&[&a, "--under", &c]);
+    assert_eq!(listed_children(temp_path, "Book"), ["C", "B"]);
+    assert_eq!(listed_children(temp_path, "C"), ["A"]);
+
+    bok(temp_path)
+        .args(["node", "mv", &c, "--under", &a])
+        .assert()
+        .stderr(predicates::str::contains("into its own subtree"));
+    assert_eq!(listed_children(temp_path, "C"), ["A"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_node_ids_survive_collisions_and_renames() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[("Book", "Same"), ("Book", "Same")]);
+    let book_dir = temp_path.join(format!("{} Book", node_id(temp_path, "Book")));
+    let same_dirs: Vec<_> = fs::read_dir(&book_dir)?
+        .filter_map(Result::ok)
+        .filter(|e| e.file_name().to_string_lossy().ends_with(" Same"))
+        .collect();
+    assert_eq!(
+        same_dirs.len(),
+        2,
+        "Nodes with the same blurb must not merge"
+    );
+
+    let same_name = same_dirs[0].file_name().to_string_lossy().to_string();
+    let same_id = same_name.split_once(' ').unwrap().0.to_string();
+    bok(temp_path)
+        .args([
+            "node",
+            "rename",
+            &format!("{}-Same", same_id),
+            "Different",
+            "now",
+        ])
+        .assert()
+        .success();
+
+    let renamed = book_dir.join(format!("{} Different now", same_id));
+    assert!(renamed.is_dir());
+    assert!(fs::read_to_string(renamed.join("meta.yaml"))?.contains("title: Different now"));
+    assert_eq!(node_id(temp_path, "Different now"), same_id);
+
+    Ok(())
+}
+
+#[test]
+fn test_init_rejects_blurb_with_path_separator() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    bok(temp_path)
+        .args(["init", "a/b"])
+        .assert()
+        .code(1)
+        .stderr(predicates::str::contains(
+            "Blurb 'a/b' cannot contain path separators.",
+        ));
+    assert_eq!(fs::read_dir(temp_path)?.count(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_node_rm_and_restore() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(
+        temp_path,
+        "Book",
+        &[
+            ("Book", "A"),
+            ("Book", "B"),
+            ("Book", "C"),
+            ("B", "B child"),
+        ],
+    );
+    let a = node_arg(temp_path, "A");
+    let b = node_arg(temp_path, "B");
+    let c = node_arg(temp_path, "C");
+    let b_id = node_id(temp_path, "B");
+    for (node, after) in [(&b, &a), (&c, &b)] {
+        bok(temp_path)
+            .args(["node", "mv", node, "--after", after])
+            .assert()
+            .success();
+    }
+    bok(temp_path)
+        .args(["node", "link", &a, &b])
+        .assert()
+        .success();
+
+    // Dry run and a declined prompt leave everything in place
+    bok(temp_path)
+        .args(["node", "rm", &b, "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("B child"))
+        .stdout(predicates::str::contains(format!("-> {} (see-also)", b_id)));
+    assert_cmd::Command::from_std(bok(temp_path))
+        .args(["node", "rm", &b])
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Aborted."));
+    assert_eq!(listed_children(temp_path, "Book"), ["A", "B", "C"]);
+
+    bok(temp_path)
+        .args(["node", "rm", &b, "--yes"])
+        .assert()
+        .success();
+    assert_eq!(listed_children(temp_path, "Book"), ["A", "C"]);
+    bok(temp_path)
+        .args(["node", "ls"])
+        .assert()
+        .stdout(predicates::str::contains("see-also").not());
+
+    bok(temp_path)
+        .args(["node", "restore", &b_id])
+        .assert()
+        .success();
+    assert_eq!(listed_children(temp_path, "Book"), ["A", "B", "C"]);
+    assert_eq!(listed_children(temp_path, "B"), ["B child"]);
+    bok(temp_path)
+        .args(["node", "ls"])
+        .assert()
+        .stdout(predicates::str::contains(format!("[see-also: {}]", b_id)));
+
+    Ok(())
+}
+
+#[test]
+fn test_node_rm_keeps_chain_of_linking_follower() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(
+        temp_path,
+        "Book",
+        &[("Book", "A"), ("Book", "B"), ("Book", "C")],
+    );
+    let a = node_arg(temp_path, "A");
+    let b = node_arg(temp_path, "B");
+    let c = node_arg(temp_path, "C");
+    for (node, after) in [(&b, &a), (&c, &b)] {
+        bok(temp_path)
+            .args(["node", "mv", node, "--after", after])
+            .assert()
+            .success();
+    }
+    // C follows B and relates to it, both have to change
+    bok(temp_path)
+        .args(["node", "link", &c, &b])
+        .assert()
+        .success();
+
+    bok(temp_path)
+        .args(["node", "rm", &b, "--yes"])
+        .assert()
+        .success();
+
+    let c_meta = fs::read_to_string(node_text_path(temp_path, "C").with_file_name("meta.yaml"))?;
+    assert!(c_meta.contains(&format!("after: '{}'", node_id(temp_path, "A"))));
+    assert!(!c_meta.contains("see-also"));
+    bok(temp_path)
+        .args(["check", "--only", "dangling-after,dangling-relations"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("No problems found."));
+
+    Ok(())
+}
+
+#[test]
+fn test_node_rm_requires_exact_id() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[("Book", "Keep me")]);
+    let keep_id = node_id(temp_path, "Keep me");
+
+    // A node whose id is a prefix of the other one's, a prefix match would find both
+    let prefix = &keep_id[..2];
+    let decoy = node_text_path(temp_path, "Keep me")
+        .parent()
+        .unwrap()
+        .with_file_name(format!("{} Decoy", prefix));
+    fs::create_dir(&decoy)?;
+    fs::write(
+        decoy.join("meta.yaml"),
+        format!("meta:\n  title: Decoy\n  after: '{}'\n", keep_id),
+    )?;
+    fs::write(decoy.join("text.qmd"), "")?;
+    assert_eq!(listed_children(temp_path, "Book"), ["Keep me", "Decoy"]);
+
+    bok(temp_path)
+        .args(["node", "rm", &format!("{}-Decoy", prefix), "--yes"])
+        .assert()
+        .success();
+    assert_eq!(listed_children(temp_path, "Book"), ["Keep me"]);
+    assert!(!decoy.exists());
+
+    Ok(())
+}
+
+/// Push the mtime of every directory below `dir` an hour into the past
+fn backdate_dirs(dir: &std::path::Path) {
+    let an_hour_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+    for entry in fs::read_dir(dir).unwrap().filter_map(Result::ok) {
+        if entry.path().is_dir() {
+            backdate_dirs(&entry.path());
+        }
+    }
+    fs::File::open(dir)
+        .unwrap()
+        .set_modified(an_hour_ago)
+        .unwrap();
+}
+
+#[test]
+fn test_node_index_follows_external_changes() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(
+        temp_path,
+        "Book",
+        &[("Book", "One"), ("Book", "Two"), ("One", "Leaf")],
+    );
+    let book_dir = temp_path.join(format!("{} Book", node_id(temp_path, "Book")));
+    let one_dir = book_dir.join(format!("{} One", node_id(temp_path, "One")));
+    let two_dir = book_dir.join(format!("{} Two", node_id(temp_path, "Two")));
+
+    // Old mtimes make the index trust its cached directories
+    backdate_dirs(temp_path);
+    bok(temp_path).args(["node", "ls"]).assert().success();
+    assert!(temp_path.join(".bok/index").exists());
+
+    // Moved and created behind bok's back
+    let leaf_dir = format!("{} Leaf", node_id(temp_path, "Leaf"));
+    fs::rename(one_dir.join(&leaf_dir), two_dir.join(&leaf_dir))?;
+    fs::create_dir(two_dir.join("42 Handmade"))?;
+    fs::write(
+        two_dir.join("42 Handmade/meta.yaml"),
+        "meta:\n  title: Handmade\n",
+    )?;
+    fs::write(two_dir.join("42 Handmade/text.qmd"), "Handmade")?;
+
+    bok(temp_path)
+        .args([
+            "node",
+            "rename",
+            &node_arg(temp_path, "Leaf"),
+            "Fallen",
+            "leaf",
+        ])
+        .assert()
+        .success();
+    assert!(
+        two_dir
+            .join(format!("{} Fallen leaf", node_id(temp_path, "Fallen leaf")))
+            .exists()
+    );
+
+    bok(temp_path)
+        .args(["node", "rename", "42-Handmade", "Crafted"])
+        .assert()
+        .success();
+    assert!(two_dir.join("42 Crafted").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_book_root_is_found_from_anywhere() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let book_path = temp_dir.path().join("book");
+    fs::create_dir(&book_path)?;
+
+    init_book(&book_path, "Book", &[("Book", "Chapter")]);
+    let chapter_dir = book_path
+        .join(format!("{} Book", node_id(&book_path, "Book")))
+        .join(format!("{} Chapter", node_id(&book_path, "Chapter")));
+
+    // From inside a chapter the book is found by walking up
+    bok(&chapter_dir)
+        .args([
+            "node",
+            "add",
+            "--under",
+            &node_arg(&book_path, "Chapter"),
+            "Deep",
+        ])
+        .assert()
+        .success();
+    assert!(
+        chapter_dir
+            .join(format!("{} Deep", node_id(&book_path, "Deep")))
+            .exists()
+    );
+    assert!(!chapter_dir.join("bok.yaml").exists());
+    assert!(!chapter_dir.join(".bok").exists());
+
+    bok(&chapter_dir)
+        .args(["node", "ls"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Deep"));
+
+    // Outside of the book it has to be pointed at explicitly
+    bok(temp_dir.path())
+        .args(["node", "ls"])
+        .assert()
+        .stderr(predicate::str::contains("bok.yaml"));
+    bok(temp_dir.path())
+        .args(["--book", "book", "node", "ls"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Chapter"));
+    // Node values are checked against that book, wherever --book is given
+    bok(temp_dir.path())
+        .args([
+            "node",
+            "mv",
+            &node_arg(&book_path, "Deep"),
+            "--book=book",
+            "--first",
+        ])
+        .assert()
+        .success();
+    bok(temp_dir.path())
+        .env("BOK_ROOT", &book_path)
+        .args(["node", "ls"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Chapter"));
+
+    Ok(())
+}
+
+#[test]
+fn test_node_merge() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(
+        temp_path,
+        "Book",
+        &[
+            ("Book", "A"),
+            ("Book", "B"),
+            ("Book", "C"),
+            ("A", "A1"),
+            ("B", "B1"),
+            ("B", "B2"),
+        ],
+    );
+    let arg = |blurb: &str| node_arg(temp_path, blurb);
+    let run = |args: &[&str]| {
+        bok(temp_path).args(args).assert().success();
+    };
+    run(&["node", "mv", &arg("B"), "--after", &arg("A")]);
+    run(&["node", "mv", &arg("C"), "--after", &arg("B")]);
+    run(&["node", "mv", &arg("B2"), "--after", &arg("B1")]);
+    run(&["node", "link", &arg("C"), &arg("B")]);
+    run(&["node", "link", &arg("B"), &arg("A")]);
+    fs::write(common::node_text_path(temp_path, "B"), "Text of B.\n")?;
+    let a_id = node_id(temp_path, "A");
+    let b_id = node_id(temp_path, "B");
+
+    run(&["node", "merge", &arg("A"), &arg("B")]);
+
+    assert_eq!(listed_children(temp_path, "Book"), ["A", "C"]);
+    assert_eq!(listed_children(temp_path, "A"), ["A1", "B1", "B2"]);
+    assert_eq!(
+        fs::read_to_string(common::node_text_path(temp_path, "A"))?,
+        "A\n\nText of B.\n"
+    );
+    let c_meta =
+        fs::read_to_string(common::node_text_path(temp_path, "C").with_file_name("meta.yaml"))?;
+    assert!(c_meta.contains(&format!("target: '{}'", a_id)));
+    // B's relation to A would now be a relation of A to itself
+    let a_meta =
+        fs::read_to_string(common::node_text_path(temp_path, "A").with_file_name("meta.yaml"))?;
+    assert!(!a_meta.contains("relations"));
+
+    // Restoring B points C's relation back at it
+    run(&["node", "restore", &b_id]);
+    let c_meta =
+        fs::read_to_string(common::node_text_path(temp_path, "C").with_file_name("meta.yaml"))?;
+    assert!(c_meta.contains(&format!("target: '{}'", b_id)));
+    assert!(!c_meta.contains(&format!("target: '{}'", a_id)));
+
+    // Nothing changes when a child cannot move
+    run(&["node", "add", "B1", "--under", &arg("C")]);
+    let c1_dir = common::node_text_path(temp_path, "C")
+        .parent()
+        .unwrap()
+        .read_dir()?
+        .filter_map(Result::ok)
+        .find(|entry| entry.path().is_dir())
+        .unwrap()
+        .path();
+    let a_dir = common::node_text_path(temp_path, "A")
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    fs::create_dir(a_dir.join(c1_dir.file_name().unwrap()))?;
+    let c_text = fs::read_to_string(common::node_text_path(temp_path, "C"))?;
+    bok(temp_path)
+        .args(["node", "merge", &arg("A"), &arg("C")])
+        .assert()
+        .stderr(predicate::str::contains("already exists"));
+    assert!(c1_dir.exists());
+    assert_eq!(
+        fs::read_to_string(common::node_text_path(temp_path, "C"))?,
+        c_text
+    );
+    assert_eq!(listed_children(temp_path, "Book"), ["A", "B", "C"]);
+
+    bok(temp_path)
+        .args(["node", "merge", &arg("A"), &arg("A1")])
+        .assert()
+        .stderr(predicate::str::contains("one contains the other"));
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use assert_cmd::prelude::*;
+use common::{bok, chat_stream, init_book, mock_server, node_id, node_text_path, use_openai};
+use predicates::prelude::*;
+use serde_json::json;
+use std::fs;
+use tempfile::tempdir;
+
+/// The JSON body of a raw HTTP request
+fn request_body(request: &str) -> serde_json::Value {
+    let (_, body) = request.split_once("\r\n\r\n").unwrap();
+    serde_json::from_str(body).unwrap()
+}
+
+#[test]
+fn test_openai_import_streams_units() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[]);
+    let stream = chat_stream(&[
+        "[\"Roses need pruning\", \"Prune ",
+        "roses in spring.\"]\n\n[\"Tulips",
+        "\", \"Plant tulips in autumn.\"]\n",
+    ]);
+    let (port, server) = mock_server(vec![(200, "text/event-stream", stream)]);
+    use_openai(temp_path, port);
+    fs::write(
+        temp_path.join("garden.md"),
+        "Prune roses in spring.\n\nPlant tulips in autumn.",
+    )?;
+
+    bok(temp_path)
+        .args(["import", "garden.md"])
+        .env_remove("OPENAI_API_KEY")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Using OpenAI provider."))
+        .stdout(predicate::str::contains(
+            "Successfully imported 2 parts from 'garden.md'.",
+        ));
+
+    assert_eq!(
+        fs::read_to_string(node_text_path(temp_path, "Tulips"))?,
+        "Plant tulips in autumn."
+    );
+
+    let requests = server.join().unwrap();
+    // A base URL ending in /v1 works as well, local servers usually document it that way
+    assert!(requests[0].starts_with("POST /v1/chat/completions "));
+    assert!(!requests[0].to_lowercase().contains("authorization:"));
+    let body = request_body(&requests[0]);
+    assert_eq!(body["model"], "local-model");
+    assert_eq!(body["stream"], true);
+    assert_eq!(body["messages"][0]["role"], "system");
+    assert!(body.get("response_format").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_openai_falls_back_without_json_mode() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[("Book", "Gardening basics")]);
+    fs::write(
+        node_text_path(temp_path, "Gardening basics"),
+        "How to file your taxes.",
+    )?;
+    let rejected = json!({"error": {"message": "response_format is not supported"}});
+    let answer = json!({
+        "choices": [{
+            "index": 0,
+            "message": {
+                "role": "assistant",
+                "content": "{\"fits\": false, \"reason\": \"the text is about taxes\"}"
+            },
+            "finish_reason": "stop"
+        }]
+    });
+    let (port, server) = mock_server(vec![
+        (400, "application/json", rejected.to_string()),
+        (200, "application/json", answer.to_string()),
+    ]);
+    use_openai(temp_path, port);
+
+    bok(temp_path)
+        .args(["check", "--llm", "--only", "blurb-fit"])
+        .env("OPENAI_API_KEY", "sk-test")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("does not support JSON mode"))
+        .stdout(predicate::str::contains("the text is about taxes"));
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].contains("authorization: Bearer sk-test"));
+    assert_eq!(
+        request_body(&requests[0])["response_format"]["type"],
+        "json_object"
+    );
+    assert!(request_body(&requests[1]).get("response_format").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_openai_reports_other_bad_requests() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[("Book", "Gardening basics")]);
+    fs::write(
+        node_text_path(temp_path, "Gardening basics"),
+        "How to file your taxes.",
+    )?;
+    let rejected = json!({"error": {"message": "This model's maximum context length is exceeded"}});
+    let (port, server) = mock_server(vec![(400, "application/json", rejected.to_string())]);
+    use_openai(temp_path, port);
+
+    bok(temp_path)
+        .args(["check", "--llm", "--only", "blurb-fit"])
+        .env("OPENAI_API_KEY", "sk-test")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("maximum context length"))
+        .stderr(predicate::str::contains("JSON mode").not());
+
+    assert_eq!(server.join().unwrap().len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_openai_embeddings() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(temp_path, "Book", &[("Book", "Roses")]);
+    // Returned out of order, the index says which input each one belongs to
+    let embeddings = json!({
+        "data": [
+            {"object": "embedding", "index": 1, "embedding": [0.0, 1.0]},
+            {"object": "embedding", "index": 0, "embedding": [1.0, 0.0]}
+        ]
+    });
+    let (port, server) = mock_server(vec![(200, "application/json", embeddings.to_string())]);
+    use_openai(temp_path, port);
+
+    bok(temp_path)
+        .arg("embed")
+        .env_remove("OPENAI_API_KEY")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Embedded 2 node(s), 0 unchanged."));
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].starts_with("POST /v1/embeddings "));
+    let body = request_body(&requests[0]);
+    assert_eq!(body["model"], "text-embedding-3-small");
+    let first_input = body["input"][0].as_str().unwrap();
+    let first = if first_input.starts_with("Roses") {
+        "Roses"
+    } else {
+        "Book"
+    };
+
+    let store: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(temp_path.join(".bok/embeddings.json"))?)?;
+    assert_eq!(
+        store["nodes"][node_id(temp_path, first)]["vector"],
+        json!([1.0, 0.0])
+    );
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use assert_cmd::prelude::*;
+use common::{bok, init_book, node_id, node_text_path, use_dummy_llm};
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_search() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(
+        temp_path,
+        "Book",
+        &[("Book", "Garden"), ("Garden", "Roses"), ("Book", "Kitchen")],
+    );
+    use_dummy_llm(temp_path);
+    fs::write(
+        node_text_path(temp_path, "Roses"),
+        "Most climbing roses flower twice. Prune them in early spring.",
+    )?;
+    fs::write(
+        node_text_path(temp_path, "Kitchen"),
+        "Rose hips make a good jelly.\nPrune nothing here.",
+    )?;
+
+    bok(temp_path)
+        .args(["search", "PRUNE", "roses"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(format!(
+            "{} Roses\n    Book > Garden > Roses\n    Most climbing roses flower twice. Prune them",
+            node_id(temp_path, "Roses")
+        )))
+        .stdout(predicate::str::contains("Kitchen").not());
+
+    let output = bok(temp_path)
+        .args(["search", "prune", "--json"])
+        .output()?;
+    assert!(output.status.success());
+    let results: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(results.as_array().unwrap().len(), 2);
+    assert_eq!(results[0]["tree"][0], "Book");
+    let blurb = results[0]["blurb"].as_str().unwrap();
+    assert!(results[0]["path"].as_str().unwrap().ends_with(blurb));
+
+    bok(temp_path)
+        .args(["search", "tulips"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No nodes match 'tulips'."));
+
+    // The dummy embeddings rank the node sharing most words first
+    let output = bok(temp_path)
+        .args([
+            "search",
+            "--semantic",
+            "--json",
+            "--limit",
+            "1",
+            "rose",
+            "hips",
+            "jelly",
+        ])
+        .output()?;
+    assert!(output.status.success());
+    let results: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(results.as_array().unwrap().len(), 1);
+    assert_eq!(results[0]["blurb"], "Kitchen");
+
+    Ok(())
+}
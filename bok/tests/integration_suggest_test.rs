@@ -0,0 +1,85 @@
+mod common;
+
+use assert_cmd::prelude::*;
+use common::{bok, init_book, node_arg, node_id, node_text_path, use_dummy_llm};
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_suggest_links() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(
+        temp_path,
+        "Book",
+        &[
+            ("Book", "Garden"),
+            ("Garden", "Roses"),
+            ("Book", "Kitchen"),
+            ("Kitchen", "Jelly"),
+        ],
+    );
+    use_dummy_llm(temp_path);
+    fs::write(
+        node_text_path(temp_path, "Roses"),
+        "Leave the faded roses on the bush in autumn to get plenty of hips.",
+    )?;
+    fs::write(
+        node_text_path(temp_path, "Jelly"),
+        "Rose hips from unsprayed roses make a tart jelly in autumn.",
+    )?;
+    fs::write(node_text_path(temp_path, "Garden"), "Outdoors.")?;
+    fs::write(node_text_path(temp_path, "Kitchen"), "Indoors.")?;
+    let roses_id = node_id(temp_path, "Roses");
+    let jelly_id = node_id(temp_path, "Jelly");
+
+    // The dummy provider's hashed embeddings give low similarities
+    let output = bok(temp_path)
+        .args(["suggest", "links", "--json", "--threshold", "0.2"])
+        .output()?;
+    assert!(output.status.success());
+    let suggestions: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(suggestions.as_array().unwrap().len(), 1);
+    let suggestion = &suggestions[0];
+    let mut pair = [
+        suggestion["source"].as_str().unwrap(),
+        suggestion["target"].as_str().unwrap(),
+    ];
+    pair.sort();
+    let mut expected = [roses_id.as_str(), jelly_id.as_str()];
+    expected.sort();
+    assert_eq!(pair, expected);
+    assert_eq!(suggestion["kind"], "see-also");
+    assert_eq!(suggestion["reason"], "both are about autumn, roses");
+
+    // Declined interactively, nothing changes
+    assert_cmd::Command::from_std(bok(temp_path))
+        .args(["suggest", "links", "--threshold", "0.2"])
+        .args(["--node", &node_arg(temp_path, "Jelly")])
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Added 0 of 1 relations."));
+
+    bok(temp_path)
+        .args(["suggest", "links", "--threshold", "0.2", "--accept"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Added 1 of 1 relations."));
+    let source = suggestion["source"].as_str().unwrap();
+    let target = suggestion["target"].as_str().unwrap();
+    let blurb = if source == roses_id { "Roses" } else { "Jelly" };
+    let meta = fs::read_to_string(node_text_path(temp_path, blurb).with_file_name("meta.yaml"))?;
+    assert!(meta.contains(&format!("target: '{}'", target)));
+
+    // Related nodes are not suggested again
+    bok(temp_path)
+        .args(["suggest", "links", "--threshold", "0.2", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No relations to suggest."));
+
+    Ok(())
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use assert_cmd::prelude::*;
+use common::{bok, init_book, node_arg, node_id};
+use tempfile::tempdir;
+
+#[test]
+fn test_vis_mermaid_depth_and_escaping() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(
+        temp_path,
+        "Book",
+        &[
+            ("Book", "Quotes \"and\" [brackets]: too"),
+            ("Book", "Plain"),
+            ("Plain", "Deep"),
+        ],
+    );
+    let book_id = node_id(temp_path, "Book");
+    let plain_id = node_id(temp_path, "Plain");
+    let deep_id = node_id(temp_path, "Deep");
+
+    let output = bok(temp_path).args(["vis", "mermaid"]).output()?;
+    let diagram = String::from_utf8(output.stdout)?;
+    assert!(diagram.starts_with("graph TD\n"));
+    assert!(diagram.contains("Quotes #quot;and#quot; #91;brackets#93;#58; too"));
+    assert!(diagram.contains(&format!("n{} --> n{}", plain_id, deep_id)));
+
+    let output = bok(temp_path)
+        .args(["vis", "mermaid", "--depth", "2"])
+        .output()?;
+    let diagram = String::from_utf8(output.stdout)?;
+    assert!(diagram.contains(&format!("n{} --> n{}", book_id, plain_id)));
+    assert!(!diagram.contains(&format!("n{}", deep_id)));
+
+    bok(temp_path)
+        .args([
+            "vis",
+            "mermaid",
+            "--mindmap",
+            "--root",
+            &node_arg(temp_path, "Plain"),
+        ])
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with(format!(
+            "mindmap\n  n{}[\"Plain\"]\n    n{}[\"Deep\"]\n",
+            plain_id, deep_id
+        )));
+
+    Ok(())
+}
+
+#[test]
+fn test_vis_dot_clusters_chapters() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(
+        temp_path,
+        "Book",
+        &[("Book", "Chapter \"one\""), ("Chapter \"one\"", "Section")],
+    );
+    let book_id = node_id(temp_path, "Book");
+    let chapter_id = node_id(temp_path, "Chapter \"one\"");
+    let section_id = node_id(temp_path, "Section");
+
+    let output = bok(temp_path).args(["vis", "dot"]).output()?;
+    let graph = String::from_utf8(output.stdout)?;
+    assert!(graph.starts_with("digraph bok {\n"));
+    assert!(graph.contains(&format!("subgraph cluster_{} {{", chapter_id)));
+    assert!(graph.contains("label=\"Chapter \\\"one\\\"\";"));
+    assert!(graph.contains(&format!("n{} -> n{};", book_id, chapter_id)));
+    assert!(graph.contains(&format!("n{} -> n{};", chapter_id, section_id)));
+
+    Ok(())
+}
+
+#[test]
+fn test_vis_d3_shapes() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = tempdir()?;
+    let temp_path = temp_dir.path();
+
+    init_book(
+        temp_path,
+        "Book",
+        &[("Book", "Chapter"), ("Chapter", "Section")],
+    );
+    let book_id = node_id(temp_path, "Book");
+    let chapter_id = node_id(temp_path, "Chapter");
+
+    let output = bok(temp_path)
+        .args(["vis", "d3", "--root", &node_arg(temp_path, "Book")])
+        .output()?;
+    let tree: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(tree["name"], "Book");
+    assert_eq!(tree["depth"], 1);
+    assert_eq!(tree["words"], 1);
+    assert_eq!(tree["children"][0]["name"], "Chapter");
+    assert_eq!(tree["children"][0]["children"][0]["depth"], 3);
+
+    let output = bok(temp_path)
+        .args(["vis", "d3", "--shape", "graph", "--depth", "2"])
+        .output()?;
+    let graph: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(graph["nodes"].as_array().unwrap().len(), 2);
+    assert_eq!(
+        graph["links"],
+        serde_json::json!([{"source": book_id, "target": chapter_id, "type": "child"}])
+    );
+
+    Ok(())
+}